@@ -5,9 +5,19 @@ use ink_lang as ink;
 #[ink::contract]
 mod product {
 
+    use ink_env::call::{
+        build_call,
+        ExecutionInput,
+        Selector,
+    };
+    use ink_prelude::{
+        boxed::Box,
+        string::String,
+        vec::Vec,
+    };
     use ink_storage::{
         collections::{
-            Vec as StorageVec,
+            HashMap as StorageHashMap,
         },
         traits::{
             PackedLayout,
@@ -15,7 +25,163 @@ mod product {
         },
     };
 
-    #[derive(Copy, Clone, scale::Encode, scale::Decode, SpreadLayout, PackedLayout)]
+    /// Lifecycle state of a product.
+    ///
+    /// The variant order is part of the encoded representation and must
+    /// not change, `Owned` encodes as `0`, `PendingDelegation` as `1`,
+    /// `Recalled` as `2`, `ComponentLocked` as `3`, `Disputed` as `4`,
+    /// `Listed` as `5`, `Auctioned` as `6`, `Lent` as `7` and
+    /// `PendingVerification` as `8`.
+    #[derive(Copy, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout)]
+    #[cfg_attr(
+        feature = "std",
+        derive(
+            Debug,
+            scale_info::TypeInfo,
+            ink_storage::traits::StorageLayout
+        )
+    )]
+    pub enum State {
+        Owned,
+        PendingDelegation,
+        /// Pulled back by the admin, e.g. as part of a defective batch. The
+        /// product stays visible but cannot change hands.
+        Recalled,
+        /// Built into a parent product, see `assemble`. It stays locked to
+        /// the parent and cannot change hands on its own.
+        ComponentLocked,
+        /// Claimed counterfeit after a handover, see `raise_dispute`. It
+        /// cannot change hands until the arbiter resolves the dispute.
+        Disputed,
+        /// Up for sale at a fixed price, see `list_for_sale`.
+        Listed,
+        /// Being auctioned, see `start_auction`.
+        Auctioned,
+        /// In the custody of a borrower, see `lend_product`. Neither party
+        /// can hand it over until it is returned or reclaimed.
+        Lent,
+        /// Waiting for the oracle to report on an inspection, see
+        /// `request_verification`.
+        PendingVerification,
+    }
+
+    /// Lifecycle actions a product can go through, see `State::transition`.
+    #[derive(Copy, Clone, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(Debug))]
+    pub enum Action {
+        Delegate,
+        Accept,
+        /// Ends a pending delegation through reject, revoke or expiry.
+        Cancel,
+        Transfer,
+        Burn,
+        Recall,
+        ResolveRecall,
+        /// Moves part of a lot into a new product.
+        Split,
+        /// Folds a lot into another one with the same code.
+        Merge,
+        /// Locks a product into a parent assembly.
+        Assemble,
+        /// Takes an assembled parent apart.
+        Disassemble,
+        /// Frees a component of a parent being taken apart.
+        Release,
+        Dispute,
+        ResolveDispute,
+        List,
+        Unlist,
+        Buy,
+        StartAuction,
+        FinalizeAuction,
+        /// Corrects the code of a product that never changed hands.
+        UpdateCode,
+        Lend,
+        /// Ends a loan, through the borrower returning the product or the
+        /// owner reclaiming it.
+        Return,
+        RequestVerification,
+        SubmitVerification,
+    }
+
+    impl State {
+        /// The single table of legal state transitions. Every message that
+        /// moves a product through its lifecycle validates against it, and
+        /// the `StoredProduct` mutators apply it.
+        pub fn transition(self, action: Action) -> Result<State> {
+            match (self, action) {
+                (State::Owned, Action::Delegate) => Ok(State::PendingDelegation),
+                (State::Owned, Action::Transfer) => Ok(State::Owned),
+                (State::Owned, Action::Burn) => Ok(State::Owned),
+                (State::Owned, Action::Split) => Ok(State::Owned),
+                (State::Owned, Action::Merge) => Ok(State::Owned),
+                (State::Owned, Action::Assemble) => Ok(State::ComponentLocked),
+                (State::Owned, Action::Disassemble) => Ok(State::Owned),
+                (State::ComponentLocked, Action::Release) => Ok(State::Owned),
+                (State::Owned, Action::Dispute) => Ok(State::Disputed),
+                (State::Disputed, Action::ResolveDispute) => Ok(State::Owned),
+                (State::Owned, Action::List) => Ok(State::Listed),
+                (State::Listed, Action::Unlist) => Ok(State::Owned),
+                (State::Listed, Action::Buy) => Ok(State::Owned),
+                (State::Listed, Action::Recall) => Ok(State::Recalled),
+                (State::Owned, Action::StartAuction) => Ok(State::Auctioned),
+                (State::Auctioned, Action::FinalizeAuction) => Ok(State::Owned),
+                (State::Owned, Action::UpdateCode) => Ok(State::Owned),
+                (State::Owned, Action::Lend) => Ok(State::Lent),
+                (State::Lent, Action::Return) => Ok(State::Owned),
+                (State::Owned, Action::RequestVerification) => Ok(State::PendingVerification),
+                (State::PendingVerification, Action::SubmitVerification) => Ok(State::Owned),
+                (State::PendingDelegation, Action::Accept) => Ok(State::Owned),
+                (State::PendingDelegation, Action::Cancel) => Ok(State::Owned),
+                (State::Owned, Action::Recall) => Ok(State::Recalled),
+                (State::PendingDelegation, Action::Recall) => Ok(State::Recalled),
+                (State::Recalled, Action::ResolveRecall) => Ok(State::Owned),
+                (State::Recalled, _) => Err(Error::ProductRecalled),
+                (State::ComponentLocked, _) => Err(Error::ComponentLocked),
+                (State::Disputed, _) => Err(Error::ProductDisputed),
+                (State::Listed, _) => Err(Error::ProductListed),
+                (State::Auctioned, _) => Err(Error::ProductAuctioned),
+                (State::Lent, _) => Err(Error::ProductLent),
+                (State::PendingVerification, _) => Err(Error::VerificationPending),
+                (_, Action::Buy) => Err(Error::NotListed),
+                _ => Err(Error::InvalidState),
+            }
+        }
+    }
+
+    /// Where a product is in the supply chain, independent of who owns it.
+    /// Stages only move forward one step at a time, see `advance_stage`.
+    #[derive(Copy, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout)]
+    #[cfg_attr(
+        feature = "std",
+        derive(
+            Debug,
+            scale_info::TypeInfo,
+            ink_storage::traits::StorageLayout
+        )
+    )]
+    pub enum Stage {
+        Manufactured,
+        Shipped,
+        Delivered,
+        Sold,
+    }
+
+    impl Stage {
+        /// The stage following this one, `FinalStage` past `Sold`.
+        pub fn next(self) -> Result<Stage> {
+            match self {
+                Stage::Manufactured => Ok(Stage::Shipped),
+                Stage::Shipped => Ok(Stage::Delivered),
+                Stage::Delivered => Ok(Stage::Sold),
+                Stage::Sold => Err(Error::FinalStage),
+            }
+        }
+    }
+
+    /// A product as stored. Its name, description and metadata URI live in
+    /// maps of their own, see `Product` for the view messages return.
+    #[derive(Clone, scale::Encode, scale::Decode, SpreadLayout, PackedLayout)]
     #[cfg_attr(
         feature = "std",
         derive(
@@ -26,274 +192,9702 @@ mod product {
             ink_storage::traits::StorageLayout
         )
     )]
-    pub struct Product{
-        state: u8,
+    pub struct StoredProduct{
+        state: State,
         code: u16,
         owner:AccountId, 
         delegate_to:Option<AccountId>,
+        delegation_deadline:Option<Timestamp>,
+        metadata_frozen:bool,
+        created_at:Timestamp,
+        created_block:BlockNumber,
+        /// Set by the admin to hold a single product in place.
+        frozen:bool,
+        /// Number of units in the lot, `1` for a single item.
+        quantity:u32,
+        /// Amount the delegate has to pay to accept, or a buyer to buy the
+        /// listed product, `0` when free.
+        price:Balance,
+        /// Account that minted the product, it never changes. Lots split off
+        /// a product keep its creator.
+        creator:AccountId,
+        /// Share of every sale price paid to the creator, in basis points.
+        royalty_bps:u16,
+        /// Accounts any of which may accept a delegation made with
+        /// `delegate_to_any`, empty otherwise.
+        candidates:Vec<AccountId>,
+        /// Borrower holding a lent product.
+        custodian:Option<AccountId>,
+        /// Block after which the owner may reclaim a lent product.
+        lent_until:Option<BlockNumber>,
+        /// Note the owner left for the delegate, e.g. a shipment reference.
+        memo:Option<Vec<u8>>,
+        /// Timestamp up to which the product is under warranty.
+        warranty_until:Option<Timestamp>,
+        stage:Stage,
+        /// Outcome and report hash of the latest oracle verification.
+        verification:Option<(bool, Hash)>,
+        /// Production run minted together by `create_lot`. Lots split off a
+        /// member stay in its run.
+        lot_id:Option<u32>,
+        /// Set by the owner to keep the product out of listings, see
+        /// `archive_product`.
+        archived:bool,
     }
 
-    impl Product {
+    impl StoredProduct {
             pub fn new(
-                state: u8,
+                state: State,
                 code: u16,
                 owner: AccountId,
-            ) -> Product{
-                Product { 
-                    state: state,
-                    code: code,
-                    owner:owner,
-                    delegate_to:None
+                created_at: Timestamp,
+                created_block: BlockNumber,
+            ) -> StoredProduct{
+                StoredProduct { 
+                    state,
+                    code,
+                    owner,
+                    delegate_to:None,
+                    delegation_deadline:None,
+                    metadata_frozen:false,
+                    created_at,
+                    created_block,
+                    frozen:false,
+                    quantity:1,
+                    price:0,
+                    creator:owner,
+                    royalty_bps:0,
+                    candidates:Vec::new(),
+                    custodian:None,
+                    lent_until:None,
+                    memo:None,
+                    warranty_until:None,
+                    stage:Stage::Manufactured,
+                    verification:None,
+                    lot_id:None,
+                    archived:false,
                 } 
             }
         }
 
-    impl Product {
-            pub fn get_owner(&mut self) -> AccountId{
+    impl StoredProduct {
+            pub fn get_owner(&self) -> AccountId{
                 self.owner    
             } 
     }
 
-    impl Product {
-            pub fn get_code(&mut self) -> u16{
+    impl StoredProduct {
+            pub fn get_code(&self) -> u16{
                 self.code
             } 
     }
 
-    impl Product {
-            pub fn get(& self) -> Product{
-                Product{
-                    code:self.code,
-                    state:self.state,
-                    owner:self.owner,
-                    delegate_to:self.delegate_to
-                }    
+    impl StoredProduct {
+            pub fn get_delegate(&self) -> Option<AccountId>{
+                self.delegate_to    
             } 
     }
 
-    impl Product {
-            pub fn get_delegate(&mut self) -> Option<AccountId>{
-                self.delegate_to    
+    impl StoredProduct {
+            pub fn is_metadata_frozen(&self) -> bool{
+                self.metadata_frozen
             } 
     }
 
-    impl Product {
-            pub fn get_state(&mut self) -> u8{
-                self.state 
+    impl StoredProduct {
+            pub fn freeze_metadata(&mut self){
+                self.metadata_frozen = true;
             } 
     }
 
-    impl Product {
-            pub fn delegate_to(&mut self, delegate: AccountId){
-                self.state = 1;
-                self.delegate_to = Some(delegate);
+    impl StoredProduct {
+            pub fn is_frozen(&self) -> bool{
+                self.frozen
             } 
     }
 
-    impl Product {
-            pub fn accept(&mut self, delegate: AccountId){
-                self.state = 0;
-                self.owner = delegate;
-                self.delegate_to = None;
+    impl StoredProduct {
+            pub fn set_frozen(&mut self, frozen: bool){
+                self.frozen = frozen;
             } 
     }
 
-    /// Errors that can occur upon calling this contract.
-    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
-    #[cfg_attr(feature = "std", derive(::scale_info::TypeInfo))]
-    pub enum Error {
-        PidNotExists,
-        InvalidOwner,
-        InvalidDelegate,
-        InvalidState,
+    impl StoredProduct {
+            pub fn is_archived(&self) -> bool{
+                self.archived
+            } 
     }
 
-    pub type Result<T> = core::result::Result<T, Error>;
+    impl StoredProduct {
+            pub fn get_quantity(&self) -> u32{
+                self.quantity
+            } 
+    }
 
-    /// Defines the storage of your contract.
-    /// Add new fields to the below struct in order
-    /// to add new static storage fields to your contract.
-    #[ink(storage)]
-    pub struct ProductFactory{
-        products: StorageVec<Product>,
+    impl StoredProduct {
+            pub fn get_price(&self) -> Balance{
+                self.price
+            } 
     }
 
-    impl ProductFactory {
-        #[ink(constructor)]
-        pub fn new()->  Self{
-            Self{
-                products:StorageVec::<Product>::new(),
-            }        
+    impl StoredProduct {
+            pub fn get_creator(&self) -> AccountId{
+                self.creator
+            } 
+    }
 
-        }
-        
-    
-        #[ink(message)]
-        pub fn create_product(&mut self, code: u16){
-            let p = Product::new(
-                0,
-                code,
-                Self::env().caller(),
-            );
-            self.products.push(p);
+    impl StoredProduct {
+            pub fn get_royalty_bps(&self) -> u16{
+                self.royalty_bps
+            } 
+    }
 
-        }
+    impl StoredProduct {
+            pub fn get_lot_id(&self) -> Option<u32>{
+                self.lot_id
+            } 
+    }
 
-        #[ink(message)]
-        pub fn get_last(&mut self) ->  Product{
-            self.products[self.products.len()-1]
-        }
+    impl StoredProduct {
+            pub fn get_stage(&self) -> Stage{
+                self.stage
+            } 
+    }
 
+    impl StoredProduct {
+            pub fn get_warranty_until(&self) -> Option<Timestamp>{
+                self.warranty_until
+            } 
+    }
 
-        #[ink(message)]
-        pub fn delegate_product(&mut self, pid: u32, delegate_to: AccountId) -> Result<()>{
-            if pid >= self.products.len(){
-                return Err(Error::PidNotExists)
-            }
+    impl StoredProduct {
+            pub fn get_custodian(&self) -> Option<AccountId>{
+                self.custodian
+            } 
+    }
 
-            let  p = &mut self.products[pid];
-            if p.get_owner() != Self::env().caller(){
-                return Err(Error::InvalidOwner)
-            }
-            if p.get_state() != 0{
-                return Err(Error::InvalidState)
-            }
-            p.delegate_to(delegate_to);
-            Ok(())        
-            
+    impl StoredProduct {
+            pub fn get_lent_until(&self) -> Option<BlockNumber>{
+                self.lent_until
+            } 
+    }
 
-        }
+    impl StoredProduct {
+            /// Every account the pending delegation can be accepted by.
+            fn pending_delegates(&self) -> Vec<AccountId>{
+                match self.delegate_to{
+                    Some(delegate) => ink_prelude::vec![delegate],
+                    None => self.candidates.clone(),
+                }
+            } 
+    }
 
-        #[ink(message)]
-        pub fn accept_product(&mut self, pid: u32) -> Result<()>{
-            if pid >= self.products.len(){
-                return Err(Error::PidNotExists)
-            }
+    impl StoredProduct {
+            /// Fails unless `account` may accept the pending delegation.
+            fn ensure_delegate(&self, account: AccountId) -> Result<()>{
+                if !self.candidates.is_empty(){
+                    if !self.candidates.contains(&account){
+                        return Err(Error::NotCandidate)
+                    }
+                } else if self.delegate_to != Some(account){
+                    return Err(Error::InvalidDelegate)
+                }
+                Ok(())
+            } 
+    }
 
-            let  p = &mut self.products[pid];
-            if p.get_delegate() != Some(Self::env().caller()){
-                return Err(Error::InvalidDelegate)
-            }
-            if p.get_state() != 1{
-                return Err(Error::InvalidState)
-            }
-            p.accept(Self::env().caller());
-            Ok(())        
-            
+    impl StoredProduct {
+            pub fn get_delegation_deadline(&self) -> Option<Timestamp>{
+                self.delegation_deadline
+            } 
+    }
 
-        }
+    impl StoredProduct {
+            pub fn get_state(&self) -> State{
+                self.state 
+            } 
+    }
 
-            
+    impl StoredProduct {
+            /// Moves the product through `action`. Every state write goes
+            /// through here, see `ProductFactory::change_product` for the
+            /// event.
+            fn apply(&mut self, action: Action) -> Result<()>{
+                self.state = self.state.transition(action)?;
+                Ok(())
+            } 
     }
-    
-/// Unit tests in Rust are normally defined within such a `#[cfg(test)]`
-/// module and test functions are marked with a `#[test]` attribute.
-/// The below code is technically just normal Rust code.
-    #[cfg(test)]
-    mod tests {
-        /// Imports all the definitions from the outer scope so we can use them here.
-        use super::*;
-        
-        /// Imports `ink_lang` so we can use `#[ink::test]`.
-        use ink_lang as ink;
 
-        use ink_env::{
-            call,
-            test,
-        };
+    impl StoredProduct {
+            fn delegate_to(&mut self, delegate: AccountId, deadline: Option<Timestamp>, price: Balance, memo: Option<Vec<u8>>) -> Result<()>{
+                self.apply(Action::Delegate)?;
+                self.delegate_to = Some(delegate);
+                self.delegation_deadline = deadline;
+                self.price = price;
+                self.memo = memo;
+                Ok(())
+            } 
+    }
 
-        #[ink::test]
-        fn create_product_test() {
-            let accounts =
-                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
-                    .expect("Cannot get accounts");
-            let mut product_factory = ProductFactory::new();
-            assert_eq!(product_factory.products.len(), 0);
-            product_factory.create_product(1);
+    impl StoredProduct {
+            fn delegate_to_any(&mut self, candidates: Vec<AccountId>, deadline: Option<Timestamp>, price: Balance) -> Result<()>{
+                self.apply(Action::Delegate)?;
+                self.candidates = candidates;
+                self.delegation_deadline = deadline;
+                self.price = price;
+                Ok(())
+            } 
+    }
 
-            assert_eq!(product_factory.get_last().owner, accounts.alice);
-            assert_eq!(product_factory.get_last().state, 0);
-        }
+    impl StoredProduct {
+            fn accept(&mut self, delegate: AccountId) -> Result<()>{
+                self.apply(Action::Accept)?;
+                self.owner = delegate;
+                self.delegate_to = None;
+                self.candidates.clear();
+                self.memo = None;
+                self.delegation_deadline = None;
+                self.price = 0;
+                Ok(())
+            } 
+    }
 
-        #[ink::test]
-        fn delegate_product_test() {
-            let accounts =
-                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
-                    .expect("Cannot get accounts");
-            let mut product_factory = ProductFactory::new();
-            assert_eq!(product_factory.products.len(), 0);
-            product_factory.create_product(1);
+    impl StoredProduct {
+            fn transfer(&mut self, to: AccountId) -> Result<()>{
+                self.apply(Action::Transfer)?;
+                self.owner = to;
+                self.delegate_to = None;
+                self.delegation_deadline = None;
+                Ok(())
+            } 
+    }
 
-            assert_eq!(product_factory.get_last().owner, accounts.alice);
-            assert_eq!(product_factory.get_last().state, 0);
+    impl StoredProduct {
+            /// Recalls the product, dropping any pending delegation.
+            fn recall(&mut self) -> Result<()>{
+                self.apply(Action::Recall)?;
+                self.delegate_to = None;
+                self.candidates.clear();
+                self.memo = None;
+                self.delegation_deadline = None;
+                self.price = 0;
+                Ok(())
+            } 
+    }
 
-            product_factory.delegate_product(0,accounts.bob);
-            assert_eq!(product_factory.get_last().owner, accounts.alice);
-            assert_eq!(product_factory.get_last().state, 1);
-            assert_eq!(product_factory.get_last().get_delegate(), Some(accounts.bob));
+    impl StoredProduct {
+            /// Locks the product into a parent assembly.
+            fn lock(&mut self) -> Result<()>{
+                self.apply(Action::Assemble)?;
+                Ok(())
+            } 
+    }
 
-            assert_eq!(
-                product_factory.delegate_product(1,accounts.bob), 
-                Err(Error::PidNotExists)
-            );
+    impl StoredProduct {
+            /// Frees a component of a parent being taken apart, handing it
+            /// to the parent's current owner.
+            fn release(&mut self, owner: AccountId) -> Result<()>{
+                self.apply(Action::Release)?;
+                self.owner = owner;
+                Ok(())
+            } 
+    }
 
-            assert_eq!(
-                product_factory.delegate_product(0,accounts.bob), 
-                Err(Error::InvalidState)
-            );
+    impl StoredProduct {
+            fn list(&mut self, price: Balance) -> Result<()>{
+                self.apply(Action::List)?;
+                self.price = price;
+                Ok(())
+            } 
+    }
 
-            set_sender(accounts.bob);
-            assert_eq!(
-                product_factory.delegate_product(0,accounts.bob), 
-                Err(Error::InvalidOwner)
-            );
+    impl StoredProduct {
+            fn unlist(&mut self) -> Result<()>{
+                self.apply(Action::Unlist)?;
+                self.price = 0;
+                Ok(())
+            } 
+    }
 
+    impl StoredProduct {
+            fn buy(&mut self, buyer: AccountId) -> Result<()>{
+                self.apply(Action::Buy)?;
+                self.owner = buyer;
+                self.price = 0;
+                Ok(())
+            } 
+    }
 
-        }
+    impl StoredProduct {
+            fn start_auction(&mut self) -> Result<()>{
+                self.apply(Action::StartAuction)?;
+                Ok(())
+            } 
+    }
 
-        #[ink::test]
-        fn accept_product_test() {
-            let accounts =
-                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
-                    .expect("Cannot get accounts");
-            let mut product_factory = ProductFactory::new();
-            assert_eq!(product_factory.products.len(), 0);
-            product_factory.create_product(1);
+    impl StoredProduct {
+            fn lend(&mut self, borrower: AccountId, until: BlockNumber) -> Result<()>{
+                self.apply(Action::Lend)?;
+                self.custodian = Some(borrower);
+                self.lent_until = Some(until);
+                Ok(())
+            } 
+    }
 
-            assert_eq!(product_factory.get_last().owner, accounts.alice);
-            assert_eq!(product_factory.get_last().state, 0);
+    impl StoredProduct {
+            fn end_loan(&mut self) -> Result<()>{
+                self.apply(Action::Return)?;
+                self.custodian = None;
+                self.lent_until = None;
+                Ok(())
+            } 
+    }
 
-            product_factory.delegate_product(0,accounts.bob);
-            assert_eq!(product_factory.get_last().owner, accounts.alice);
-            assert_eq!(product_factory.get_last().state, 1);
-            assert_eq!(product_factory.get_last().get_delegate(), Some(accounts.bob));
-            
-            set_sender(accounts.bob);
-            product_factory.accept_product(0);
+    impl StoredProduct {
+            fn request_verification(&mut self) -> Result<()>{
+                self.apply(Action::RequestVerification)?;
+                Ok(())
+            } 
+    }
 
-            assert_eq!(product_factory.get_last().owner, accounts.bob);
-            assert_eq!(product_factory.get_last().state, 0);
-            assert_eq!(product_factory.get_last().get_delegate(), None);
+    impl StoredProduct {
+            fn record_verification(&mut self, passed: bool, report_hash: Hash) -> Result<()>{
+                self.apply(Action::SubmitVerification)?;
+                self.verification = Some((passed, report_hash));
+                Ok(())
+            } 
+    }
 
+    impl StoredProduct {
+            /// Ends an auction, leaving the product with `owner`.
+            fn finalize_auction(&mut self, owner: AccountId) -> Result<()>{
+                self.apply(Action::FinalizeAuction)?;
+                self.owner = owner;
+                Ok(())
+            } 
+    }
 
-            assert_eq!(
-                product_factory.accept_product(0),
-                Err(Error::InvalidDelegate));
+    impl StoredProduct {
+            fn dispute(&mut self) -> Result<()>{
+                self.apply(Action::Dispute)?;
+                Ok(())
+            } 
+    }
 
-        }
+    impl StoredProduct {
+            /// Ends a dispute, leaving the product with `owner`.
+            fn resolve_dispute(&mut self, owner: AccountId) -> Result<()>{
+                self.apply(Action::ResolveDispute)?;
+                self.owner = owner;
+                Ok(())
+            } 
+    }
 
-        fn set_sender(sender: AccountId) {
-            let callee = ink_env::account_id::<ink_env::DefaultEnvironment>()
-                .unwrap_or_else(|_| [0x0; 32].into());
-            test::push_execution_context::<Environment>(
-                sender,
-                callee,
-                1000000,
-                1000000,
-                test::CallData::new(call::Selector::new([0x00; 4])), // dummy
-            );
-        }
+    impl StoredProduct {
+            fn resolve_recall(&mut self) -> Result<()>{
+                self.apply(Action::ResolveRecall)?;
+                Ok(())
+            } 
+    }
 
-        
+    impl StoredProduct {
+            fn cancel_delegation(&mut self) -> Result<()>{
+                self.apply(Action::Cancel)?;
+                self.delegate_to = None;
+                self.candidates.clear();
+                self.memo = None;
+                self.delegation_deadline = None;
+                self.price = 0;
+                Ok(())
+            } 
+    }
+
+    impl StoredProduct {
+            /// Joins the cold metadata kept apart from the entry back in.
+            pub fn view(&self, name: Vec<u8>, description: Vec<u8>, metadata_uri: Option<Vec<u8>>) -> Product{
+                Product{
+                    state:self.state,
+                    code:self.code,
+                    owner:self.owner,
+                    delegate_to:self.delegate_to,
+                    delegation_deadline:self.delegation_deadline,
+                    name,
+                    description,
+                    metadata_uri,
+                    metadata_frozen:self.metadata_frozen,
+                    created_at:self.created_at,
+                    created_block:self.created_block,
+                    frozen:self.frozen,
+                    quantity:self.quantity,
+                    price:self.price,
+                    creator:self.creator,
+                    royalty_bps:self.royalty_bps,
+                    candidates:self.candidates.clone(),
+                    custodian:self.custodian,
+                    lent_until:self.lent_until,
+                    memo:self.memo.clone(),
+                    warranty_until:self.warranty_until,
+                    stage:self.stage,
+                    verification:self.verification,
+                    lot_id:self.lot_id,
+                    archived:self.archived,
+                }
+            } 
+    }
+
+    /// A product as messages return it, its stored entry together with
+    /// the name, description and metadata URI.
+    #[derive(Clone, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(
+            Debug,
+            PartialEq,
+            Eq,
+            scale_info::TypeInfo
+        )
+    )]
+    pub struct Product{
+        state: State,
+        code: u16,
+        owner:AccountId, 
+        delegate_to:Option<AccountId>,
+        delegation_deadline:Option<Timestamp>,
+        name:Vec<u8>,
+        description:Vec<u8>,
+        metadata_uri:Option<Vec<u8>>,
+        metadata_frozen:bool,
+        created_at:Timestamp,
+        created_block:BlockNumber,
+        /// Set by the admin to hold a single product in place.
+        frozen:bool,
+        /// Number of units in the lot, `1` for a single item.
+        quantity:u32,
+        /// Amount the delegate has to pay to accept, or a buyer to buy the
+        /// listed product, `0` when free.
+        price:Balance,
+        /// Account that minted the product, it never changes. Lots split off
+        /// a product keep its creator.
+        creator:AccountId,
+        /// Share of every sale price paid to the creator, in basis points.
+        royalty_bps:u16,
+        /// Accounts any of which may accept a delegation made with
+        /// `delegate_to_any`, empty otherwise.
+        candidates:Vec<AccountId>,
+        /// Borrower holding a lent product.
+        custodian:Option<AccountId>,
+        /// Block after which the owner may reclaim a lent product.
+        lent_until:Option<BlockNumber>,
+        /// Note the owner left for the delegate, e.g. a shipment reference.
+        memo:Option<Vec<u8>>,
+        /// Timestamp up to which the product is under warranty.
+        warranty_until:Option<Timestamp>,
+        stage:Stage,
+        /// Outcome and report hash of the latest oracle verification.
+        verification:Option<(bool, Hash)>,
+        /// Production run minted together by `create_lot`. Lots split off a
+        /// member stay in its run.
+        lot_id:Option<u32>,
+        /// Set by the owner to keep the product out of listings, see
+        /// `archive_product`.
+        archived:bool,
+    }
+
+    impl Product {
+            pub fn get_owner(&self) -> AccountId{
+                self.owner    
+            } 
+    }
+
+    impl Product {
+            pub fn get_code(&self) -> u16{
+                self.code
+            } 
+    }
+
+    impl Product {
+            pub fn get_delegate(&self) -> Option<AccountId>{
+                self.delegate_to    
+            } 
+    }
+
+    impl Product {
+            pub fn get_name(&self) -> Vec<u8>{
+                self.name.clone()
+            } 
+    }
+
+    impl Product {
+            pub fn get_description(&self) -> Vec<u8>{
+                self.description.clone()
+            } 
+    }
+
+    impl Product {
+            pub fn get_metadata_uri(&self) -> Option<Vec<u8>>{
+                self.metadata_uri.clone()
+            } 
+    }
+
+    impl Product {
+            pub fn is_metadata_frozen(&self) -> bool{
+                self.metadata_frozen
+            } 
+    }
+
+    impl Product {
+            pub fn get_created_at(&self) -> Timestamp{
+                self.created_at
+            } 
+    }
+
+    impl Product {
+            pub fn get_created_block(&self) -> BlockNumber{
+                self.created_block
+            } 
+    }
+
+    impl Product {
+            pub fn is_frozen(&self) -> bool{
+                self.frozen
+            } 
+    }
+
+    impl Product {
+            pub fn is_archived(&self) -> bool{
+                self.archived
+            } 
+    }
+
+    impl Product {
+            pub fn get_quantity(&self) -> u32{
+                self.quantity
+            } 
+    }
+
+    impl Product {
+            pub fn get_price(&self) -> Balance{
+                self.price
+            } 
+    }
+
+    impl Product {
+            pub fn get_creator(&self) -> AccountId{
+                self.creator
+            } 
+    }
+
+    impl Product {
+            pub fn get_royalty_bps(&self) -> u16{
+                self.royalty_bps
+            } 
+    }
+
+    impl Product {
+            pub fn get_lot_id(&self) -> Option<u32>{
+                self.lot_id
+            } 
+    }
+
+    impl Product {
+            pub fn get_stage(&self) -> Stage{
+                self.stage
+            } 
+    }
+
+    impl Product {
+            pub fn get_warranty_until(&self) -> Option<Timestamp>{
+                self.warranty_until
+            } 
+    }
+
+    impl Product {
+            pub fn get_custodian(&self) -> Option<AccountId>{
+                self.custodian
+            } 
+    }
+
+    impl Product {
+            pub fn get_lent_until(&self) -> Option<BlockNumber>{
+                self.lent_until
+            } 
+    }
+
+    impl Product {
+            pub fn get_candidates(&self) -> Vec<AccountId>{
+                self.candidates.clone()
+            } 
+    }
+
+    impl Product {
+            pub fn get_delegation_deadline(&self) -> Option<Timestamp>{
+                self.delegation_deadline
+            } 
+    }
+
+    impl Product {
+            pub fn get_state(&self) -> State{
+                self.state 
+            } 
+    }
+
+    /// An ascending auction of a single product.
+    #[derive(Clone, scale::Encode, scale::Decode, SpreadLayout, PackedLayout)]
+    #[cfg_attr(
+        feature = "std",
+        derive(
+            Debug,
+            PartialEq,
+            Eq,
+            scale_info::TypeInfo,
+            ink_storage::traits::StorageLayout
+        )
+    )]
+    pub struct Auction{
+        seller:AccountId,
+        min_bid:Balance,
+        /// Last block bids are accepted in.
+        end_block:BlockNumber,
+        /// Highest bidder and its bid, held by the contract.
+        highest_bid:Option<(AccountId, Balance)>,
+    }
+
+    /// A delegation that changes hands once `threshold` of the `signers`
+    /// confirmed it, see `delegate_product_multisig`.
+    #[derive(Clone, scale::Encode, scale::Decode, SpreadLayout, PackedLayout)]
+    #[cfg_attr(
+        feature = "std",
+        derive(
+            Debug,
+            PartialEq,
+            Eq,
+            scale_info::TypeInfo,
+            ink_storage::traits::StorageLayout
+        )
+    )]
+    pub struct MultisigDelegation{
+        signers:Vec<AccountId>,
+        threshold:u8,
+        /// Account that becomes the owner.
+        recipient:AccountId,
+        /// Signers that confirmed so far.
+        confirmations:Vec<AccountId>,
+    }
+
+    /// What happens to an ownership change when notifying the registry
+    /// contract of it fails.
+    #[derive(Copy, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout)]
+    #[cfg_attr(
+        feature = "std",
+        derive(
+            Debug,
+            scale_info::TypeInfo,
+            ink_storage::traits::StorageLayout
+        )
+    )]
+    pub enum RegistryPolicy {
+        /// The change goes through regardless.
+        Ignore,
+        /// The whole call is reverted.
+        Revert,
+    }
+
+    /// Settings the admin tunes the registry with, see `get_config`.
+    #[derive(Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout)]
+    #[cfg_attr(
+        feature = "std",
+        derive(
+            Debug,
+            scale_info::TypeInfo,
+            ink_storage::traits::StorageLayout
+        )
+    )]
+    pub struct Config{
+        /// Value each created product must be paid with.
+        creation_fee:Balance,
+        /// Cap on the number of live products, `0` for no cap.
+        max_products:u32,
+        /// Cap on the number of products a single account may own, `0` for
+        /// no cap.
+        max_per_owner:u32,
+        /// Account resolving disputes.
+        arbiter:Option<AccountId>,
+        /// Account reporting on inspections.
+        oracle:Option<AccountId>,
+        /// Contract notified of every ownership change.
+        registry:Option<AccountId>,
+    }
+
+    /// Snapshot of the registry returned by `get_contract_info`, so a
+    /// frontend can render its header in a single call.
+    #[derive(Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(Debug, scale_info::TypeInfo))]
+    pub struct ContractInfo{
+        admin:AccountId,
+        /// Account nominated as the next admin, if any.
+        pending_admin:Option<AccountId>,
+        /// Products ever created, burned ones included.
+        product_count:u32,
+        /// Products not burned yet.
+        live_product_count:u32,
+        paused:bool,
+        creation_fee:Balance,
+        max_products:u32,
+        max_per_owner:u32,
+        schema_version:u32,
+        /// Block the termination was announced at, if it was.
+        termination_announced_at:Option<BlockNumber>,
+    }
+
+    /// Merkle root of the live products returned by `current_root`.
+    #[derive(Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(Debug, scale_info::TypeInfo))]
+    pub struct ProductsRoot{
+        root:Hash,
+        /// Block the root was finalized in.
+        block:BlockNumber,
+        /// Whether a product changed since, so that the root no longer
+        /// reflects the registry.
+        stale:bool,
+    }
+
+    /// Root computation in progress, see `compute_products_root`.
+    ///
+    /// Leaves are folded in as they come: `frontier` holds the roots of the
+    /// complete subtrees built so far with their heights, strictly
+    /// decreasing, like the set bits of the leaf count. Merging them right
+    /// to left at the end gives the same root as hashing the leaves level
+    /// by level, pairing them up and carrying an odd last node to the next
+    /// level unchanged.
+    #[derive(Clone, Default, scale::Encode, scale::Decode, SpreadLayout, PackedLayout)]
+    #[cfg_attr(
+        feature = "std",
+        derive(
+            Debug,
+            PartialEq,
+            Eq,
+            scale_info::TypeInfo,
+            ink_storage::traits::StorageLayout
+        )
+    )]
+    pub struct RootBuild{
+        /// Next pid to visit.
+        cursor:u32,
+        frontier:Vec<(u8, Hash)>,
+    }
+
+    impl RootBuild {
+        /// Returns the leaf of `pid`, the BLAKE2b-256 hash of the encoded
+        /// `(pid, owner, code, state)` with the state as its `u8` encoding.
+        fn leaf(pid: u32, product: &StoredProduct) -> Hash{
+            let mut output = <ink_env::hash::Blake2x256 as ink_env::hash::HashOutput>::Type::default();
+            ink_env::hash_encoded::<ink_env::hash::Blake2x256, _>(
+                &(pid, product.get_owner(), product.get_code(), product.get_state() as u8),
+                &mut output,
+            );
+            Hash::from(output)
+        }
+
+        /// Returns the BLAKE2b-256 hash of `left` followed by `right`.
+        fn node(left: Hash, right: Hash) -> Hash{
+            let mut output = <ink_env::hash::Blake2x256 as ink_env::hash::HashOutput>::Type::default();
+            ink_env::hash_encoded::<ink_env::hash::Blake2x256, _>(&(left, right), &mut output);
+            Hash::from(output)
+        }
+
+        fn push(&mut self, leaf: Hash){
+            let (mut height, mut node) = (0, leaf);
+            while let Some(&(top_height, top)) = self.frontier.last(){
+                if top_height != height{
+                    break
+                }
+                self.frontier.pop();
+                node = Self::node(top, node);
+                height += 1;
+            }
+            self.frontier.push((height, node));
+        }
+
+        /// Returns the root, all zeroes for an empty registry.
+        fn finish(self) -> Hash{
+            self.frontier
+                .into_iter()
+                .rev()
+                .map(|(_, node)| node)
+                .reduce(|right, left| Self::node(left, right))
+                .unwrap_or_default()
+        }
+    }
+
+    /// Field of `Config` a `ConfigChanged` event reports on.
+    ///
+    /// The variant order is part of the encoded representation and must
+    /// not change, `CreationFee` encodes as `0`, `MaxProducts` as `1`,
+    /// `MaxPerOwner` as `2`, `Arbiter` as `3`, `Oracle` as `4` and
+    /// `Registry` as `5`.
+    #[derive(Copy, Clone, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(Debug))]
+    pub enum ConfigField {
+        CreationFee,
+        MaxProducts,
+        MaxPerOwner,
+        Arbiter,
+        Oracle,
+        Registry,
+    }
+
+    /// Privileged action an `AdminAction` event reports on.
+    ///
+    /// The variant order is part of the encoded representation and must
+    /// not change, new actions are appended: `SetCreationFee` encodes as
+    /// `0`, `SetMaxProducts` as `1` and so on down to `SetRoyalty` as
+    /// `24`.
+    #[derive(Copy, Clone, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(Debug))]
+    pub enum AdminActionKind {
+        SetCreationFee,
+        SetMaxProducts,
+        SetMaxPerOwner,
+        WithdrawFees,
+        RecallProduct,
+        ResolveRecall,
+        FreezeProduct,
+        UnfreezeProduct,
+        SetMaxReadings,
+        TransferAdmin,
+        AcceptAdmin,
+        Pause,
+        Unpause,
+        AnnounceTermination,
+        Terminate,
+        SetRegistry,
+        SetRegistryPolicy,
+        AddManufacturer,
+        RemoveManufacturer,
+        AddCertifier,
+        RemoveCertifier,
+        SetArbiter,
+        SetOracle,
+        SetBlacklisted,
+        SetRoyalty,
+    }
+
+    /// Errors that can occur upon calling this contract.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(::scale_info::TypeInfo))]
+    pub enum Error {
+        PidNotExists,
+        InvalidOwner,
+        InvalidDelegate,
+        InvalidState,
+        SelfDelegation,
+        ZeroAddress,
+        BatchTooLarge,
+        BatchItemFailed { pid: u32, error: Box<Error> },
+        SelfTransfer,
+        DelegationExpired,
+        NotExpired,
+        MetadataTooLong,
+        MetadataFrozen,
+        DuplicateCode,
+        NotAdmin,
+        Paused,
+        NotManufacturer,
+        NotApproved,
+        InsufficientFee,
+        TransferFailed,
+        RefundFailed,
+        ProductRecalled,
+        ProductFrozen,
+        Blacklisted,
+        InvalidQuantity,
+        InsufficientQuantity,
+        SelfMerge,
+        CodeMismatch,
+        ComponentLocked,
+        NoComponents,
+        InvalidComponent,
+        HasComponents,
+        NotCertifier,
+        DuplicateCertification,
+        CertificationNotFound,
+        NotIssuer,
+        ProductDisputed,
+        NotArbiter,
+        DisputeWindowClosed,
+        InsufficientPayment,
+        NothingToWithdraw,
+        ProductListed,
+        NotListed,
+        ProductAuctioned,
+        NotAuctioned,
+        InvalidDuration,
+        BidTooLow,
+        AuctionEnded,
+        AuctionNotEnded,
+        NotCreator,
+        RoyaltyTooHigh,
+        NotPaused,
+        TerminationNotAnnounced,
+        TerminationTooEarly,
+        FundsEscrowed,
+        InvalidCandidates,
+        NotCandidate,
+        InvalidThreshold,
+        NotSigner,
+        AlreadyConfirmed,
+        InvalidShare,
+        InsufficientShare,
+        NotCoOwner,
+        AlreadyCoOwner,
+        CoOwnerApprovalMissing,
+        ProductLent,
+        NotBorrower,
+        LoanNotExpired,
+        MemoTooLong,
+        NoWarranty,
+        WarrantyExpired,
+        FinalStage,
+        NotOwnerOrDelegate,
+        LocationTooLong,
+        NotSensor,
+        InvalidRange,
+        NotOracle,
+        OracleNotSet,
+        VerificationPending,
+        NoteTooLong,
+        TooManyNotes,
+        ReentrancyDetected,
+        ArithmeticOverflow,
+        MaxProductsReached,
+        MaxProductsTooLow,
+        OwnerQuotaExceeded,
+        ProductArchived,
+        IndexOutOfBounds,
+    }
+
+    impl Error {
+        fn batch_item_failed(pid: u32, error: Error) -> Error {
+            Error::BatchItemFailed { pid, error: Box::new(error) }
+        }
+    }
+
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    /// PSP34 token id. Products are exposed as `Id::U32(pid)`, ids of any
+    /// other shape never name a token.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(::scale_info::TypeInfo))]
+    pub enum Id {
+        U8(u8),
+        U16(u16),
+        U32(u32),
+        U64(u64),
+        U128(u128),
+        Bytes(Vec<u8>),
+    }
+
+    impl Id {
+        fn pid(&self) -> Option<u32> {
+            match self {
+                Id::U32(pid) => Some(*pid),
+                _ => None,
+            }
+        }
+    }
+
+    /// Errors of the PSP34 messages. Failures without a PSP34 counterpart,
+    /// such as a product pending delegation, are reported as `Custom` with
+    /// the name of the factory error.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(::scale_info::TypeInfo))]
+    pub enum PSP34Error {
+        Custom(String),
+        SelfApprove,
+        NotApproved,
+        TokenExists,
+        TokenNotExists,
+        SafeTransferCheckFailed(String),
+    }
+
+    impl From<Error> for PSP34Error {
+        fn from(error: Error) -> Self {
+            match error {
+                Error::PidNotExists => PSP34Error::TokenNotExists,
+                Error::InvalidOwner => PSP34Error::NotApproved,
+                error => PSP34Error::Custom(ink_prelude::format!("{:?}", error)),
+            }
+        }
+    }
+
+    /// Core of the PSP34 non-fungible token standard. Selectors are composed
+    /// from the trait name, e.g. `PSP34::owner_of`, so they match what
+    /// PSP34 wallets call.
+    #[ink_lang::trait_definition]
+    pub trait PSP34 {
+        /// Returns the number of products owned by `owner`.
+        #[ink(message)]
+        fn balance_of(&self, owner: AccountId) -> u32;
+
+        #[ink(message)]
+        fn owner_of(&self, id: Id) -> Option<AccountId>;
+
+        /// Returns whether `operator` may transfer `id` of `owner`, or every
+        /// token of `owner` when `id` is `None`.
+        #[ink(message)]
+        fn allowance(&self, owner: AccountId, operator: AccountId, id: Option<Id>) -> bool;
+
+        /// Approves or revokes `operator` for `id`, or for every token of the
+        /// caller when `id` is `None`.
+        #[ink(message)]
+        fn approve(&mut self, operator: AccountId, id: Option<Id>, approved: bool) -> core::result::Result<(), PSP34Error>;
+
+        /// Transfers `id` to `to`. `data` is accepted for compatibility and
+        /// ignored.
+        #[ink(message)]
+        fn transfer(&mut self, to: AccountId, id: Id, data: Vec<u8>) -> core::result::Result<(), PSP34Error>;
+
+        /// Returns the number of live products.
+        #[ink(message)]
+        fn total_supply(&self) -> Balance;
+    }
+
+    /// Core of the product registry, for contracts that work with any
+    /// registry implementation. Selectors are composed from the trait name,
+    /// e.g. `ProductRegistry::create_product`, and leave the ones of the
+    /// inherent messages unchanged.
+    #[ink_lang::trait_definition]
+    pub trait ProductRegistry {
+        /// See `ProductFactory::create_product`.
+        #[ink(message)]
+        fn create_product(&mut self, code: u16, quantity: Option<u32>, warranty_until: Option<Timestamp>) -> Result<u32>;
+
+        /// See `ProductFactory::delegate_product`.
+        #[ink(message)]
+        fn delegate_product(&mut self, pid: u32, delegate_to: AccountId, deadline: Option<Timestamp>, price: Option<Balance>, memo: Option<Vec<u8>>) -> Result<()>;
+
+        /// See `ProductFactory::accept_product`.
+        #[ink(message)]
+        fn accept_product(&mut self, pid: u32) -> Result<()>;
+
+        #[ink(message)]
+        fn get_product(&self, pid: u32) -> Result<Product>;
+
+        #[ink(message)]
+        fn product_count(&self) -> u32;
+    }
+
+    /// Maximum number of items a single batch message may operate on.
+    pub const MAX_BATCH_SIZE: usize = 100;
+
+    /// Maximum number of accounts a delegation made with `delegate_to_any`
+    /// or `delegate_product_multisig` may name.
+    pub const MAX_CANDIDATES: usize = 5;
+
+    /// Maximum length in bytes of a product name.
+    pub const MAX_NAME_LEN: usize = 64;
+
+    /// Maximum length in bytes of a product description.
+    pub const MAX_DESCRIPTION_LEN: usize = 256;
+
+    /// Number of sensor readings kept per product until the admin changes
+    /// it with `set_max_readings`.
+    pub const DEFAULT_MAX_READINGS: u32 = 100;
+
+    /// Maximum length in bytes of a product note.
+    pub const MAX_NOTE_LEN: usize = 256;
+
+    /// Maximum number of notes a product can carry.
+    pub const MAX_NOTES: u32 = 32;
+
+    /// Maximum length in bytes of a checkpoint location.
+    pub const MAX_LOCATION_LEN: usize = 64;
+
+    /// Maximum length in bytes of a delegation memo.
+    pub const MAX_MEMO_LEN: usize = 128;
+
+    /// Maximum length in bytes of an off-chain metadata URI.
+    pub const MAX_URI_LEN: usize = 128;
+
+    /// Maximum number of entries a single paginated query returns.
+    pub const MAX_PAGE_SIZE: u32 = 100;
+
+    /// Maximum number of pids a single `export_products` call looks at,
+    /// burned ones included.
+    pub const MAX_EXPORT_SCAN: u32 = 1_000;
+
+    /// Maximum number of pids a single `compute_products_root` call folds
+    /// in.
+    pub const MAX_ROOT_CHUNK: u32 = 1_000;
+
+    /// Version of the storage layout written by this code. An instance keeps
+    /// the code it was deployed with, so bump it whenever stored entries
+    /// change to tell instances of the new layout apart.
+    pub const SCHEMA_VERSION: u32 = 3;
+
+    /// Number of blocks between `announce_termination` and the earliest
+    /// `terminate`, about a day at six second blocks.
+    pub const TERMINATION_DELAY: BlockNumber = 14_400;
+
+    /// Selector of the registry message notified of every ownership change,
+    /// `on_ownership_changed(pid: u32, old_owner: AccountId, new_owner:
+    /// AccountId)`. A burned product changes hands to the zero address.
+    pub const OWNERSHIP_CHANGED_SELECTOR: [u8; 4] = [0x59, 0x4D, 0xCD, 0x19];
+
+    /// Storage key of the reentrancy guard, the blake2b-256 hash of
+    /// `product_factory::reentrancy_guard` so it cannot clash with the keys
+    /// of the contract fields.
+    const REENTRANCY_GUARD_KEY: [u8; 32] = [
+        0x73, 0x57, 0x23, 0xE9, 0x94, 0x64, 0xDC, 0x72, 0xAE, 0xF3, 0xC4, 0x66, 0xCC, 0xE0, 0x47, 0xBC,
+        0xDA, 0x52, 0x8B, 0xEF, 0xD1, 0xEE, 0xFB, 0x80, 0xD6, 0x96, 0xBD, 0x21, 0x7D, 0xE7, 0xB6, 0x9E,
+    ];
+
+    /// Held for the whole of a message that transfers value or calls
+    /// another contract, so a call back into any such message fails with
+    /// `ReentrancyDetected` instead of running on half-written state.
+    ///
+    /// The flag is written straight to contract storage. The fields of
+    /// `ProductFactory` are only written back once a message returns, so a
+    /// flag kept there would still read as unset to a call entering mid
+    /// message. Dropping the guard clears it, on `Ok` and `Err` alike; a
+    /// trap reverts it with everything else.
+    struct ReentrancyGuard;
+
+    impl ReentrancyGuard {
+        fn enter() -> Result<ReentrancyGuard>{
+            let key = ink_primitives::Key::from(REENTRANCY_GUARD_KEY);
+            if ink_env::get_contract_storage::<bool>(&key).ok().flatten().unwrap_or(false){
+                return Err(Error::ReentrancyDetected)
+            }
+            ink_env::set_contract_storage(&key, &true);
+            Ok(ReentrancyGuard)
+        }
+    }
+
+    impl Drop for ReentrancyGuard {
+        fn drop(&mut self){
+            ink_env::clear_contract_storage(&ink_primitives::Key::from(REENTRANCY_GUARD_KEY));
+        }
+    }
+
+    /// Share of a product held by its sole owner, in basis points.
+    pub const FULL_SHARE_BPS: u16 = 10_000;
+
+    /// Highest royalty a product can carry, in basis points.
+    pub const MAX_ROYALTY_BPS: u16 = 1000;
+
+    /// Number of blocks after an accepted delegation during which either
+    /// party may dispute it.
+    pub const DISPUTE_WINDOW: BlockNumber = 100;
+
+    /// Emitted whenever a new product is created.
+    #[ink(event)]
+    pub struct ProductCreated {
+        #[ink(topic)]
+        pid: u32,
+        code: u16,
+        #[ink(topic)]
+        owner: AccountId,
+        creator: AccountId,
+        /// Hash identifier of the product, see `id_of`.
+        #[ink(topic)]
+        id: Hash,
+        /// Production run the product belongs to, see `create_lot`.
+        lot_id: Option<u32>,
+        created_at: Timestamp,
+        created_block: BlockNumber,
+    }
+
+    /// Emitted on every change of the state of a product, alongside the
+    /// event of the action behind it. States are given by their encoding,
+    /// see `State`. Freezing leaves the state alone, `ProductFrozen` and
+    /// `ProductUnfrozen` are the only events reporting it.
+    #[ink(event)]
+    pub struct StateChanged {
+        #[ink(topic)]
+        pid: u32,
+        from: u8,
+        to: u8,
+        #[ink(topic)]
+        actor: AccountId,
+    }
+
+    /// Emitted whenever an owner delegates a product to another account.
+    #[ink(event)]
+    pub struct ProductDelegated {
+        #[ink(topic)]
+        pid: u32,
+        #[ink(topic)]
+        from: AccountId,
+        #[ink(topic)]
+        to: AccountId,
+        memo: Option<Vec<u8>>,
+    }
+
+    /// Emitted whenever a delegate accepts a product and becomes its owner.
+    #[ink(event)]
+    pub struct ProductAccepted {
+        #[ink(topic)]
+        pid: u32,
+        #[ink(topic)]
+        previous_owner: AccountId,
+        #[ink(topic)]
+        new_owner: AccountId,
+        /// Hash identifier of the product, see `id_of`.
+        id: Hash,
+    }
+
+    /// Emitted whenever a signer confirms a multisig delegation,
+    /// `confirmations` counting it.
+    #[ink(event)]
+    pub struct AcceptanceConfirmed {
+        #[ink(topic)]
+        pid: u32,
+        #[ink(topic)]
+        signer: AccountId,
+        confirmations: u8,
+    }
+
+    /// Emitted whenever a delegate rejects a pending delegation.
+    #[ink(event)]
+    pub struct DelegationRejected {
+        #[ink(topic)]
+        pid: u32,
+        #[ink(topic)]
+        owner: AccountId,
+        #[ink(topic)]
+        delegate: AccountId,
+    }
+
+    /// Emitted whenever an owner revokes a pending delegation.
+    #[ink(event)]
+    pub struct DelegationRevoked {
+        #[ink(topic)]
+        pid: u32,
+        #[ink(topic)]
+        owner: AccountId,
+        #[ink(topic)]
+        delegate: AccountId,
+    }
+
+    /// Emitted whenever an owner transfers a product directly to another account.
+    #[ink(event)]
+    pub struct ProductTransferred {
+        #[ink(topic)]
+        pid: u32,
+        #[ink(topic)]
+        from: AccountId,
+        #[ink(topic)]
+        to: AccountId,
+        /// Hash identifier of the product, see `id_of`.
+        id: Hash,
+    }
+
+    /// Emitted whenever an expired delegation is cleaned up.
+    #[ink(event)]
+    pub struct DelegationExpired {
+        #[ink(topic)]
+        pid: u32,
+        #[ink(topic)]
+        owner: AccountId,
+        #[ink(topic)]
+        delegate: AccountId,
+    }
+
+    /// Emitted whenever an owner permanently burns a product.
+    #[ink(event)]
+    pub struct ProductBurned {
+        #[ink(topic)]
+        pid: u32,
+        #[ink(topic)]
+        owner: AccountId,
+        /// Hash identifier of the product, see `id_of`.
+        id: Hash,
+    }
+
+    /// Emitted whenever the owner changes the metadata URI of a product.
+    #[ink(event)]
+    pub struct MetadataUpdated {
+        #[ink(topic)]
+        pid: u32,
+        uri: Vec<u8>,
+    }
+
+    /// Emitted whenever the admin recalls a product. `delegate` is set when
+    /// the recall cancelled a pending delegation.
+    #[ink(event)]
+    pub struct ProductRecalled {
+        #[ink(topic)]
+        pid: u32,
+        #[ink(topic)]
+        owner: AccountId,
+        delegate: Option<AccountId>,
+    }
+
+    /// Emitted whenever the admin lifts a recall.
+    #[ink(event)]
+    pub struct RecallResolved {
+        #[ink(topic)]
+        pid: u32,
+        #[ink(topic)]
+        owner: AccountId,
+    }
+
+    /// Emitted whenever `amount` units of `pid` are split into `new_pid`.
+    #[ink(event)]
+    pub struct ProductSplit {
+        #[ink(topic)]
+        pid: u32,
+        #[ink(topic)]
+        new_pid: u32,
+        amount: u32,
+    }
+
+    /// Emitted whenever `source_pid` is folded into `target_pid`, which now
+    /// holds `quantity` units.
+    #[ink(event)]
+    pub struct ProductsMerged {
+        #[ink(topic)]
+        target_pid: u32,
+        #[ink(topic)]
+        source_pid: u32,
+        quantity: u32,
+    }
+
+    /// Emitted whenever `components` are locked into `parent_pid`.
+    #[ink(event)]
+    pub struct Assembled {
+        #[ink(topic)]
+        parent_pid: u32,
+        components: Vec<u32>,
+    }
+
+    /// Emitted whenever `parent_pid` is taken apart, releasing `components`.
+    #[ink(event)]
+    pub struct Disassembled {
+        #[ink(topic)]
+        parent_pid: u32,
+        #[ink(topic)]
+        owner: AccountId,
+        components: Vec<u32>,
+    }
+
+    #[ink(event)]
+    pub struct ProductCertified {
+        #[ink(topic)]
+        pid: u32,
+        #[ink(topic)]
+        certifier: AccountId,
+        cert_code: u16,
+        document_hash: Hash,
+    }
+
+    /// Emitted whenever the certificate `cert_code` issued by `certifier`
+    /// is withdrawn by `revoked_by`, the certifier itself or the admin.
+    #[ink(event)]
+    pub struct CertificationRevoked {
+        #[ink(topic)]
+        pid: u32,
+        #[ink(topic)]
+        certifier: AccountId,
+        cert_code: u16,
+        revoked_by: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct DisputeRaised {
+        #[ink(topic)]
+        pid: u32,
+        #[ink(topic)]
+        raised_by: AccountId,
+        reason_hash: Hash,
+    }
+
+    /// Emitted whenever the arbiter resolves a dispute, leaving `pid` with
+    /// `owner`.
+    #[ink(event)]
+    pub struct DisputeResolved {
+        #[ink(topic)]
+        pid: u32,
+        #[ink(topic)]
+        owner: AccountId,
+        /// Hash identifier of the product, see `id_of`.
+        id: Hash,
+        returned_to_previous_owner: bool,
+    }
+
+    /// Emitted whenever `account` pulls its sale proceeds.
+    #[ink(event)]
+    pub struct Withdrawn {
+        #[ink(topic)]
+        account: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct ProductListed {
+        #[ink(topic)]
+        pid: u32,
+        price: Balance,
+    }
+
+    #[ink(event)]
+    pub struct ProductUnlisted {
+        #[ink(topic)]
+        pid: u32,
+    }
+
+    /// Emitted whenever `buyer` buys the listed `pid` from `seller`.
+    #[ink(event)]
+    pub struct ProductSold {
+        #[ink(topic)]
+        pid: u32,
+        #[ink(topic)]
+        seller: AccountId,
+        #[ink(topic)]
+        buyer: AccountId,
+        /// Hash identifier of the product, see `id_of`.
+        id: Hash,
+        price: Balance,
+    }
+
+    #[ink(event)]
+    pub struct AuctionStarted {
+        #[ink(topic)]
+        pid: u32,
+        min_bid: Balance,
+        end_block: BlockNumber,
+    }
+
+    #[ink(event)]
+    pub struct ProductLent {
+        #[ink(topic)]
+        pid: u32,
+        #[ink(topic)]
+        owner: AccountId,
+        #[ink(topic)]
+        borrower: AccountId,
+        until: BlockNumber,
+    }
+
+    #[ink(event)]
+    pub struct ProductReturned {
+        #[ink(topic)]
+        pid: u32,
+        #[ink(topic)]
+        borrower: AccountId,
+    }
+
+    /// Emitted whenever an owner takes a lent product back after its loan
+    /// ran out.
+    #[ink(event)]
+    pub struct ProductReclaimed {
+        #[ink(topic)]
+        pid: u32,
+        #[ink(topic)]
+        borrower: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct BidPlaced {
+        #[ink(topic)]
+        pid: u32,
+        #[ink(topic)]
+        bidder: AccountId,
+        amount: Balance,
+    }
+
+    /// Emitted whenever an auction ends, `winner` is `None` when nobody bid.
+    #[ink(event)]
+    pub struct AuctionFinalized {
+        #[ink(topic)]
+        pid: u32,
+        /// Hash identifier of the product, see `id_of`.
+        id: Hash,
+        winner: Option<AccountId>,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct RoyaltySet {
+        #[ink(topic)]
+        pid: u32,
+        royalty_bps: u16,
+    }
+
+    /// Emitted whenever part of a co-owned product changes hands, including
+    /// the share `add_co_owner` carves off the owner's.
+    #[ink(event)]
+    pub struct ShareTransferred {
+        #[ink(topic)]
+        pid: u32,
+        #[ink(topic)]
+        from: AccountId,
+        #[ink(topic)]
+        to: AccountId,
+        share_bps: u16,
+    }
+
+    #[ink(event)]
+    pub struct ActionApproved {
+        #[ink(topic)]
+        pid: u32,
+        #[ink(topic)]
+        co_owner: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct CheckpointAdded {
+        #[ink(topic)]
+        pid: u32,
+        #[ink(topic)]
+        author: AccountId,
+        index: u32,
+    }
+
+    #[ink(event)]
+    pub struct NoteAdded {
+        #[ink(topic)]
+        pid: u32,
+        #[ink(topic)]
+        author: AccountId,
+        index: u32,
+    }
+
+    /// Emitted whenever a sensor reading falls outside the range set with
+    /// `set_reading_range`.
+    #[ink(event)]
+    pub struct ReadingOutOfRange {
+        #[ink(topic)]
+        pid: u32,
+        value: i32,
+        unit: u8,
+        min: i32,
+        max: i32,
+    }
+
+    #[ink(event)]
+    pub struct StageAdvanced {
+        #[ink(topic)]
+        pid: u32,
+        from: Stage,
+        to: Stage,
+    }
+
+    #[ink(event)]
+    pub struct WarrantySet {
+        #[ink(topic)]
+        pid: u32,
+        warranty_until: Option<Timestamp>,
+    }
+
+    #[ink(event)]
+    pub struct WarrantyClaimed {
+        #[ink(topic)]
+        pid: u32,
+        #[ink(topic)]
+        owner: AccountId,
+        claim_id: u32,
+        claim_hash: Hash,
+    }
+
+    #[ink(event)]
+    pub struct CodeUpdated {
+        #[ink(topic)]
+        pid: u32,
+        old_code: u16,
+        new_code: u16,
+    }
+
+    #[ink(event)]
+    pub struct TerminationAnnounced {
+        announced_at: BlockNumber,
+    }
+
+    #[ink(event)]
+    pub struct VerificationRequested {
+        #[ink(topic)]
+        pid: u32,
+        #[ink(topic)]
+        owner: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct Verified {
+        #[ink(topic)]
+        pid: u32,
+        report_hash: Hash,
+    }
+
+    /// Emitted whenever the oracle reports a failed inspection, `frozen`
+    /// telling whether the product got frozen for it.
+    #[ink(event)]
+    pub struct VerificationFailed {
+        #[ink(topic)]
+        pid: u32,
+        report_hash: Hash,
+        frozen: bool,
+    }
+
+    #[ink(event)]
+    pub struct ProductFrozen {
+        #[ink(topic)]
+        pid: u32,
+    }
+
+    #[ink(event)]
+    pub struct ProductUnfrozen {
+        #[ink(topic)]
+        pid: u32,
+    }
+
+    #[ink(event)]
+    pub struct ProductArchived {
+        #[ink(topic)]
+        pid: u32,
+    }
+
+    #[ink(event)]
+    pub struct ProductUnarchived {
+        #[ink(topic)]
+        pid: u32,
+    }
+
+    /// Emitted whenever the admin changes a field of the configuration,
+    /// given by its `ConfigField` encoding. The new value can be read with
+    /// `get_config`.
+    #[ink(event)]
+    pub struct ConfigChanged {
+        #[ink(topic)]
+        field: u8,
+        #[ink(topic)]
+        admin: AccountId,
+    }
+
+    /// Emitted after every privileged action that went through, given by
+    /// its `AdminActionKind` encoding, with the account or product it
+    /// targeted, if any. `AcceptAdmin` is reported by the new admin with
+    /// the previous one as its subject.
+    #[ink(event)]
+    pub struct AdminAction {
+        #[ink(topic)]
+        admin: AccountId,
+        action: u8,
+        subject: Option<AccountId>,
+        pid: Option<u32>,
+    }
+
+    #[ink(event)]
+    pub struct Paused {
+        #[ink(topic)]
+        admin: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct Unpaused {
+        #[ink(topic)]
+        admin: AccountId,
+    }
+
+    /// PSP34 `Transfer`, emitted alongside `ProductTransferred` whenever a
+    /// product moves through `transfer_product` or PSP34 `transfer`.
+    #[ink(event)]
+    pub struct Transfer {
+        #[ink(topic)]
+        from: Option<AccountId>,
+        #[ink(topic)]
+        to: Option<AccountId>,
+        #[ink(topic)]
+        id: Id,
+    }
+
+    /// PSP34 `Approval`, `id` is `None` for an approval for every product of
+    /// `owner`.
+    #[ink(event)]
+    pub struct Approval {
+        #[ink(topic)]
+        owner: AccountId,
+        #[ink(topic)]
+        operator: AccountId,
+        #[ink(topic)]
+        id: Option<Id>,
+        approved: bool,
+    }
+
+    #[ink(event)]
+    pub struct ApprovalForAll {
+        #[ink(topic)]
+        owner: AccountId,
+        #[ink(topic)]
+        operator: AccountId,
+        approved: bool,
+    }
+
+    #[ink(event)]
+    pub struct FeesWithdrawn {
+        #[ink(topic)]
+        to: AccountId,
+        amount: Balance,
+    }
+
+    /// Defines the storage of your contract.
+    /// Add new fields to the below struct in order
+    /// to add new static storage fields to your contract.
+    #[ink(storage)]
+    pub struct ProductFactory{
+        /// The fields every lifecycle message touches. The name,
+        /// description and metadata URI live in `metadata` and
+        /// `metadata_uris` so that delegating or transferring a product does
+        /// not load and rewrite them.
+        products: StorageHashMap<u32, StoredProduct>,
+        /// Name and description of each product that has some.
+        metadata: StorageHashMap<u32, (Vec<u8>, Vec<u8>)>,
+        /// Off-chain metadata URI of each product that has one.
+        metadata_uris: StorageHashMap<u32, Vec<u8>>,
+        next_pid: u32,
+        owned_products: StorageHashMap<AccountId, Vec<u32>>,
+        /// Ownership history entries keyed by `(pid, index)`, oldest first.
+        history: StorageHashMap<(u32, u32), (AccountId, Timestamp)>,
+        history_lens: StorageHashMap<u32, u32>,
+        /// Location checkpoints keyed by `(pid, index)`, oldest first.
+        checkpoints: StorageHashMap<(u32, u32), (AccountId, Timestamp, Vec<u8>)>,
+        checkpoint_counts: StorageHashMap<u32, u32>,
+        /// Owner notes keyed by `(pid, index)` as `(author, timestamp,
+        /// note)`, oldest first.
+        notes: StorageHashMap<(u32, u32), (AccountId, Timestamp, Vec<u8>)>,
+        note_counts: StorageHashMap<u32, u32>,
+        /// Device account allowed to record sensor readings per product.
+        sensors: StorageHashMap<u32, AccountId>,
+        /// Sensor readings keyed by `(pid, index)` as `(timestamp, value,
+        /// unit)`. Only the latest `max_readings` per product are kept.
+        readings: StorageHashMap<(u32, u32), (Timestamp, i32, u8)>,
+        /// Number of readings ever recorded per product.
+        reading_counts: StorageHashMap<u32, u32>,
+        /// Inclusive `(min, max)` range readings are expected in.
+        reading_ranges: StorageHashMap<u32, (i32, i32)>,
+        max_readings: u32,
+        /// Codes already used by a product. Burning a product does not free
+        /// its code.
+        code_taken: StorageHashMap<u16, bool>,
+        /// Live pids by product code.
+        code_index: StorageHashMap<u16, Vec<u32>>,
+        /// Live pids by production run, see `create_lot`.
+        lots: StorageHashMap<u32, Vec<u32>>,
+        /// Id the next production run gets.
+        next_lot_id: u32,
+        admin: AccountId,
+        /// Account nominated by `transfer_admin`, until it accepts.
+        pending_admin: Option<AccountId>,
+        /// While set, every message that changes products fails with `Paused`.
+        paused: bool,
+        /// Accounts allowed to create products.
+        manufacturers: StorageHashMap<AccountId, bool>,
+        /// Operator allowed to delegate a product for its owner. Cleared
+        /// whenever the product changes hands.
+        approvals: StorageHashMap<u32, AccountId>,
+        /// `(owner, operator)` pairs where the operator may delegate and
+        /// transfer any product of the owner.
+        operator_approvals: StorageHashMap<(AccountId, AccountId), bool>,
+        /// Shares of co-owned products in basis points, summing to
+        /// `FULL_SHARE_BPS` per product. Products owned by a single account
+        /// have no entries.
+        shares: StorageHashMap<(u32, AccountId), u16>,
+        /// Holders of a share of each co-owned product, the owner first.
+        co_owners: StorageHashMap<u32, Vec<AccountId>>,
+        /// Co-owners that approved handing a co-owned product over.
+        action_approvals: StorageHashMap<u32, Vec<AccountId>>,
+        config: Config,
+        /// Pids pending delegation by delegate.
+        pending_delegations: StorageHashMap<AccountId, Vec<u32>>,
+        /// Delegations made with `delegate_product_multisig` still waiting
+        /// for confirmations.
+        multisig_delegations: StorageHashMap<u32, MultisigDelegation>,
+        /// Number of products per owner, kept alongside `owned_products` so
+        /// counting does not load the pid lists.
+        owned_counts: StorageHashMap<AccountId, u32>,
+        /// Accounts barred from creating or receiving products.
+        blacklist: StorageHashMap<AccountId, bool>,
+        /// Pids locked into each assembled parent.
+        components: StorageHashMap<u32, Vec<u32>>,
+        /// Accounts allowed to certify products.
+        certifiers: StorageHashMap<AccountId, bool>,
+        /// Certificates attached to each product as `(certifier, cert_code,
+        /// document_hash)`.
+        certifications: StorageHashMap<u32, Vec<(AccountId, u16, Hash)>>,
+        /// Certificates revoked from each product, kept so that they can
+        /// still be looked up.
+        revoked_certifications: StorageHashMap<u32, Vec<(AccountId, u16, Hash)>>,
+        /// Warranty claims per product as `(owner, timestamp, claim_hash)`,
+        /// indexed by claim id.
+        warranty_claims: StorageHashMap<u32, Vec<(AccountId, Timestamp, Hash)>>,
+        /// Whether a failed verification freezes the product.
+        freeze_on_failed_verification: bool,
+        /// Previous owner and block of the last accepted delegation of each
+        /// product, for disputes.
+        last_acceptance: StorageHashMap<u32, (AccountId, BlockNumber)>,
+        /// Sale proceeds each account can pull with `withdraw`.
+        withdrawable: StorageHashMap<AccountId, Balance>,
+        /// Sum of `withdrawable` and the highest bids of running auctions,
+        /// held back from `withdraw_fees`.
+        escrowed: Balance,
+        /// Running auction of each auctioned product.
+        auctions: StorageHashMap<u32, Auction>,
+        /// Block `announce_termination` was called in.
+        termination_announced_at: Option<BlockNumber>,
+        registry_policy: RegistryPolicy,
+        /// Hash identifier of each product, see `id_of`.
+        ids: StorageHashMap<u32, Hash>,
+        /// Pid of each hash identifier.
+        pids_by_id: StorageHashMap<Hash, u32>,
+        /// Number of products minted with each creator, hashed into the ids.
+        creation_nonces: StorageHashMap<AccountId, u64>,
+        /// Last finalized Merkle root of the products and its block.
+        products_root: Option<(Hash, BlockNumber)>,
+        /// Whether a product changed since `products_root` was finalized.
+        root_stale: bool,
+        /// Root computation in progress, if any.
+        root_build: Option<RootBuild>,
+    }
+
+    /// Every message and the constructor carry an explicit selector so that
+    /// renaming them does not break clients encoding calls by selector. The
+    /// selectors equal the ones ink! derived from the original names, i.e.
+    /// the first four bytes of the BLAKE2b-256 hash of the name. New messages
+    /// pin the selector derived from their name the same way;
+    /// `selector_tests` guards the whole table.
+    impl ProductFactory {
+        /// The instantiator becomes admin and the first registered
+        /// manufacturer. `max_products` caps the number of live products,
+        /// `0` leaves it unlimited.
+        #[ink(constructor, selector = "0x9BAE9D5E")]
+        pub fn new(creation_fee: Balance, max_products: u32)->  Self{
+            let caller = Self::env().caller();
+            let mut manufacturers = StorageHashMap::new();
+            manufacturers.insert(caller, true);
+            Self{
+                products:StorageHashMap::new(),
+                metadata:StorageHashMap::new(),
+                metadata_uris:StorageHashMap::new(),
+                next_pid:0,
+                owned_products:StorageHashMap::new(),
+                history:StorageHashMap::new(),
+                history_lens:StorageHashMap::new(),
+                checkpoints:StorageHashMap::new(),
+                checkpoint_counts:StorageHashMap::new(),
+                notes:StorageHashMap::new(),
+                note_counts:StorageHashMap::new(),
+                sensors:StorageHashMap::new(),
+                readings:StorageHashMap::new(),
+                reading_counts:StorageHashMap::new(),
+                reading_ranges:StorageHashMap::new(),
+                max_readings:DEFAULT_MAX_READINGS,
+                code_taken:StorageHashMap::new(),
+                code_index:StorageHashMap::new(),
+                lots:StorageHashMap::new(),
+                next_lot_id:0,
+                admin:caller,
+                pending_admin:None,
+                paused:false,
+                manufacturers,
+                approvals:StorageHashMap::new(),
+                operator_approvals:StorageHashMap::new(),
+                shares:StorageHashMap::new(),
+                co_owners:StorageHashMap::new(),
+                action_approvals:StorageHashMap::new(),
+                config:Config {
+                    creation_fee,
+                    max_products,
+                    max_per_owner:0,
+                    arbiter:None,
+                    oracle:None,
+                    registry:None,
+                },
+                pending_delegations:StorageHashMap::new(),
+                multisig_delegations:StorageHashMap::new(),
+                owned_counts:StorageHashMap::new(),
+                blacklist:StorageHashMap::new(),
+                components:StorageHashMap::new(),
+                certifiers:StorageHashMap::new(),
+                certifications:StorageHashMap::new(),
+                revoked_certifications:StorageHashMap::new(),
+                warranty_claims:StorageHashMap::new(),
+                freeze_on_failed_verification:false,
+                last_acceptance:StorageHashMap::new(),
+                withdrawable:StorageHashMap::new(),
+                escrowed:0,
+                auctions:StorageHashMap::new(),
+                termination_announced_at:None,
+                registry_policy:RegistryPolicy::Ignore,
+                ids:StorageHashMap::new(),
+                pids_by_id:StorageHashMap::new(),
+                creation_nonces:StorageHashMap::new(),
+                products_root:None,
+                root_stale:true,
+                root_build:None,
+            }        
+
+        }
+        
+    
+        /// Creates a product owned by the caller. Codes are unique across
+        /// the registry, a taken code fails with `DuplicateCode`. Only
+        /// `split_product` and `create_lot` create further products with the
+        /// same code.
+        ///
+        /// `quantity` makes the product a lot of that many units, it
+        /// defaults to `1` and must not be zero. `warranty_until` starts a
+        /// warranty running up to that timestamp, see `claim_warranty`.
+        ///
+        /// The call must carry at least the creation fee.
+        #[ink(message, payable, selector = "0x3C62910F")]
+        pub fn create_product(&mut self, code: u16, quantity: Option<u32>, warranty_until: Option<Timestamp>) -> Result<u32>{
+            self.paying(|this| {
+                let _guard = ReentrancyGuard::enter()?;
+                this.ensure_not_paused()?;
+                this.ensure_manufacturer()?;
+                this.ensure_not_blacklisted(Self::env().caller())?;
+                let surplus = this.ensure_fee_paid(1)?;
+                this.ensure_code_available(code)?;
+                let quantity = quantity.unwrap_or(1);
+                if quantity == 0{
+                    return Err(Error::InvalidQuantity)
+                }
+                let pid = this.mint(Self::env().caller(), Self::env().caller(), code, Vec::new(), Vec::new(), None)?;
+                let p = this.product_mut(pid).expect("product was minted");
+                p.quantity = quantity;
+                p.warranty_until = warranty_until;
+                Self::refund(surplus);
+                Ok(pid)
+            })
+        }
+
+        /// Creates a product carrying a human readable name and description.
+        /// Fields longer than `MAX_NAME_LEN` and `MAX_DESCRIPTION_LEN` are
+        /// rejected rather than truncated.
+        #[ink(message, payable, selector = "0x043C15B6")]
+        pub fn create_product_with_metadata(&mut self, code: u16, name: Vec<u8>, description: Vec<u8>) -> Result<u32>{
+            self.paying(|this| {
+                let _guard = ReentrancyGuard::enter()?;
+                this.ensure_not_paused()?;
+                this.ensure_manufacturer()?;
+                this.ensure_not_blacklisted(Self::env().caller())?;
+                let surplus = this.ensure_fee_paid(1)?;
+                if name.len() > MAX_NAME_LEN || description.len() > MAX_DESCRIPTION_LEN{
+                    return Err(Error::MetadataTooLong)
+                }
+                this.ensure_code_available(code)?;
+                let pid = this.mint(Self::env().caller(), Self::env().caller(), code, name, description, None)?;
+                Self::refund(surplus);
+                Ok(pid)
+            })
+        }
+
+        /// Creates one product per code. Nothing is created if any code is
+        /// already taken or appears twice in `codes`. The call must carry the
+        /// creation fee once per code.
+        #[ink(message, payable, selector = "0xB68D7497")]
+        pub fn create_products(&mut self, codes: Vec<u16>) -> Result<Vec<u32>>{
+            self.paying(|this| {
+                let _guard = ReentrancyGuard::enter()?;
+                this.ensure_not_paused()?;
+                this.ensure_manufacturer()?;
+                this.ensure_not_blacklisted(Self::env().caller())?;
+                if codes.len() > MAX_BATCH_SIZE{
+                    return Err(Error::BatchTooLarge)
+                }
+                let surplus = this.ensure_fee_paid(codes.len() as u32)?;
+                for (i, code) in codes.iter().enumerate(){
+                    if codes[..i].contains(code){
+                        return Err(Error::DuplicateCode)
+                    }
+                    this.ensure_code_available(*code)?;
+                }
+                let owner = Self::env().caller();
+                this.ensure_mintable(owner, codes.len() as u32)?;
+                this.ensure_quota(owner, codes.len() as u32)?;
+                let pids = codes.into_iter().map(|code| this.mint(owner, owner, code, Vec::new(), Vec::new(), None)).collect::<Result<_>>()?;
+                Self::refund(surplus);
+                Ok(pids)
+            })
+        }
+
+        /// Creates a production run of `count` identical products labelled
+        /// with `code`, at most `MAX_BATCH_SIZE`, and returns the id of the
+        /// run with the pids of its products. The call must carry the
+        /// creation fee once per product.
+        #[ink(message, payable, selector = "0xC19A43C3")]
+        pub fn create_lot(&mut self, code: u16, count: u32) -> Result<(u32, Vec<u32>)>{
+            self.paying(|this| {
+                let _guard = ReentrancyGuard::enter()?;
+                this.ensure_not_paused()?;
+                this.ensure_manufacturer()?;
+                this.ensure_not_blacklisted(Self::env().caller())?;
+                if count == 0{
+                    return Err(Error::InvalidQuantity)
+                }
+                if count as usize > MAX_BATCH_SIZE{
+                    return Err(Error::BatchTooLarge)
+                }
+                let surplus = this.ensure_fee_paid(count)?;
+                this.ensure_code_available(code)?;
+                let owner = Self::env().caller();
+                this.ensure_mintable(owner, count)?;
+                this.ensure_quota(owner, count)?;
+                let lot_id = this.next_lot_id;
+                this.next_lot_id = lot_id.checked_add(1).ok_or(Error::ArithmeticOverflow)?;
+                let pids = (0..count)
+                    .map(|_| this.mint(owner, owner, code, Vec::new(), Vec::new(), Some(lot_id)))
+                    .collect::<Result<_>>()?;
+                Self::refund(surplus);
+                Ok((lot_id, pids))
+            })
+        }
+
+        /// Returns the pids of the live products of a production run, lots
+        /// split off them included, whoever owns them now.
+        #[ink(message, selector = "0x91E7197D")]
+        pub fn products_in_lot(&self, lot_id: u32) -> Vec<u32>{
+            self.lots.get(&lot_id).cloned().unwrap_or_default()
+        }
+
+        #[ink(message, selector = "0xD0707806")]
+        pub fn get_config(&self) -> Config{
+            self.config.clone()
+        }
+
+        /// Returns the admin, product counts, pause flag, fees, caps and
+        /// schema version at once.
+        #[ink(message, selector = "0x57F2226D")]
+        pub fn get_contract_info(&self) -> ContractInfo{
+            ContractInfo {
+                admin: self.admin,
+                pending_admin: self.pending_admin,
+                product_count: self.next_pid,
+                live_product_count: self.products.len(),
+                paused: self.paused,
+                creation_fee: self.config.creation_fee,
+                max_products: self.config.max_products,
+                max_per_owner: self.config.max_per_owner,
+                schema_version: SCHEMA_VERSION,
+                termination_announced_at: self.termination_announced_at,
+            }
+        }
+
+        fn emit_config_changed(field: ConfigField){
+            Self::env().emit_event(ConfigChanged {
+                field: field as u8,
+                admin: Self::env().caller(),
+            });
+        }
+
+        #[ink(message, selector = "0xFD96B0AD")]
+        pub fn get_creation_fee(&self) -> Balance{
+            self.config.creation_fee
+        }
+
+        #[ink(message, selector = "0x78BC642F")]
+        pub fn set_creation_fee(&mut self, fee: Balance) -> Result<()>{
+            self.as_admin(AdminActionKind::SetCreationFee, None, None, |this| {
+                this.config.creation_fee = fee;
+                Self::emit_config_changed(ConfigField::CreationFee);
+                Ok(())
+            })
+        }
+
+        /// Returns the cap on the number of live products, `0` when there is
+        /// none.
+        #[ink(message, selector = "0x8F937EB0")]
+        pub fn get_max_products(&self) -> u32{
+            self.config.max_products
+        }
+
+        /// Sets the cap on the number of live products, `0` lifts it. A cap
+        /// below the products already live fails with `MaxProductsTooLow`.
+        /// Admin only.
+        #[ink(message, selector = "0x17EAF32D")]
+        pub fn set_max_products(&mut self, max_products: u32) -> Result<()>{
+            self.as_admin(AdminActionKind::SetMaxProducts, None, None, |this| {
+                if max_products != 0 && max_products < this.products.len(){
+                    return Err(Error::MaxProductsTooLow)
+                }
+                this.config.max_products = max_products;
+                Self::emit_config_changed(ConfigField::MaxProducts);
+                Ok(())
+            })
+        }
+
+        /// Returns the cap on the products a single account may own, `0`
+        /// when there is none.
+        #[ink(message, selector = "0xD7C7409B")]
+        pub fn get_max_per_owner(&self) -> u32{
+            self.config.max_per_owner
+        }
+
+        /// Sets the cap on the products a single account may own, `0` lifts
+        /// it. It counts the products owned now, so handing one over frees
+        /// its place. Accounts already over a lowered cap keep their
+        /// products but cannot take on more. Admin only.
+        #[ink(message, selector = "0x3FE386E9")]
+        pub fn set_max_per_owner(&mut self, max_per_owner: u32) -> Result<()>{
+            self.as_admin(AdminActionKind::SetMaxPerOwner, None, None, |this| {
+                this.config.max_per_owner = max_per_owner;
+                Self::emit_config_changed(ConfigField::MaxPerOwner);
+                Ok(())
+            })
+        }
+
+        /// Checks `owner` can take on `count` more products under
+        /// `max_per_owner`. Creating, accepting, buying and transferring
+        /// products check the receiving account; auctions, disputes and
+        /// disassembly settle products the account already had a claim on
+        /// and do not.
+        fn ensure_quota(&self, owner: AccountId, count: u32) -> Result<()>{
+            if self.config.max_per_owner != 0 && self.count_of(owner).saturating_add(count) > self.config.max_per_owner{
+                return Err(Error::OwnerQuotaExceeded)
+            }
+            Ok(())
+        }
+
+        /// Sends `amount` of the contract balance to `to`. An `amount` of
+        /// zero withdraws the whole balance. Sale proceeds not withdrawn yet
+        /// are not part of it.
+        #[ink(message, selector = "0xF7E92E05")]
+        pub fn withdraw_fees(&mut self, to: AccountId, amount: Balance) -> Result<()>{
+            let _guard = ReentrancyGuard::enter()?;
+            self.as_admin(AdminActionKind::WithdrawFees, Some(to), None, |this| {
+                let balance = Self::env().balance().saturating_sub(this.escrowed);
+                let amount = if amount == 0 { balance } else { amount };
+                if amount > balance{
+                    return Err(Error::TransferFailed)
+                }
+                Self::env().transfer(to, amount).map_err(|_| Error::TransferFailed)?;
+                Self::env().emit_event(FeesWithdrawn {
+                    to,
+                    amount,
+                });
+                Ok(())
+            })
+        }
+
+        /// Sends the caller all of its sale proceeds.
+        #[ink(message, selector = "0x410FCC9D")]
+        pub fn withdraw(&mut self) -> Result<()>{
+            let _guard = ReentrancyGuard::enter()?;
+            let account = Self::env().caller();
+            let amount = self.withdrawable_of(account);
+            if amount == 0{
+                return Err(Error::NothingToWithdraw)
+            }
+            if amount > Self::env().balance(){
+                return Err(Error::TransferFailed)
+            }
+            let escrowed = self.escrowed.checked_sub(amount).ok_or(Error::ArithmeticOverflow)?;
+            self.withdrawable.take(&account);
+            self.escrowed = escrowed;
+            Self::env().transfer(account, amount).expect("withdrawal was checked");
+            Self::env().emit_event(Withdrawn {
+                account,
+                amount,
+            });
+            Ok(())
+        }
+
+        #[ink(message, selector = "0xF81D7F80")]
+        pub fn withdrawable_of(&self, account: AccountId) -> Balance{
+            self.withdrawable.get(&account).copied().unwrap_or(0)
+        }
+
+        /// Credits `amount` of sale proceeds to `account`. Every withdrawable
+        /// balance is part of `escrowed`, which the paying message checked
+        /// has room for `amount`, see `ensure_escrowable`.
+        fn credit(&mut self, account: AccountId, amount: Balance){
+            if amount > 0{
+                let balance = self.withdrawable.entry(account).or_insert(0);
+                *balance = balance.checked_add(amount).expect("escrow was checked");
+                self.escrowed = self.escrowed.checked_add(amount).expect("escrow was checked");
+            }
+        }
+
+        /// Runs the body of a payable message, sending the value the call
+        /// carried back to the caller when it fails. ink! 3.0.0-rc3 returns
+        /// an `Err` without reverting the call, so the value would otherwise
+        /// stay in the contract, outside `escrowed`. A failing refund traps,
+        /// which reverts the call and the payment with it.
+        fn paying<T>(&mut self, body: impl FnOnce(&mut Self) -> Result<T>) -> Result<T>{
+            let result = body(self);
+            let paid = Self::env().transferred_balance();
+            if result.is_err() && paid > 0{
+                Self::env()
+                    .transfer(Self::env().caller(), paid)
+                    .expect("the contract holds the value the call carried");
+            }
+            result
+        }
+
+        /// Checks `amount` more can be escrowed, before a message writes
+        /// anything it would credit.
+        fn ensure_escrowable(&self, amount: Balance) -> Result<()>{
+            self.escrowed.checked_add(amount).map(|_| ()).ok_or(Error::ArithmeticOverflow)
+        }
+
+        /// Credits the price `pid` sold for, the creator's royalty rounded
+        /// down and the rest to `seller`.
+        fn credit_sale(&mut self, pid: u32, seller: AccountId, amount: Balance){
+            let p = self.products.get(&pid).expect("sold products exist");
+            let creator = p.get_creator();
+            let royalty = amount.saturating_mul(Balance::from(p.get_royalty_bps())) / 10_000;
+            self.credit(creator, royalty);
+            self.credit(seller, amount - royalty);
+        }
+
+        /// Checks the call pays for `count` products and returns the surplus
+        /// to refund.
+        fn ensure_fee_paid(&self, count: u32) -> Result<Balance>{
+            Self::ensure_paid(self.config.creation_fee.saturating_mul(Balance::from(count)), Error::InsufficientFee)
+        }
+
+        /// Checks the call carries at least `due`, failing with `underpaid`
+        /// otherwise, and returns the surplus to refund. Fails with
+        /// `RefundFailed` up front when the contract could not pay the surplus
+        /// back.
+        fn ensure_paid(due: Balance, underpaid: Error) -> Result<Balance>{
+            let paid = Self::env().transferred_balance();
+            if paid < due{
+                return Err(underpaid)
+            }
+            let surplus = paid - due;
+            if surplus > Self::env().balance(){
+                return Err(Error::RefundFailed)
+            }
+            Ok(surplus)
+        }
+
+        /// Sends `surplus` back to the caller. Runs after every storage write
+        /// of the paying message; a failing transfer traps so the whole
+        /// call is reverted.
+        fn refund(surplus: Balance){
+            if surplus > 0{
+                Self::env()
+                    .transfer(Self::env().caller(), surplus)
+                    .expect("refund was checked");
+            }
+        }
+
+        fn ensure_code_available(&self, code: u16) -> Result<()>{
+            if self.code_taken.get(&code).copied().unwrap_or(false){
+                return Err(Error::DuplicateCode)
+            }
+            Ok(())
+        }
+
+        /// Stores a new product of a single unit. `creator` differs from
+        /// `owner` only for lots split off a product that changed hands.
+        fn mint(&mut self, owner: AccountId, creator: AccountId, code: u16, name: Vec<u8>, description: Vec<u8>, lot_id: Option<u32>) -> Result<u32>{
+            self.ensure_mintable(creator, 1)?;
+            self.ensure_quota(owner, 1)?;
+            let pid = self.next_pid;
+            let next_pid = pid.checked_add(1).ok_or(Error::ArithmeticOverflow)?;
+            let id = self.assign_id(pid, creator, code)?;
+            let created_at = Self::env().block_timestamp();
+            let created_block = Self::env().block_number();
+            let mut p = StoredProduct::new(
+                State::Owned,
+                code,
+                owner,
+                created_at,
+                created_block,
+            );
+            p.creator = creator;
+            p.lot_id = lot_id;
+            self.touch_products();
+            self.products.insert(pid, p);
+            if !name.is_empty() || !description.is_empty(){
+                self.metadata.insert(pid, (name, description));
+            }
+            self.code_taken.insert(code, true);
+            self.code_index.entry(code).or_insert_with(Vec::new).push(pid);
+            if let Some(lot_id) = lot_id{
+                self.lots.entry(lot_id).or_insert_with(Vec::new).push(pid);
+            }
+            self.next_pid = next_pid;
+            self.index_add(owner, pid);
+            self.record_owner(pid, owner);
+            Self::env().emit_event(ProductCreated {
+                pid,
+                code,
+                owner,
+                creator,
+                id,
+                lot_id,
+                created_at,
+                created_block,
+            });
+            Ok(pid)
+        }
+
+        /// Checks `count` more products minted by `creator` fit under
+        /// `max_products` and overflow neither the pids nor the nonce of
+        /// `creator`, for messages minting after their first storage write or
+        /// more than once.
+        fn ensure_mintable(&self, creator: AccountId, count: u32) -> Result<()>{
+            if self.config.max_products != 0 && self.products.len().saturating_add(count) > self.config.max_products{
+                return Err(Error::MaxProductsReached)
+            }
+            let nonce = self.creation_nonces.get(&creator).copied().unwrap_or(0);
+            self.next_pid.checked_add(count)
+                .and(nonce.checked_add(u64::from(count)))
+                .map(|_| ())
+                .ok_or(Error::ArithmeticOverflow)
+        }
+
+        /// Returns the live product with the highest pid, walking back over
+        /// burned ones.
+        #[ink(message, selector = "0x9E298B06")]
+        pub fn get_last(&self) ->  Option<Product>{
+            (0..self.next_pid).rev().find_map(|pid| self.load_product(pid))
+        }
+
+        /// Returns up to `limit` products starting at pid `offset`, together
+        /// with their pids. `limit` is capped at `MAX_PAGE_SIZE` and an offset
+        /// past the end yields an empty page. Archived products are left out
+        /// of the page unless `include_archived` is set.
+        #[ink(message, selector = "0xDEC160AC")]
+        pub fn get_products(&self, offset: u32, limit: u32, include_archived: bool) -> Vec<(u32, Product)>{
+            let end = offset
+                .saturating_add(limit.min(MAX_PAGE_SIZE))
+                .min(self.next_pid);
+            (offset..end)
+                .filter_map(|pid| self.load_product(pid).map(|p| (pid, p)))
+                .filter(|(_, p)| include_archived || !p.is_archived())
+                .collect()
+        }
+
+        /// Returns up to `limit` live products from pid `cursor` on, skipping
+        /// burned pids, and the cursor to resume from, `None` once every pid
+        /// was visited. `limit` is capped at `MAX_PAGE_SIZE` and a call looks
+        /// at no more than `MAX_EXPORT_SCAN` pids, so a long run of burned
+        /// products can yield a short page that still has a next cursor.
+        #[ink(message, selector = "0xA6855CBB")]
+        pub fn export_products(&self, cursor: u32, limit: u32) -> (Vec<(u32, Product)>, Option<u32>){
+            let limit = limit.min(MAX_PAGE_SIZE) as usize;
+            let end = cursor.saturating_add(MAX_EXPORT_SCAN).min(self.next_pid);
+            let mut page = Vec::new();
+            let mut pid = cursor;
+            while pid < end && page.len() < limit{
+                if let Some(p) = self.load_product(pid){
+                    page.push((pid, p));
+                }
+                pid += 1;
+            }
+            let next = if pid < self.next_pid { Some(pid) } else { None };
+            (page, next)
+        }
+
+        /// Folds the live products from the last call on into the Merkle
+        /// root of the registry, looking at no more than `chunk` pids, capped
+        /// at `MAX_ROOT_CHUNK`. Returns the root once every pid was visited,
+        /// `None` while there are pids left. A fresh root is returned as is.
+        ///
+        /// Leaves are `RootBuild::leaf` of each live product in pid order.
+        /// Changing a product drops the computation in progress, which
+        /// starts over with the next call. Anyone may call it.
+        #[ink(message, selector = "0xF0ACCF65")]
+        pub fn compute_products_root(&mut self, chunk: u32) -> Result<Option<Hash>>{
+            if chunk == 0{
+                return Err(Error::InvalidRange)
+            }
+            if let (false, Some((root, _))) = (self.root_stale, self.products_root){
+                return Ok(Some(root))
+            }
+            let mut build = self.root_build.take().unwrap_or_default();
+            let end = build.cursor.saturating_add(chunk.min(MAX_ROOT_CHUNK)).min(self.next_pid);
+            for pid in build.cursor..end{
+                if let Some(p) = self.products.get(&pid){
+                    build.push(RootBuild::leaf(pid, p));
+                }
+            }
+            build.cursor = end;
+            if end < self.next_pid{
+                self.root_build = Some(build);
+                return Ok(None)
+            }
+            let root = build.finish();
+            self.products_root = Some((root, Self::env().block_number()));
+            self.root_stale = false;
+            Ok(Some(root))
+        }
+
+        /// Returns the last root `compute_products_root` finalized, if any.
+        #[ink(message, selector = "0x519C95CF")]
+        pub fn current_root(&self) -> Option<ProductsRoot>{
+            self.products_root.map(|(root, block)| ProductsRoot {
+                root,
+                block,
+                stale: self.root_stale,
+            })
+        }
+
+        /// Marks the products root stale and drops the root computation in
+        /// progress, which would mix leaves from before and after the change.
+        /// A message failing after the change keeps it stale, as storage is
+        /// not rolled back on `Err`.
+        fn touch_products(&mut self){
+            self.root_stale = true;
+            self.root_build = None;
+        }
+
+        /// Returns `pid` for writing. Products are only changed through
+        /// here, `mint` and `retire`, which keeps the products root honest.
+        fn product_mut(&mut self, pid: u32) -> Option<&mut StoredProduct>{
+            self.touch_products();
+            self.products.get_mut(&pid)
+        }
+
+        #[ink(message, selector = "0x2430DEE1")]
+        pub fn product_count(&self) -> u32{
+            self.next_pid
+        }
+
+        #[ink(message, selector = "0xF3AA8AC0")]
+        pub fn get_product(&self, pid: u32) -> Result<Product>{
+            self.load_product(pid).ok_or(Error::PidNotExists)
+        }
+
+        /// Returns `pid` with its name, description and metadata URI filled
+        /// in from their own maps.
+        fn load_product(&self, pid: u32) -> Option<Product>{
+            let p = self.products.get(&pid)?;
+            let (name, description) = self.metadata.get(&pid).cloned().unwrap_or_default();
+            Some(p.view(name, description, self.metadata_uris.get(&pid).cloned()))
+        }
+
+        /// Returns the hash identifier of `pid`. Unlike pids it does not
+        /// reveal how many products were created and stays unique across
+        /// registries, it is `blake2_256(creator ++ code ++ nonce)` with the
+        /// SCALE encoded fields and a per-creator nonce.
+        #[ink(message, selector = "0x602AF95C")]
+        pub fn id_of(&self, pid: u32) -> Option<Hash>{
+            self.ids.get(&pid).copied()
+        }
+
+        /// Hash identifier of the live `pid`, for the events naming it.
+        fn id(&self, pid: u32) -> Hash{
+            *self.ids.get(&pid).expect("every product has an id")
+        }
+
+        #[ink(message, selector = "0xC546FA2E")]
+        pub fn pid_of(&self, id: Hash) -> Option<u32>{
+            self.pids_by_id.get(&id).copied()
+        }
+
+        #[ink(message, selector = "0xC31BF2A6")]
+        pub fn get_product_by_id(&self, id: Hash) -> Result<Product>{
+            self.pid_of(id).ok_or(Error::PidNotExists).and_then(|pid| self.get_product(pid))
+        }
+
+        /// Derives the hash identifier of `pid` from the next nonce of
+        /// `creator`. Fails before writing anything once the nonce is used up.
+        fn assign_id(&mut self, pid: u32, creator: AccountId, code: u16) -> Result<Hash>{
+            let nonce = self.creation_nonces.get(&creator).copied().unwrap_or(0);
+            let next_nonce = nonce.checked_add(1).ok_or(Error::ArithmeticOverflow)?;
+            let mut output = <ink_env::hash::Blake2x256 as ink_env::hash::HashOutput>::Type::default();
+            ink_env::hash_encoded::<ink_env::hash::Blake2x256, _>(&(creator, code, nonce), &mut output);
+            self.creation_nonces.insert(creator, next_nonce);
+            let id = Hash::from(output);
+            self.ids.insert(pid, id);
+            self.pids_by_id.insert(id, pid);
+            Ok(id)
+        }
+
+        /// Returns the account that minted `pid`, whoever owns it now.
+        #[ink(message, selector = "0xF08DFA6E")]
+        pub fn get_creator(&self, pid: u32) -> Result<AccountId>{
+            self.products.get(&pid).map(StoredProduct::get_creator).ok_or(Error::PidNotExists)
+        }
+
+
+        /// Delegates a product to `delegate_to`.
+        ///
+        /// If a `deadline` is given the delegate must accept before the block
+        /// timestamp passes it, afterwards `accept_product` fails with
+        /// `DelegationExpired`.
+        ///
+        /// With a `price` the delegate has to pay it to accept. The payment is
+        /// held by the contract until the owner calls `withdraw`.
+        ///
+        /// A `memo` of up to `MAX_MEMO_LEN` bytes is shown to the delegate
+        /// through `pending_memo` until the delegation ends.
+        #[ink(message, selector = "0x5B920ABE")]
+        pub fn delegate_product(&mut self, pid: u32, delegate_to: AccountId, deadline: Option<Timestamp>, price: Option<Balance>, memo: Option<Vec<u8>>) -> Result<()>{
+            self.ensure_not_paused()?;
+            if memo.as_ref().map_or(false, |memo| memo.len() > MAX_MEMO_LEN){
+                return Err(Error::MemoTooLong)
+            }
+            let owner = self.acting_owner(pid, Self::env().caller());
+            self.check_delegation(pid, owner, delegate_to)?;
+            self.apply_delegation(pid, owner, delegate_to, deadline, price.unwrap_or(0), memo);
+            Ok(())
+        }
+
+        /// Returns the memo of the pending delegation of `pid`, if it was
+        /// given one.
+        #[ink(message, selector = "0xB4E686A4")]
+        pub fn pending_memo(&self, pid: u32) -> Result<Option<Vec<u8>>>{
+            self.products
+                .get(&pid)
+                .map(|p| p.memo.clone())
+                .ok_or(Error::PidNotExists)
+        }
+
+        /// Delegates a product to up to `MAX_CANDIDATES` accounts, the first
+        /// of them to call `accept_product` becomes the owner. Deadline and
+        /// price work as with `delegate_product`. Every candidate goes
+        /// through the checks of `delegate_product`, an empty, oversized or
+        /// repeating list fails with `InvalidCandidates`.
+        ///
+        /// A candidate that rejects drops out, the delegation is cancelled
+        /// once the last one did.
+        #[ink(message, selector = "0xC320AD57")]
+        pub fn delegate_to_any(&mut self, pid: u32, candidates: Vec<AccountId>, deadline: Option<Timestamp>, price: Option<Balance>) -> Result<()>{
+            self.ensure_not_paused()?;
+            if candidates.is_empty() || candidates.len() > MAX_CANDIDATES{
+                return Err(Error::InvalidCandidates)
+            }
+            let owner = self.acting_owner(pid, Self::env().caller());
+            for (i, candidate) in candidates.iter().enumerate(){
+                if candidates[..i].contains(candidate){
+                    return Err(Error::InvalidCandidates)
+                }
+                self.check_delegation(pid, owner, *candidate)?;
+            }
+            self.change_product(pid, |p| p.delegate_to_any(candidates.clone(), deadline, price.unwrap_or(0)))
+                .expect("delegation was checked");
+            for candidate in candidates{
+                self.pending_delegations.entry(candidate).or_insert_with(Vec::new).push(pid);
+                Self::env().emit_event(ProductDelegated {
+                    pid,
+                    from: owner,
+                    to: candidate,
+                    memo: None,
+                });
+            }
+            Ok(())
+        }
+
+        /// Delegates a product to `recipient`, or to the first signer when
+        /// none is given, once `threshold` of the `signers` called
+        /// `confirm_acceptance`. `accept_product` does not apply to it and the
+        /// owner can revoke it until the threshold is met.
+        ///
+        /// Signers and recipient go through the checks of `delegate_product`.
+        /// An empty, oversized or repeating list fails with
+        /// `InvalidCandidates` and a threshold of zero or above the number of
+        /// signers with `InvalidThreshold`.
+        #[ink(message, selector = "0xF2A5F922")]
+        pub fn delegate_product_multisig(&mut self, pid: u32, signers: Vec<AccountId>, threshold: u8, recipient: Option<AccountId>) -> Result<()>{
+            self.ensure_not_paused()?;
+            if signers.is_empty() || signers.len() > MAX_CANDIDATES{
+                return Err(Error::InvalidCandidates)
+            }
+            if threshold == 0 || usize::from(threshold) > signers.len(){
+                return Err(Error::InvalidThreshold)
+            }
+            let owner = self.acting_owner(pid, Self::env().caller());
+            for (i, signer) in signers.iter().enumerate(){
+                if signers[..i].contains(signer){
+                    return Err(Error::InvalidCandidates)
+                }
+                self.check_delegation(pid, owner, *signer)?;
+            }
+            let recipient = recipient.unwrap_or(signers[0]);
+            self.check_delegation(pid, owner, recipient)?;
+            self.change_product(pid, |p| p.delegate_to_any(Vec::new(), None, 0)).expect("delegation was checked");
+            self.multisig_delegations.insert(pid, MultisigDelegation {
+                signers,
+                threshold,
+                recipient,
+                confirmations: Vec::new(),
+            });
+            self.pending_delegations.entry(recipient).or_insert_with(Vec::new).push(pid);
+            Self::env().emit_event(ProductDelegated {
+                pid,
+                from: owner,
+                to: recipient,
+                memo: None,
+            });
+            Ok(())
+        }
+
+        /// Lets `operator` act for the owner on `pid`: hand it on with
+        /// `delegate_product_from` or move it with `transfer_product` and
+        /// PSP34 `transfer`. Callable by the owner and its operators, like
+        /// PSP34 `approve`. Approving the zero address clears the approval.
+        #[ink(message, selector = "0x681266A0")]
+        pub fn approve(&mut self, pid: u32, operator: AccountId) -> Result<()>{
+            self.ensure_not_paused()?;
+            if operator == AccountId::from([0x0; 32]){
+                let current = self.approvals.get(&pid).copied().unwrap_or(operator);
+                return self.set_approval(pid, current, false)
+            }
+            self.set_approval(pid, operator, true)
+        }
+
+        /// Approves or revokes `operator` for `pid` on behalf of the caller,
+        /// who must be the owner or one of its operators. Revoking an account
+        /// that is not the approved one changes nothing.
+        fn set_approval(&mut self, pid: u32, operator: AccountId, approved: bool) -> Result<()>{
+            let caller = Self::env().caller();
+            let owner = self.products.get(&pid).ok_or(Error::PidNotExists)?.get_owner();
+            if caller != owner && !self.is_approved_for_all(owner, caller){
+                return Err(Error::InvalidOwner)
+            }
+            if approved{
+                self.approvals.insert(pid, operator);
+            } else if self.approvals.get(&pid) == Some(&operator){
+                self.approvals.take(&pid);
+            } else {
+                return Ok(())
+            }
+            Self::env().emit_event(Approval {
+                owner,
+                operator,
+                id: Some(Id::U32(pid)),
+                approved,
+            });
+            Ok(())
+        }
+
+        /// Lets `operator` delegate and transfer every product of the caller,
+        /// including ones acquired later, until revoked.
+        #[ink(message, selector = "0xCFD0C27B")]
+        pub fn set_approval_for_all(&mut self, operator: AccountId, approved: bool) -> Result<()>{
+            self.ensure_not_paused()?;
+            let owner = Self::env().caller();
+            if approved{
+                self.operator_approvals.insert((owner, operator), true);
+            } else {
+                self.operator_approvals.take(&(owner, operator));
+            }
+            Self::env().emit_event(ApprovalForAll {
+                owner,
+                operator,
+                approved,
+            });
+            Self::env().emit_event(Approval {
+                owner,
+                operator,
+                id: None,
+                approved,
+            });
+            Ok(())
+        }
+
+        #[ink(message, selector = "0x0F5922E9")]
+        pub fn is_approved_for_all(&self, owner: AccountId, operator: AccountId) -> bool{
+            self.operator_approvals.get(&(owner, operator)).copied().unwrap_or(false)
+        }
+
+        /// Returns the account `caller` acts for on `pid`: the product owner
+        /// if `caller` is one of its operators, `caller` itself otherwise.
+        fn acting_owner(&self, pid: u32, caller: AccountId) -> AccountId{
+            match self.products.get(&pid){
+                Some(p) if self.is_approved_for_all(p.owner, caller) => p.owner,
+                _ => caller,
+            }
+        }
+
+        #[ink(message, selector = "0x27592DEA")]
+        pub fn get_approved(&self, pid: u32) -> Option<AccountId>{
+            self.approvals.get(&pid).copied()
+        }
+
+        /// Delegates `pid` for `owner`, callable by the operator approved for
+        /// it or by one of the owner's operators. The product goes through the same checks as `delegate_product`.
+        #[ink(message, selector = "0x42C0D6D4")]
+        pub fn delegate_product_from(&mut self, pid: u32, owner: AccountId, delegate_to: AccountId) -> Result<()>{
+            self.ensure_not_paused()?;
+            if !self.products.contains_key(&pid){
+                return Err(Error::PidNotExists)
+            }
+            let caller = Self::env().caller();
+            if self.approvals.get(&pid) != Some(&caller) && !self.is_approved_for_all(owner, caller){
+                return Err(Error::NotApproved)
+            }
+            self.check_delegation(pid, owner, delegate_to)?;
+            self.apply_delegation(pid, owner, delegate_to, None, 0, None);
+            Ok(())
+        }
+
+        /// Delegates every product in `pids` to `delegate_to`. Like
+        /// `delegate_product`, an operator of the owner may call it.
+        ///
+        /// The batch is atomic: every pid goes through the same checks as
+        /// `delegate_product` before anything is mutated. The first pid that
+        /// fails is reported through `Error::BatchItemFailed` together with the
+        /// error `delegate_product` would have returned for it. A pid listed
+        /// more than once fails with `InvalidState` on its repeated occurrence,
+        /// just as delegating it twice in a row would.
+        #[ink(message, selector = "0xA953C24F")]
+        pub fn delegate_products(&mut self, pids: Vec<u32>, delegate_to: AccountId) -> Result<()>{
+            self.ensure_not_paused()?;
+            if pids.len() > MAX_BATCH_SIZE{
+                return Err(Error::BatchTooLarge)
+            }
+            let caller = Self::env().caller();
+            let mut owners = Vec::with_capacity(pids.len());
+            for (i, pid) in pids.iter().enumerate(){
+                if pids[..i].contains(pid){
+                    return Err(Error::batch_item_failed(*pid, Error::InvalidState))
+                }
+                let owner = self.acting_owner(*pid, caller);
+                self.check_delegation(*pid, owner, delegate_to)
+                    .map_err(|error| Error::batch_item_failed(*pid, error))?;
+                owners.push(owner);
+            }
+            for (pid, owner) in pids.into_iter().zip(owners){
+                self.apply_delegation(pid, owner, delegate_to, None, 0, None);
+            }
+            Ok(())
+        }
+
+        fn check_delegation(&self, pid: u32, caller: AccountId, delegate_to: AccountId) -> Result<()>{
+            let  p = self.products.get(&pid).ok_or(Error::PidNotExists)?;
+            if p.owner != caller{
+                return Err(Error::InvalidOwner)
+            }
+            if p.frozen{
+                return Err(Error::ProductFrozen)
+            }
+            if p.archived{
+                return Err(Error::ProductArchived)
+            }
+            p.state.transition(Action::Delegate)?;
+            if delegate_to == p.owner{
+                return Err(Error::SelfDelegation)
+            }
+            Self::ensure_not_zero(delegate_to)?;
+            self.ensure_not_blacklisted(delegate_to)?;
+            self.ensure_co_owners_approved(pid)?;
+            Ok(())
+        }
+
+        fn apply_delegation(&mut self, pid: u32, from: AccountId, delegate_to: AccountId, deadline: Option<Timestamp>, price: Balance, memo: Option<Vec<u8>>){
+            self.change_product(pid, |p| p.delegate_to(delegate_to, deadline, price, memo.clone()))
+                .expect("delegation was checked");
+            self.pending_delegations.entry(delegate_to).or_insert_with(Vec::new).push(pid);
+            Self::env().emit_event(ProductDelegated {
+                pid,
+                from,
+                to: delegate_to,
+                memo,
+            });
+        }
+
+        /// Accepts `pid`, paying its delegation price if there is one. Any
+        /// surplus is refunded.
+        #[ink(message, payable, selector = "0xAE92354A")]
+        pub fn accept_product(&mut self, pid: u32) -> Result<()>{
+            self.paying(|this| {
+                let _guard = ReentrancyGuard::enter()?;
+                this.ensure_not_paused()?;
+                let caller = Self::env().caller();
+                this.check_acceptance(pid, caller)?;
+                let price = this.products.get(&pid).map_or(0, StoredProduct::get_price);
+                let surplus = Self::ensure_paid(price, Error::InsufficientPayment)?;
+                this.ensure_escrowable(price)?;
+                let previous_owner = this.apply_acceptance(pid, caller);
+                this.notify_registry(pid, previous_owner, caller);
+                Self::refund(surplus);
+                Ok(())
+            })
+        }
+
+        /// Accepts every product in `pids` on behalf of the caller.
+        ///
+        /// Like `delegate_products` the batch is atomic and reports the first
+        /// failing pid through `Error::BatchItemFailed`. A pid listed more than
+        /// once fails with `InvalidDelegate` on its repeated occurrence, since
+        /// the first acceptance already cleared the delegate.
+        ///
+        /// The call must carry the sum of the delegation prices.
+        #[ink(message, payable, selector = "0x85F8A0CD")]
+        pub fn accept_products(&mut self, pids: Vec<u32>) -> Result<()>{
+            self.paying(|this| {
+                let _guard = ReentrancyGuard::enter()?;
+                this.ensure_not_paused()?;
+                if pids.len() > MAX_BATCH_SIZE{
+                    return Err(Error::BatchTooLarge)
+                }
+                let caller = Self::env().caller();
+                for (i, pid) in pids.iter().enumerate(){
+                    if pids[..i].contains(pid){
+                        return Err(Error::batch_item_failed(*pid, Error::InvalidDelegate))
+                    }
+                    this.check_acceptance(*pid, caller)
+                        .map_err(|error| Error::batch_item_failed(*pid, error))?;
+                }
+                this.ensure_quota(caller, pids.len() as u32)?;
+                let price = pids.iter()
+                    .filter_map(|pid| this.products.get(pid))
+                    .try_fold(0, |total: Balance, p| total.checked_add(p.get_price()))
+                    .ok_or(Error::ArithmeticOverflow)?;
+                let surplus = Self::ensure_paid(price, Error::InsufficientPayment)?;
+                this.ensure_escrowable(price)?;
+                let previous_owners: Vec<AccountId> = pids.iter()
+                    .map(|pid| this.apply_acceptance(*pid, caller))
+                    .collect();
+                for (pid, previous_owner) in pids.into_iter().zip(previous_owners){
+                    this.notify_registry(pid, previous_owner, caller);
+                }
+                Self::refund(surplus);
+                Ok(())
+            })
+        }
+
+        fn check_acceptance(&self, pid: u32, caller: AccountId) -> Result<()>{
+            let  p = self.products.get(&pid).ok_or(Error::PidNotExists)?;
+            p.ensure_delegate(caller)?;
+            if p.frozen{
+                return Err(Error::ProductFrozen)
+            }
+            self.ensure_not_blacklisted(caller)?;
+            self.ensure_quota(caller, 1)?;
+            p.state.transition(Action::Accept)?;
+            if let Some(deadline) = p.delegation_deadline{
+                if Self::env().block_timestamp() > deadline{
+                    return Err(Error::DelegationExpired)
+                }
+            }
+            Ok(())
+        }
+
+        /// Hands `pid` to `new_owner` and returns the previous owner. The
+        /// registry is left for the caller to notify once every storage
+        /// write of the message is done.
+        fn apply_acceptance(&mut self, pid: u32, new_owner: AccountId) -> AccountId{
+            let  p = self.products.get(&pid).expect("acceptance was checked");
+            let previous_owner = p.get_owner();
+            let price = p.get_price();
+            let delegates = p.pending_delegates();
+            self.change_product(pid, |p| p.accept(new_owner)).expect("acceptance was checked");
+            self.credit_sale(pid, previous_owner, price);
+            self.approvals.take(&pid);
+            for delegate in delegates{
+                self.remove_pending_delegation(delegate, pid);
+            }
+            self.index_remove(previous_owner, pid);
+            self.index_add(new_owner, pid);
+            self.record_owner(pid, new_owner);
+            self.last_acceptance.insert(pid, (previous_owner, Self::env().block_number()));
+            Self::env().emit_event(ProductAccepted {
+                pid,
+                previous_owner,
+                new_owner,
+                id: self.id(pid),
+            });
+            previous_owner
+        }
+
+        /// Confirms a multisig delegation of `pid` as one of its signers. The
+        /// confirmation reaching the threshold hands the product to the
+        /// recipient. Confirming twice fails with `AlreadyConfirmed`.
+        #[ink(message, selector = "0x3AC88BB8")]
+        pub fn confirm_acceptance(&mut self, pid: u32) -> Result<()>{
+            let _guard = ReentrancyGuard::enter()?;
+            self.ensure_not_paused()?;
+            let signer = Self::env().caller();
+            let  p = self.products.get(&pid).ok_or(Error::PidNotExists)?;
+            if p.frozen{
+                return Err(Error::ProductFrozen)
+            }
+            p.state.transition(Action::Accept)?;
+            let multisig = self.multisig_delegations.get(&pid).ok_or(Error::InvalidDelegate)?;
+            if !multisig.signers.contains(&signer){
+                return Err(Error::NotSigner)
+            }
+            if multisig.confirmations.contains(&signer){
+                return Err(Error::AlreadyConfirmed)
+            }
+            self.ensure_not_blacklisted(multisig.recipient)?;
+            if multisig.confirmations.len() + 1 >= usize::from(multisig.threshold){
+                self.ensure_quota(multisig.recipient, 1)?;
+            }
+            self.ensure_escrowable(p.get_price())?;
+            let multisig = self.multisig_delegations.get_mut(&pid).expect("multisig was checked");
+            multisig.confirmations.push(signer);
+            let confirmations = multisig.confirmations.len() as u8;
+            let (threshold, recipient) = (multisig.threshold, multisig.recipient);
+            Self::env().emit_event(AcceptanceConfirmed {
+                pid,
+                signer,
+                confirmations,
+            });
+            if confirmations >= threshold{
+                self.multisig_delegations.take(&pid);
+                self.remove_pending_delegation(recipient, pid);
+                let previous_owner = self.apply_acceptance(pid, recipient);
+                self.notify_registry(pid, previous_owner, recipient);
+            }
+            Ok(())
+        }
+
+        #[ink(message, selector = "0x620FEF4E")]
+        pub fn multisig_of(&self, pid: u32) -> Option<MultisigDelegation>{
+            self.multisig_delegations.get(&pid).cloned()
+        }
+
+        #[ink(message, selector = "0xCD6882BF")]
+        pub fn reject_delegation(&mut self, pid: u32) -> Result<()>{
+            self.ensure_not_paused()?;
+            let  p = self.products.get(&pid).ok_or(Error::PidNotExists)?;
+            p.ensure_delegate(Self::env().caller())?;
+            p.get_state().transition(Action::Cancel)?;
+            let owner = p.get_owner();
+            if p.candidates.len() > 1{
+                self.product_mut(pid)
+                    .expect("rejection was checked")
+                    .candidates
+                    .retain(|candidate| *candidate != Self::env().caller());
+            } else {
+                self.change_product(pid, StoredProduct::cancel_delegation)?;
+            }
+            Self::env().emit_event(DelegationRejected {
+                pid,
+                owner,
+                delegate: Self::env().caller(),
+            });
+            self.remove_pending_delegation(Self::env().caller(), pid);
+            Ok(())
+        }
+
+        #[ink(message, selector = "0xB230565F")]
+        pub fn revoke_delegation(&mut self, pid: u32) -> Result<()>{
+            self.ensure_not_paused()?;
+            let  p = self.products.get(&pid).ok_or(Error::PidNotExists)?;
+            if p.get_owner() != Self::env().caller(){
+                return Err(Error::InvalidOwner)
+            }
+            p.get_state().transition(Action::Cancel)?;
+            let mut delegates = p.pending_delegates();
+            self.change_product(pid, StoredProduct::cancel_delegation)?;
+            if let Some(multisig) = self.multisig_delegations.take(&pid){
+                delegates.push(multisig.recipient);
+            }
+            for delegate in delegates{
+                Self::env().emit_event(DelegationRevoked {
+                    pid,
+                    owner: Self::env().caller(),
+                    delegate,
+                });
+                self.remove_pending_delegation(delegate, pid);
+            }
+            Ok(())
+        }
+
+        /// Resets a pending delegation whose deadline has passed. Anyone may
+        /// call this, delegations without a deadline never expire.
+        #[ink(message, selector = "0x547EA517")]
+        pub fn expire_delegation(&mut self, pid: u32) -> Result<()>{
+            self.ensure_not_paused()?;
+            let now = Self::env().block_timestamp();
+            let  p = self.products.get(&pid).ok_or(Error::PidNotExists)?;
+            p.get_state().transition(Action::Cancel)?;
+            match p.get_delegation_deadline(){
+                Some(deadline) if now > deadline => {}
+                _ => return Err(Error::NotExpired),
+            }
+            let (delegates, owner) = (p.pending_delegates(), p.get_owner());
+            self.change_product(pid, StoredProduct::cancel_delegation)?;
+            for delegate in delegates{
+                Self::env().emit_event(DelegationExpired {
+                    pid,
+                    owner,
+                    delegate,
+                });
+                self.remove_pending_delegation(delegate, pid);
+            }
+            Ok(())
+        }
+
+        /// Transfers an owned product straight to `to`, skipping the
+        /// delegate/accept handshake. Callable by the owner, the operator
+        /// approved for `pid` and the owner's operators.
+        #[ink(message, selector = "0x92145248")]
+        pub fn transfer_product(&mut self, pid: u32, to: AccountId) -> Result<()>{
+            let _guard = ReentrancyGuard::enter()?;
+            self.ensure_not_paused()?;
+            let owner = self.transferring_owner(pid, Self::env().caller())?;
+            self.move_product(pid, owner, to)
+        }
+
+        /// Returns the owner of `pid` if `caller` may transfer it, checked
+        /// the same way for `transfer_product` and PSP34 `transfer`.
+        fn transferring_owner(&self, pid: u32, caller: AccountId) -> Result<AccountId>{
+            let owner = self.products.get(&pid).ok_or(Error::PidNotExists)?.get_owner();
+            if caller != owner
+                && self.approvals.get(&pid) != Some(&caller)
+                && !self.is_approved_for_all(owner, caller){
+                return Err(Error::InvalidOwner)
+            }
+            Ok(owner)
+        }
+
+        /// Moves `pid` from `owner` to `to` once the caller was found to act
+        /// for `owner`.
+        fn move_product(&mut self, pid: u32, owner: AccountId, to: AccountId) -> Result<()>{
+            self.ensure_co_owners_approved(pid)?;
+            let  p = self.products.get(&pid).ok_or(Error::PidNotExists)?;
+            if p.get_owner() != owner{
+                return Err(Error::InvalidOwner)
+            }
+            if p.is_frozen(){
+                return Err(Error::ProductFrozen)
+            }
+            if p.is_archived(){
+                return Err(Error::ProductArchived)
+            }
+            p.get_state().transition(Action::Transfer)?;
+            if to == owner{
+                return Err(Error::SelfTransfer)
+            }
+            Self::ensure_not_zero(to)?;
+            self.ensure_not_blacklisted(to)?;
+            self.ensure_quota(to, 1)?;
+            self.change_product(pid, |p| p.transfer(to))?;
+            self.approvals.take(&pid);
+            self.index_remove(owner, pid);
+            self.index_add(to, pid);
+            self.record_owner(pid, to);
+            Self::env().emit_event(ProductTransferred {
+                pid,
+                from: owner,
+                to,
+                id: self.id(pid),
+            });
+            Self::env().emit_event(Transfer {
+                from: Some(owner),
+                to: Some(to),
+                id: Id::U32(pid),
+            });
+            self.notify_registry(pid, owner, to);
+            Ok(())
+        }
+
+        /// Anchors an off-chain metadata URI, e.g. an IPFS CID, on a product.
+        #[ink(message, selector = "0xA3BA3410")]
+        pub fn set_metadata_uri(&mut self, pid: u32, uri: Vec<u8>) -> Result<()>{
+            self.ensure_not_paused()?;
+            let  p = self.products.get(&pid).ok_or(Error::PidNotExists)?;
+            if p.get_owner() != Self::env().caller(){
+                return Err(Error::InvalidOwner)
+            }
+            if p.get_state() != State::Owned{
+                return Err(Error::InvalidState)
+            }
+            if p.is_metadata_frozen(){
+                return Err(Error::MetadataFrozen)
+            }
+            if uri.len() > MAX_URI_LEN{
+                return Err(Error::MetadataTooLong)
+            }
+            self.metadata_uris.insert(pid, uri.clone());
+            Self::env().emit_event(MetadataUpdated {
+                pid,
+                uri,
+            });
+            Ok(())
+        }
+
+        /// Replaces the name and description of a product, as long as its
+        /// metadata has not been frozen.
+        #[ink(message, selector = "0x946DE4AE")]
+        pub fn update_metadata(&mut self, pid: u32, name: Vec<u8>, description: Vec<u8>) -> Result<()>{
+            self.ensure_not_paused()?;
+            let  p = self.products.get(&pid).ok_or(Error::PidNotExists)?;
+            if p.get_owner() != Self::env().caller(){
+                return Err(Error::InvalidOwner)
+            }
+            if p.is_metadata_frozen(){
+                return Err(Error::MetadataFrozen)
+            }
+            if name.len() > MAX_NAME_LEN || description.len() > MAX_DESCRIPTION_LEN{
+                return Err(Error::MetadataTooLong)
+            }
+            if name.is_empty() && description.is_empty(){
+                self.metadata.take(&pid);
+            } else{
+                self.metadata.insert(pid, (name, description));
+            }
+            Ok(())
+        }
+
+        /// Makes the metadata of a product immutable. There is no way back,
+        /// not even for later owners.
+        #[ink(message, selector = "0xD8B6F2AD")]
+        pub fn freeze_metadata(&mut self, pid: u32) -> Result<()>{
+            self.ensure_not_paused()?;
+            let  p = self.products.get(&pid).ok_or(Error::PidNotExists)?;
+            if p.get_owner() != Self::env().caller(){
+                return Err(Error::InvalidOwner)
+            }
+            self.product_mut(pid).expect("pid was checked").freeze_metadata();
+            Ok(())
+        }
+
+        #[ink(message, selector = "0xCF8381B3")]
+        pub fn get_metadata_uri(&self, pid: u32) -> Result<Option<Vec<u8>>>{
+            if !self.products.contains_key(&pid){
+                return Err(Error::PidNotExists)
+            }
+            Ok(self.metadata_uris.get(&pid).cloned())
+        }
+
+        /// Recalls a product so it can no longer be delegated, accepted or
+        /// transferred. A pending delegation is cancelled. Admin only.
+        ///
+        /// Products under auction or on loan cannot be recalled, since that
+        /// would strand the escrowed bid or the borrower's custody; recall
+        /// them once the auction is finalized or the loan has ended.
+        #[ink(message, selector = "0xFBC453C9")]
+        pub fn recall_product(&mut self, pid: u32) -> Result<()>{
+            self.as_admin(AdminActionKind::RecallProduct, None, Some(pid), |this| {
+                let  p = this.products.get(&pid).ok_or(Error::PidNotExists)?;
+                p.get_state().transition(Action::Recall)?;
+                let (delegate, owner) = (p.get_delegate(), p.get_owner());
+                let mut delegates = p.pending_delegates();
+                this.change_product(pid, StoredProduct::recall)?;
+                if let Some(multisig) = this.multisig_delegations.take(&pid){
+                    delegates.push(multisig.recipient);
+                }
+                for delegate in delegates{
+                    this.remove_pending_delegation(delegate, pid);
+                }
+                Self::env().emit_event(ProductRecalled {
+                    pid,
+                    owner,
+                    delegate,
+                });
+                Ok(())
+            })
+        }
+
+        /// Moves a recalled product back to `Owned`. Admin only.
+        #[ink(message, selector = "0xD66A8871")]
+        pub fn resolve_recall(&mut self, pid: u32) -> Result<()>{
+            self.as_admin(AdminActionKind::ResolveRecall, None, Some(pid), |this| {
+                let  p = this.products.get(&pid).ok_or(Error::PidNotExists)?;
+                p.get_state().transition(Action::ResolveRecall)?;
+                let owner = p.get_owner();
+                this.change_product(pid, StoredProduct::resolve_recall)?;
+                Self::env().emit_event(RecallResolved {
+                    pid,
+                    owner,
+                });
+                Ok(())
+            })
+        }
+
+        /// Freezes a single product: it cannot be delegated, accepted,
+        /// transferred or burned until unfrozen. A pending delegation is
+        /// kept and can be accepted once the product is unfrozen. Admin only.
+        #[ink(message, selector = "0x96048657")]
+        pub fn freeze_product(&mut self, pid: u32) -> Result<()>{
+            self.as_admin(AdminActionKind::FreezeProduct, None, Some(pid), |this| {
+                if !this.products.contains_key(&pid){
+                    return Err(Error::PidNotExists)
+                }
+                this.product_mut(pid).expect("pid was checked").set_frozen(true);
+                Self::env().emit_event(ProductFrozen { pid });
+                Ok(())
+            })
+        }
+
+        #[ink(message, selector = "0x17C54B53")]
+        pub fn unfreeze_product(&mut self, pid: u32) -> Result<()>{
+            self.as_admin(AdminActionKind::UnfreezeProduct, None, Some(pid), |this| {
+                if !this.products.contains_key(&pid){
+                    return Err(Error::PidNotExists)
+                }
+                this.product_mut(pid).expect("pid was checked").set_frozen(false);
+                Self::env().emit_event(ProductUnfrozen { pid });
+                Ok(())
+            })
+        }
+
+        /// Moves `amount` units of a lot into a new product with the same
+        /// code, name, description, metadata URI and owner, and returns its
+        /// pid. At least one unit must stay behind.
+        #[ink(message, selector = "0x67253E02")]
+        pub fn split_product(&mut self, pid: u32, amount: u32) -> Result<u32>{
+            self.ensure_not_paused()?;
+            self.ensure_co_owners_approved(pid)?;
+            let owner = Self::env().caller();
+            let p = self.products.get(&pid).ok_or(Error::PidNotExists)?;
+            if p.get_owner() != owner{
+                return Err(Error::InvalidOwner)
+            }
+            if p.is_frozen(){
+                return Err(Error::ProductFrozen)
+            }
+            if p.is_archived(){
+                return Err(Error::ProductArchived)
+            }
+            p.get_state().transition(Action::Split)?;
+            if amount == 0{
+                return Err(Error::InvalidQuantity)
+            }
+            if amount >= p.get_quantity(){
+                return Err(Error::InsufficientQuantity)
+            }
+            let code = p.get_code();
+            let (creator, royalty_bps, warranty_until) = (p.get_creator(), p.get_royalty_bps(), p.get_warranty_until());
+            let (stage, lot_id) = (p.get_stage(), p.get_lot_id());
+            self.ensure_mintable(creator, 1)?;
+            self.ensure_quota(owner, 1)?;
+            self.product_mut(pid).expect("split was checked").quantity -= amount;
+            let (name, description) = self.metadata.get(&pid).cloned().unwrap_or_default();
+            let new_pid = self.mint(owner, creator, code, name, description, lot_id)?;
+            let split = self.product_mut(new_pid).expect("split was minted");
+            split.quantity = amount;
+            split.royalty_bps = royalty_bps;
+            split.warranty_until = warranty_until;
+            split.stage = stage;
+            if let Some(uri) = self.metadata_uris.get(&pid).cloned(){
+                self.metadata_uris.insert(new_pid, uri);
+            }
+            Self::env().emit_event(ProductSplit {
+                pid,
+                new_pid,
+                amount,
+            });
+            Ok(new_pid)
+        }
+
+        /// Adds the units of `source_pid` to `target_pid` and retires the
+        /// source pid, which is reported burned like by `burn_product`. Both
+        /// lots must be owned by the caller, carry the same code and not be
+        /// waiting on a delegation.
+        #[ink(message, selector = "0x6387B0DF")]
+        pub fn merge_products(&mut self, target_pid: u32, source_pid: u32) -> Result<()>{
+            let _guard = ReentrancyGuard::enter()?;
+            self.ensure_not_paused()?;
+            if target_pid == source_pid{
+                return Err(Error::SelfMerge)
+            }
+            self.ensure_co_owners_approved(target_pid)?;
+            self.ensure_co_owners_approved(source_pid)?;
+            let caller = Self::env().caller();
+            let target = self.products.get(&target_pid).ok_or(Error::PidNotExists)?;
+            let source = self.products.get(&source_pid).ok_or(Error::PidNotExists)?;
+            for p in [target, source].iter(){
+                if p.get_owner() != caller{
+                    return Err(Error::InvalidOwner)
+                }
+                if p.is_frozen(){
+                    return Err(Error::ProductFrozen)
+                }
+                p.get_state().transition(Action::Merge)?;
+            }
+            if target.get_code() != source.get_code(){
+                return Err(Error::CodeMismatch)
+            }
+            if self.components.contains_key(&source_pid){
+                return Err(Error::HasComponents)
+            }
+            let code = source.get_code();
+            let quantity = target.get_quantity()
+                .checked_add(source.get_quantity())
+                .ok_or(Error::ArithmeticOverflow)?;
+            let id = self.retire(source_pid, caller, code);
+            self.product_mut(target_pid).expect("target was checked").quantity = quantity;
+            Self::env().emit_event(ProductBurned {
+                pid: source_pid,
+                owner: caller,
+                id,
+            });
+            Self::env().emit_event(ProductsMerged {
+                target_pid,
+                source_pid,
+                quantity,
+            });
+            self.notify_registry(source_pid, caller, AccountId::from([0x0; 32]));
+            Ok(())
+        }
+
+        /// Builds `component_pids` into `parent_pid`. The caller must own
+        /// every product involved and none may be waiting on a delegation.
+        /// The components are locked to the parent until it is taken apart
+        /// and the parent can be assembled further later on.
+        #[ink(message, selector = "0xE92A60F7")]
+        pub fn assemble(&mut self, parent_pid: u32, component_pids: Vec<u32>) -> Result<()>{
+            self.ensure_not_paused()?;
+            if component_pids.is_empty(){
+                return Err(Error::NoComponents)
+            }
+            if component_pids.len() > MAX_BATCH_SIZE{
+                return Err(Error::BatchTooLarge)
+            }
+            let caller = Self::env().caller();
+            let mut pids = Vec::with_capacity(component_pids.len() + 1);
+            pids.push(parent_pid);
+            for pid in component_pids.iter(){
+                if pids.contains(pid){
+                    return Err(Error::InvalidComponent)
+                }
+                pids.push(*pid);
+            }
+            for pid in pids.iter(){
+                let p = self.products.get(pid).ok_or(Error::PidNotExists)?;
+                if p.get_owner() != caller{
+                    return Err(Error::InvalidOwner)
+                }
+                if p.is_frozen(){
+                    return Err(Error::ProductFrozen)
+                }
+                p.get_state().transition(Action::Assemble)?;
+            }
+            for pid in component_pids.iter(){
+                self.change_product(*pid, StoredProduct::lock)?;
+                self.approvals.take(pid);
+            }
+            self.components.entry(parent_pid).or_insert_with(Vec::new).extend_from_slice(&component_pids);
+            Self::env().emit_event(Assembled {
+                parent_pid,
+                components: component_pids,
+            });
+            Ok(())
+        }
+
+        /// Returns the pids locked into `pid`, empty unless it was assembled.
+        #[ink(message, selector = "0x5726DDAF")]
+        pub fn components_of(&self, pid: u32) -> Vec<u32>{
+            self.components.get(&pid).cloned().unwrap_or_default()
+        }
+
+        /// Takes `parent_pid` apart. Its components return to the `Owned`
+        /// state under the parent's owner, whoever assembled them.
+        #[ink(message, selector = "0xE957663C")]
+        pub fn disassemble(&mut self, parent_pid: u32) -> Result<()>{
+            let _guard = ReentrancyGuard::enter()?;
+            self.ensure_not_paused()?;
+            let owner = Self::env().caller();
+            let parent = self.products.get(&parent_pid).ok_or(Error::PidNotExists)?;
+            if parent.get_owner() != owner{
+                return Err(Error::InvalidOwner)
+            }
+            if parent.is_frozen(){
+                return Err(Error::ProductFrozen)
+            }
+            parent.get_state().transition(Action::Disassemble)?;
+            let components = self.components.take(&parent_pid).ok_or(Error::NoComponents)?;
+            let mut handed_over = Vec::new();
+            for pid in components.iter(){
+                let previous_owner = self.products.get(pid).expect("components stay locked").get_owner();
+                self.change_product(*pid, |p| p.release(owner)).expect("components stay locked");
+                if previous_owner != owner{
+                    self.index_remove(previous_owner, *pid);
+                    self.index_add(owner, *pid);
+                    self.record_owner(*pid, owner);
+                    handed_over.push((*pid, previous_owner));
+                }
+            }
+            Self::env().emit_event(Disassembled {
+                parent_pid,
+                owner,
+                components,
+            });
+            for (pid, previous_owner) in handed_over{
+                self.notify_registry(pid, previous_owner, owner);
+            }
+            Ok(())
+        }
+
+        /// Sets the share of every sale price of `pid` paid to its creator,
+        /// in basis points up to `MAX_ROYALTY_BPS`. Callable by the creator
+        /// and the admin, whose override is reported through `AdminAction`.
+        #[ink(message, selector = "0x1C2086A1")]
+        pub fn set_royalty(&mut self, pid: u32, royalty_bps: u16) -> Result<()>{
+            let caller = Self::env().caller();
+            let p = self.products.get(&pid).ok_or(Error::PidNotExists)?;
+            if caller == p.get_creator(){
+                return self.write_royalty(pid, royalty_bps)
+            }
+            if caller != self.admin{
+                return Err(Error::NotCreator)
+            }
+            self.as_admin(AdminActionKind::SetRoyalty, None, Some(pid), |this| this.write_royalty(pid, royalty_bps))
+        }
+
+        fn write_royalty(&mut self, pid: u32, royalty_bps: u16) -> Result<()>{
+            if royalty_bps > MAX_ROYALTY_BPS{
+                return Err(Error::RoyaltyTooHigh)
+            }
+            self.product_mut(pid).expect("pid was checked").royalty_bps = royalty_bps;
+            Self::env().emit_event(RoyaltySet {
+                pid,
+                royalty_bps,
+            });
+            Ok(())
+        }
+
+        /// Relabels `pid` with `new_code`, for fixing a typo made at
+        /// creation. Only the owner can do it, while the product sits in
+        /// `State::Owned` and before it was ever transferred. Like a burned
+        /// product's, the old code stays used up. The hash identifier of the
+        /// product is left untouched.
+        #[ink(message, selector = "0x323003BE")]
+        pub fn update_code(&mut self, pid: u32, new_code: u16) -> Result<()>{
+            self.ensure_not_paused()?;
+            let transferred = self.history_len(pid) > 1;
+            let p = self.products.get(&pid).ok_or(Error::PidNotExists)?;
+            if p.get_owner() != Self::env().caller(){
+                return Err(Error::InvalidOwner)
+            }
+            if p.is_frozen(){
+                return Err(Error::ProductFrozen)
+            }
+            p.get_state().transition(Action::UpdateCode)?;
+            if transferred{
+                return Err(Error::InvalidState)
+            }
+            let old_code = p.get_code();
+            if new_code == old_code{
+                return Ok(())
+            }
+            self.ensure_code_available(new_code)?;
+            self.product_mut(pid).expect("pid was checked").code = new_code;
+            let emptied = match self.code_index.get_mut(&old_code){
+                Some(pids) => {
+                    pids.retain(|p| *p != pid);
+                    pids.is_empty()
+                }
+                None => false,
+            };
+            if emptied{
+                self.code_index.take(&old_code);
+            }
+            self.code_taken.insert(new_code, true);
+            self.code_index.entry(new_code).or_insert_with(Vec::new).push(pid);
+            Self::env().emit_event(CodeUpdated {
+                pid,
+                old_code,
+                new_code,
+            });
+            Ok(())
+        }
+
+        /// Moves `pid` to the next supply-chain stage. Owner only, the stage
+        /// is kept when the product changes hands.
+        #[ink(message, selector = "0x84CC8854")]
+        pub fn advance_stage(&mut self, pid: u32) -> Result<()>{
+            self.ensure_not_paused()?;
+            let p = self.products.get(&pid).ok_or(Error::PidNotExists)?;
+            if p.get_owner() != Self::env().caller(){
+                return Err(Error::InvalidOwner)
+            }
+            if p.is_frozen(){
+                return Err(Error::ProductFrozen)
+            }
+            let from = p.get_stage();
+            let to = from.next()?;
+            self.product_mut(pid).expect("pid was checked").stage = to;
+            Self::env().emit_event(StageAdvanced {
+                pid,
+                from,
+                to,
+            });
+            Ok(())
+        }
+
+        /// Sets or clears the warranty of `pid`. Creator only.
+        #[ink(message, selector = "0xC4E6A183")]
+        pub fn set_warranty(&mut self, pid: u32, warranty_until: Option<Timestamp>) -> Result<()>{
+            self.ensure_not_paused()?;
+            let p = self.products.get(&pid).ok_or(Error::PidNotExists)?;
+            if p.get_creator() != Self::env().caller(){
+                return Err(Error::NotCreator)
+            }
+            self.product_mut(pid).expect("pid was checked").warranty_until = warranty_until;
+            Self::env().emit_event(WarrantySet {
+                pid,
+                warranty_until,
+            });
+            Ok(())
+        }
+
+        /// Whether the block timestamp has not passed the warranty of `pid`.
+        #[ink(message, selector = "0x5213B9A1")]
+        pub fn is_under_warranty(&self, pid: u32) -> bool{
+            self.products
+                .get(&pid)
+                .and_then(StoredProduct::get_warranty_until)
+                .map_or(false, |until| Self::env().block_timestamp() <= until)
+        }
+
+        /// Registers a warranty claim on `pid`, `claim_hash` anchoring the
+        /// off-chain claim file, and returns its claim id. Owner only, while
+        /// the warranty runs.
+        #[ink(message, selector = "0x614F30BC")]
+        pub fn claim_warranty(&mut self, pid: u32, claim_hash: Hash) -> Result<u32>{
+            self.ensure_not_paused()?;
+            let owner = Self::env().caller();
+            let p = self.products.get(&pid).ok_or(Error::PidNotExists)?;
+            if p.get_owner() != owner{
+                return Err(Error::InvalidOwner)
+            }
+            if p.get_warranty_until().is_none(){
+                return Err(Error::NoWarranty)
+            }
+            if !self.is_under_warranty(pid){
+                return Err(Error::WarrantyExpired)
+            }
+            let claims = self.warranty_claims.entry(pid).or_insert_with(Vec::new);
+            let claim_id = claims.len() as u32;
+            claims.push((owner, Self::env().block_timestamp(), claim_hash));
+            Self::env().emit_event(WarrantyClaimed {
+                pid,
+                owner,
+                claim_id,
+                claim_hash,
+            });
+            Ok(claim_id)
+        }
+
+        /// Returns the warranty claims of `pid` as `(owner, timestamp,
+        /// claim_hash)`, oldest first.
+        #[ink(message, selector = "0x54ED88C8")]
+        pub fn warranty_claims_of(&self, pid: u32) -> Vec<(AccountId, Timestamp, Hash)>{
+            self.warranty_claims.get(&pid).cloned().unwrap_or_default()
+        }
+
+        /// Makes `account` a co-owner of `pid`, moving `share_bps` of the
+        /// owner's share to it. The owner always keeps part of the product.
+        ///
+        /// Once a product has co-owners it can only be delegated, transferred,
+        /// sold, split, merged or burned after every co-owner called
+        /// `approve_action`. The product changing hands makes the new owner
+        /// its sole owner again.
+        #[ink(message, selector = "0x766B2BA9")]
+        pub fn add_co_owner(&mut self, pid: u32, account: AccountId, share_bps: u16) -> Result<()>{
+            self.ensure_not_paused()?;
+            let owner = self.products.get(&pid).ok_or(Error::PidNotExists)?.get_owner();
+            if owner != Self::env().caller(){
+                return Err(Error::InvalidOwner)
+            }
+            if self.share_of(pid, account) > 0{
+                return Err(Error::AlreadyCoOwner)
+            }
+            self.move_share(pid, owner, account, share_bps)
+        }
+
+        /// Moves `share_bps` of the caller's share of `pid` to `to`, who
+        /// becomes a co-owner if it was not one. A co-owner giving away its
+        /// whole share stops being one.
+        #[ink(message, selector = "0xAB295329")]
+        pub fn transfer_share(&mut self, pid: u32, to: AccountId, share_bps: u16) -> Result<()>{
+            self.ensure_not_paused()?;
+            self.move_share(pid, Self::env().caller(), to, share_bps)
+        }
+
+        fn move_share(&mut self, pid: u32, from: AccountId, to: AccountId, share_bps: u16) -> Result<()>{
+            let owner = self.products.get(&pid).ok_or(Error::PidNotExists)?.get_owner();
+            let held = self.share_of(pid, from);
+            if held == 0{
+                return Err(Error::NotCoOwner)
+            }
+            if share_bps == 0{
+                return Err(Error::InvalidShare)
+            }
+            if share_bps > held || (from == owner && share_bps == held){
+                return Err(Error::InsufficientShare)
+            }
+            if to == from{
+                return Err(Error::SelfTransfer)
+            }
+            Self::ensure_not_zero(to)?;
+            self.ensure_not_blacklisted(to)?;
+            if !self.co_owners.contains_key(&pid){
+                self.co_owners.insert(pid, ink_prelude::vec![owner]);
+                self.shares.insert((pid, owner), FULL_SHARE_BPS);
+            }
+            let co_owners = self.co_owners.get_mut(&pid).expect("co-owners were set");
+            if held == share_bps{
+                self.shares.take(&(pid, from));
+                co_owners.retain(|co_owner| *co_owner != from);
+            } else {
+                self.shares.insert((pid, from), held - share_bps);
+            }
+            if !co_owners.contains(&to){
+                co_owners.push(to);
+            }
+            let sole = co_owners.len() == 1;
+            *self.shares.entry((pid, to)).or_insert(0) += share_bps;
+            self.action_approvals.take(&pid);
+            if sole{
+                self.clear_co_owners(pid);
+            }
+            Self::env().emit_event(ShareTransferred {
+                pid,
+                from,
+                to,
+                share_bps,
+            });
+            Ok(())
+        }
+
+        /// Approves, as a co-owner of `pid`, its owner handing it over. The
+        /// approval stands until the shares change or the product changes
+        /// hands.
+        #[ink(message, selector = "0x2022B5D3")]
+        pub fn approve_action(&mut self, pid: u32) -> Result<()>{
+            self.ensure_not_paused()?;
+            let co_owner = Self::env().caller();
+            if !self.products.contains_key(&pid){
+                return Err(Error::PidNotExists)
+            }
+            if !self.co_owners.get(&pid).map_or(false, |co_owners| co_owners.contains(&co_owner)){
+                return Err(Error::NotCoOwner)
+            }
+            let approvals = self.action_approvals.entry(pid).or_insert_with(Vec::new);
+            if !approvals.contains(&co_owner){
+                approvals.push(co_owner);
+            }
+            Self::env().emit_event(ActionApproved {
+                pid,
+                co_owner,
+            });
+            Ok(())
+        }
+
+        /// Returns the share of `pid` held by `account` in basis points,
+        /// `FULL_SHARE_BPS` for the owner of a product without co-owners.
+        #[ink(message, selector = "0x6C588FC5")]
+        pub fn share_of(&self, pid: u32, account: AccountId) -> u16{
+            if self.co_owners.contains_key(&pid){
+                return self.shares.get(&(pid, account)).copied().unwrap_or(0)
+            }
+            match self.products.get(&pid){
+                Some(p) if p.get_owner() == account => FULL_SHARE_BPS,
+                _ => 0,
+            }
+        }
+
+        /// Returns every holder of a share of `pid` with its share, the
+        /// owner first.
+        #[ink(message, selector = "0xC0086DF1")]
+        pub fn shares_of(&self, pid: u32) -> Vec<(AccountId, u16)>{
+            match self.co_owners.get(&pid){
+                Some(co_owners) => co_owners
+                    .iter()
+                    .map(|co_owner| (*co_owner, self.share_of(pid, *co_owner)))
+                    .collect(),
+                None => self.products
+                    .get(&pid)
+                    .map(|p| ink_prelude::vec![(p.get_owner(), FULL_SHARE_BPS)])
+                    .unwrap_or_default(),
+            }
+        }
+
+        fn ensure_co_owners_approved(&self, pid: u32) -> Result<()>{
+            if let Some(co_owners) = self.co_owners.get(&pid){
+                let approvals = self.action_approvals.get(&pid);
+                if co_owners.iter().any(|co_owner| !approvals.map_or(false, |a| a.contains(co_owner))){
+                    return Err(Error::CoOwnerApprovalMissing)
+                }
+            }
+            Ok(())
+        }
+
+        fn clear_co_owners(&mut self, pid: u32){
+            for co_owner in self.co_owners.take(&pid).unwrap_or_default(){
+                self.shares.take(&(pid, co_owner));
+            }
+            self.action_approvals.take(&pid);
+        }
+
+        /// Puts `pid` up for sale, anyone may buy it for `price` with
+        /// `buy_product` until the owner unlists it.
+        #[ink(message, selector = "0xFB33FF5F")]
+        pub fn list_for_sale(&mut self, pid: u32, price: Balance) -> Result<()>{
+            self.ensure_not_paused()?;
+            self.ensure_co_owners_approved(pid)?;
+            let p = self.products.get(&pid).ok_or(Error::PidNotExists)?;
+            if p.get_owner() != Self::env().caller(){
+                return Err(Error::InvalidOwner)
+            }
+            if p.is_frozen(){
+                return Err(Error::ProductFrozen)
+            }
+            if p.is_archived(){
+                return Err(Error::ProductArchived)
+            }
+            p.get_state().transition(Action::List)?;
+            self.change_product(pid, |p| p.list(price))?;
+            Self::env().emit_event(ProductListed {
+                pid,
+                price,
+            });
+            Ok(())
+        }
+
+        #[ink(message, selector = "0x5AEEB315")]
+        pub fn unlist(&mut self, pid: u32) -> Result<()>{
+            self.ensure_not_paused()?;
+            let p = self.products.get(&pid).ok_or(Error::PidNotExists)?;
+            if p.get_owner() != Self::env().caller(){
+                return Err(Error::InvalidOwner)
+            }
+            p.get_state().transition(Action::Unlist)?;
+            self.change_product(pid, StoredProduct::unlist)?;
+            Self::env().emit_event(ProductUnlisted {
+                pid,
+            });
+            Ok(())
+        }
+
+        /// Buys the listed `pid`. The call must carry at least the listing
+        /// price, any surplus is refunded and the price is credited to the
+        /// seller, see `withdraw`.
+        #[ink(message, payable, selector = "0x5D2A2924")]
+        pub fn buy_product(&mut self, pid: u32) -> Result<()>{
+            self.paying(|this| {
+                let _guard = ReentrancyGuard::enter()?;
+                this.ensure_not_paused()?;
+                let buyer = Self::env().caller();
+                let p = this.products.get(&pid).ok_or(Error::PidNotExists)?;
+                let seller = p.get_owner();
+                if p.is_frozen(){
+                    return Err(Error::ProductFrozen)
+                }
+                p.get_state().transition(Action::Buy)?;
+                if buyer == seller{
+                    return Err(Error::SelfTransfer)
+                }
+                this.ensure_not_blacklisted(buyer)?;
+                this.ensure_quota(buyer, 1)?;
+                let price = p.get_price();
+                let surplus = Self::ensure_paid(price, Error::InsufficientPayment)?;
+                this.ensure_escrowable(price)?;
+                this.change_product(pid, |p| p.buy(buyer)).expect("purchase was checked");
+                this.credit_sale(pid, seller, price);
+                this.approvals.take(&pid);
+                this.index_remove(seller, pid);
+                this.index_add(buyer, pid);
+                this.record_owner(pid, buyer);
+                this.last_acceptance.insert(pid, (seller, Self::env().block_number()));
+                Self::env().emit_event(ProductSold {
+                    pid,
+                    seller,
+                    buyer,
+                    id: this.id(pid),
+                    price,
+                });
+                this.notify_registry(pid, seller, buyer);
+                Self::refund(surplus);
+                Ok(())
+            })
+        }
+
+        /// Auctions `pid` for `duration_blocks` blocks, bids start at
+        /// `min_bid`.
+        #[ink(message, selector = "0x083DE57E")]
+        pub fn start_auction(&mut self, pid: u32, min_bid: Balance, duration_blocks: BlockNumber) -> Result<()>{
+            self.ensure_not_paused()?;
+            self.ensure_co_owners_approved(pid)?;
+            let seller = Self::env().caller();
+            let p = self.products.get(&pid).ok_or(Error::PidNotExists)?;
+            if p.get_owner() != seller{
+                return Err(Error::InvalidOwner)
+            }
+            if p.is_frozen(){
+                return Err(Error::ProductFrozen)
+            }
+            if p.is_archived(){
+                return Err(Error::ProductArchived)
+            }
+            p.get_state().transition(Action::StartAuction)?;
+            if duration_blocks == 0{
+                return Err(Error::InvalidDuration)
+            }
+            let end_block = Self::env().block_number().saturating_add(duration_blocks);
+            self.change_product(pid, StoredProduct::start_auction)?;
+            self.approvals.take(&pid);
+            self.auctions.insert(pid, Auction {
+                seller,
+                min_bid,
+                end_block,
+                highest_bid: None,
+            });
+            Self::env().emit_event(AuctionStarted {
+                pid,
+                min_bid,
+                end_block,
+            });
+            Ok(())
+        }
+
+        /// Bids the transferred value on `pid`. It must reach the minimum bid
+        /// and exceed the highest bid so far, which becomes withdrawable by
+        /// its bidder.
+        #[ink(message, payable, selector = "0x668D28A7")]
+        pub fn bid(&mut self, pid: u32) -> Result<()>{
+            self.paying(|this| {
+                this.ensure_not_paused()?;
+                let bidder = Self::env().caller();
+                let amount = Self::env().transferred_balance();
+                let auction = this.auctions.get(&pid).ok_or(Error::NotAuctioned)?;
+                if Self::env().block_number() > auction.end_block{
+                    return Err(Error::AuctionEnded)
+                }
+                if bidder == auction.seller{
+                    return Err(Error::SelfTransfer)
+                }
+                this.ensure_not_blacklisted(bidder)?;
+                if amount < auction.min_bid || auction.highest_bid.map_or(false, |(_, highest)| amount <= highest){
+                    return Err(Error::BidTooLow)
+                }
+                this.ensure_escrowable(amount)?;
+                let auction = this.auctions.get_mut(&pid).expect("auction was checked");
+                let outbid = auction.highest_bid.replace((bidder, amount));
+                this.escrowed = this.escrowed.checked_add(amount).expect("escrow was checked");
+                if let Some((previous_bidder, previous_amount)) = outbid{
+                    // still escrowed, now as the withdrawable balance of its bidder
+                    this.escrowed = this.escrowed.checked_sub(previous_amount).expect("bids are escrowed");
+                    this.credit(previous_bidder, previous_amount);
+                }
+                Self::env().emit_event(BidPlaced {
+                    pid,
+                    bidder,
+                    amount,
+                });
+                Ok(())
+            })
+        }
+
+        /// Ends the auction of `pid` once its end block has passed, callable
+        /// by anyone. The highest bidder becomes the owner and the bid is
+        /// credited to the seller, without bids the product stays with the
+        /// seller.
+        #[ink(message, selector = "0x28DD27B4")]
+        pub fn finalize_auction(&mut self, pid: u32) -> Result<()>{
+            let _guard = ReentrancyGuard::enter()?;
+            self.ensure_not_paused()?;
+            let auction = self.auctions.get(&pid).ok_or(Error::NotAuctioned)?;
+            if Self::env().block_number() <= auction.end_block{
+                return Err(Error::AuctionNotEnded)
+            }
+            let (owner, amount) = auction.highest_bid.unwrap_or((auction.seller, 0));
+            // the winning bid leaves the auction to be credited again
+            let escrowed = self.escrowed.checked_sub(amount).ok_or(Error::ArithmeticOverflow)?;
+            let p = self.products.get(&pid).expect("auctioned products exist");
+            if p.is_frozen(){
+                return Err(Error::ProductFrozen)
+            }
+            let Auction { seller, highest_bid, .. } = self.auctions.take(&pid).expect("auction was checked");
+            self.change_product(pid, |p| p.finalize_auction(owner)).expect("auctioned products are auctioned");
+            if owner != seller{
+                self.escrowed = escrowed;
+                self.credit_sale(pid, seller, amount);
+                self.index_remove(seller, pid);
+                self.index_add(owner, pid);
+                self.record_owner(pid, owner);
+                self.last_acceptance.insert(pid, (seller, Self::env().block_number()));
+                self.notify_registry(pid, seller, owner);
+            }
+            Self::env().emit_event(AuctionFinalized {
+                pid,
+                id: self.id(pid),
+                winner: highest_bid.map(|(bidder, _)| bidder),
+                amount,
+            });
+            Ok(())
+        }
+
+        #[ink(message, selector = "0x15A41CB5")]
+        pub fn get_auction(&self, pid: u32) -> Option<Auction>{
+            self.auctions.get(&pid).cloned()
+        }
+
+        /// Lends `pid` to `borrower` for `duration_blocks` blocks. The owner
+        /// keeps ownership, the borrower gives the product back with
+        /// `return_product` or the owner takes it back with `reclaim_product`
+        /// once the loan ran out.
+        #[ink(message, selector = "0x19AD4CFD")]
+        pub fn lend_product(&mut self, pid: u32, borrower: AccountId, duration_blocks: BlockNumber) -> Result<()>{
+            self.ensure_not_paused()?;
+            let owner = Self::env().caller();
+            let p = self.products.get(&pid).ok_or(Error::PidNotExists)?;
+            if p.get_owner() != owner{
+                return Err(Error::InvalidOwner)
+            }
+            if p.is_frozen(){
+                return Err(Error::ProductFrozen)
+            }
+            if p.is_archived(){
+                return Err(Error::ProductArchived)
+            }
+            p.get_state().transition(Action::Lend)?;
+            if duration_blocks == 0{
+                return Err(Error::InvalidDuration)
+            }
+            if borrower == owner{
+                return Err(Error::SelfDelegation)
+            }
+            Self::ensure_not_zero(borrower)?;
+            self.ensure_not_blacklisted(borrower)?;
+            let until = Self::env().block_number().saturating_add(duration_blocks);
+            self.change_product(pid, |p| p.lend(borrower, until))?;
+            self.approvals.take(&pid);
+            Self::env().emit_event(ProductLent {
+                pid,
+                owner,
+                borrower,
+                until,
+            });
+            Ok(())
+        }
+
+        /// Gives a lent product back to its owner, callable by the borrower
+        /// at any time.
+        #[ink(message, selector = "0x94324806")]
+        pub fn return_product(&mut self, pid: u32) -> Result<()>{
+            self.ensure_not_paused()?;
+            let borrower = Self::env().caller();
+            let p = self.products.get(&pid).ok_or(Error::PidNotExists)?;
+            p.get_state().transition(Action::Return)?;
+            if p.get_custodian() != Some(borrower){
+                return Err(Error::NotBorrower)
+            }
+            self.change_product(pid, StoredProduct::end_loan)?;
+            Self::env().emit_event(ProductReturned {
+                pid,
+                borrower,
+            });
+            Ok(())
+        }
+
+        /// Takes a lent product back once its loan ran out. Owner only.
+        #[ink(message, selector = "0x25844938")]
+        pub fn reclaim_product(&mut self, pid: u32) -> Result<()>{
+            self.ensure_not_paused()?;
+            let now = Self::env().block_number();
+            let p = self.products.get(&pid).ok_or(Error::PidNotExists)?;
+            if p.get_owner() != Self::env().caller(){
+                return Err(Error::InvalidOwner)
+            }
+            p.get_state().transition(Action::Return)?;
+            if p.get_lent_until().map_or(false, |until| now <= until){
+                return Err(Error::LoanNotExpired)
+            }
+            let borrower = p.get_custodian().expect("lent products always have a custodian");
+            self.change_product(pid, StoredProduct::end_loan)?;
+            Self::env().emit_event(ProductReclaimed {
+                pid,
+                borrower,
+            });
+            Ok(())
+        }
+
+        /// Permanently removes an owned product. Its pid is never reused and
+        /// every other message treats it as unknown afterwards.
+        #[ink(message, selector = "0x3644A2D9")]
+        pub fn burn_product(&mut self, pid: u32) -> Result<()>{
+            let _guard = ReentrancyGuard::enter()?;
+            self.ensure_not_paused()?;
+            let caller = Self::env().caller();
+            let  p = self.products.get(&pid).ok_or(Error::PidNotExists)?;
+            if p.get_owner() != caller{
+                return Err(Error::InvalidOwner)
+            }
+            if p.is_frozen(){
+                return Err(Error::ProductFrozen)
+            }
+            p.get_state().transition(Action::Burn)?;
+            if self.components.contains_key(&pid){
+                return Err(Error::HasComponents)
+            }
+            let code = p.get_code();
+            self.ensure_co_owners_approved(pid)?;
+            let id = self.retire(pid, caller, code);
+            Self::env().emit_event(ProductBurned {
+                pid,
+                owner: caller,
+                id,
+            });
+            self.notify_registry(pid, caller, AccountId::from([0x0; 32]));
+            Ok(())
+        }
+
+        /// Keeps an owned product out of `products_of` and `get_products`
+        /// unless archived entries are asked for. An archived product cannot
+        /// be delegated, transferred, sold, auctioned, lent or split until
+        /// `unarchive_product`, and stays readable with `get_product`.
+        #[ink(message, selector = "0xE88B5EA1")]
+        pub fn archive_product(&mut self, pid: u32) -> Result<()>{
+            self.set_archived(pid, true)?;
+            Self::env().emit_event(ProductArchived { pid });
+            Ok(())
+        }
+
+        #[ink(message, selector = "0x794571DD")]
+        pub fn unarchive_product(&mut self, pid: u32) -> Result<()>{
+            self.set_archived(pid, false)?;
+            Self::env().emit_event(ProductUnarchived { pid });
+            Ok(())
+        }
+
+        fn set_archived(&mut self, pid: u32, archived: bool) -> Result<()>{
+            self.ensure_not_paused()?;
+            let p = self.products.get(&pid).ok_or(Error::PidNotExists)?;
+            if p.get_owner() != Self::env().caller(){
+                return Err(Error::InvalidOwner)
+            }
+            if p.get_state() != State::Owned{
+                return Err(Error::InvalidState)
+            }
+            self.product_mut(pid).expect("pid was checked").archived = archived;
+            Ok(())
+        }
+
+        /// Returns the pids waiting for `delegate` to accept them, multisig
+        /// delegations naming it as recipient included.
+        #[ink(message, selector = "0x79719B8C")]
+        pub fn pending_for(&self, delegate: AccountId) -> Vec<u32>{
+            self.pending_delegations.get(&delegate).cloned().unwrap_or_default()
+        }
+
+        /// Returns each product `owner` has delegated but that was not
+        /// accepted yet, together with its delegate. A product delegated with
+        /// `delegate_to_any` is listed once per candidate.
+        #[ink(message, selector = "0x0418B122")]
+        pub fn delegated_by(&self, owner: AccountId) -> Vec<(u32, AccountId)>{
+            self.products_of(owner, true)
+                .into_iter()
+                .filter_map(|pid| self.products.get(&pid).map(|p| (pid, p)))
+                .flat_map(|(pid, p)| {
+                    let delegates = match p.state{
+                        State::PendingDelegation => {
+                            let mut delegates = p.pending_delegates();
+                            if let Some(multisig) = self.multisig_delegations.get(&pid){
+                                delegates.push(multisig.recipient);
+                            }
+                            delegates
+                        }
+                        State::Owned
+                        | State::Recalled
+                        | State::ComponentLocked
+                        | State::Disputed
+                        | State::Listed
+                        | State::Auctioned
+                        | State::Lent
+                        | State::PendingVerification => Vec::new(),
+                    };
+                    delegates.into_iter().map(move |delegate| (pid, delegate))
+                })
+                .collect()
+        }
+
+        /// Returns the number of products owned by `owner`.
+        #[ink(message, selector = "0x7F138C4B")]
+        pub fn count_of(&self, owner: AccountId) -> u32{
+            self.owned_counts.get(&owner).copied().unwrap_or(0)
+        }
+
+        /// Returns the pids `owner` owns, archived ones only with
+        /// `include_archived`.
+        #[ink(message, selector = "0x08980795")]
+        pub fn products_of(&self, owner: AccountId, include_archived: bool) -> Vec<u32>{
+            let mut pids = self.owned_products.get(&owner).cloned().unwrap_or_default();
+            if !include_archived{
+                pids.retain(|pid| self.products.get(pid).map_or(false, |p| !p.is_archived()));
+            }
+            pids
+        }
+
+        /// Returns the pid at `index` among the products `owner` owns,
+        /// archived ones included, for `0 <= index < count_of(owner)`.
+        /// Positions are stable until the holdings change: handing a product
+        /// over moves the last one into its place.
+        #[ink(message, selector = "0x3221994F")]
+        pub fn product_of_owner_by_index(&self, owner: AccountId, index: u32) -> Result<u32>{
+            self.owned_products
+                .get(&owner)
+                .and_then(|pids| pids.get(index as usize))
+                .copied()
+                .ok_or(Error::IndexOutOfBounds)
+        }
+
+        /// Like `products_of`, with the hash identifiers of the products,
+        /// archived ones included.
+        #[ink(message, selector = "0xC1015CFA")]
+        pub fn ids_of(&self, owner: AccountId) -> Vec<Hash>{
+            self.products_of(owner, true).iter().filter_map(|pid| self.id_of(*pid)).collect()
+        }
+
+        /// Returns the pids of the live products labelled with `code`, the
+        /// created product followed by the lots split off it.
+        #[ink(message, selector = "0xA6DBC974")]
+        pub fn get_products_by_code(&self, code: u16) -> Vec<u32>{
+            self.code_index.get(&code).cloned().unwrap_or_default()
+        }
+
+        /// Returns the number of recorded owners of a product.
+        #[ink(message, selector = "0x623963FE")]
+        pub fn history_len(&self, pid: u32) -> u32{
+            self.history_lens.get(&pid).copied().unwrap_or(0)
+        }
+
+        /// Returns the `index`-th owner of a product and when it took
+        /// ownership, `0` being the creator.
+        #[ink(message, selector = "0xA97E97EA")]
+        pub fn history_entry(&self, pid: u32, index: u32) -> Option<(AccountId, Timestamp)>{
+            self.history.get(&(pid, index)).copied()
+        }
+
+        /// Returns the custody chain of a product oldest first, at most
+        /// `MAX_PAGE_SIZE` entries starting at `offset`. Burned products keep
+        /// their history.
+        #[ink(message, selector = "0x7F9D5C65")]
+        pub fn get_history(&self, pid: u32, offset: u32) -> Result<Vec<(AccountId, Timestamp)>>{
+            let len = *self.history_lens.get(&pid).ok_or(Error::PidNotExists)?;
+            let end = offset.saturating_add(MAX_PAGE_SIZE).min(len);
+            Ok((offset..end)
+                .filter_map(|index| self.history.get(&(pid, index)).copied())
+                .collect())
+        }
+
+        /// Appends a location checkpoint, e.g. a depot scan, to the trail of
+        /// `pid`. Callable by the owner and by the pending delegates while a
+        /// delegation waits, so a carrier can log the route before accepting.
+        #[ink(message, selector = "0x3D537CA3")]
+        pub fn add_checkpoint(&mut self, pid: u32, location: Vec<u8>) -> Result<()>{
+            self.ensure_not_paused()?;
+            let author = Self::env().caller();
+            let p = self.products.get(&pid).ok_or(Error::PidNotExists)?;
+            if p.get_owner() != author && !p.pending_delegates().contains(&author){
+                return Err(Error::NotOwnerOrDelegate)
+            }
+            if location.len() > MAX_LOCATION_LEN{
+                return Err(Error::LocationTooLong)
+            }
+            let index = self.checkpoint_count(pid);
+            let count = index.checked_add(1).ok_or(Error::ArithmeticOverflow)?;
+            self.checkpoint_counts.insert(pid, count);
+            self.checkpoints.insert((pid, index), (author, Self::env().block_timestamp(), location));
+            Self::env().emit_event(CheckpointAdded {
+                pid,
+                author,
+                index,
+            });
+            Ok(())
+        }
+
+        #[ink(message, selector = "0x7A29DDF4")]
+        pub fn checkpoint_count(&self, pid: u32) -> u32{
+            self.checkpoint_counts.get(&pid).copied().unwrap_or(0)
+        }
+
+        /// Returns the `index`-th checkpoint of `pid` as `(author, timestamp,
+        /// location)`, `0` being the oldest.
+        #[ink(message, selector = "0x2BF6B37A")]
+        pub fn checkpoint_at(&self, pid: u32, index: u32) -> Option<(AccountId, Timestamp, Vec<u8>)>{
+            self.checkpoints.get(&(pid, index)).cloned()
+        }
+
+        /// Adds a note, e.g. a repair record, to `pid` and returns its index.
+        /// Owner only. Notes stay with the product when it changes hands and
+        /// anyone can read them.
+        #[ink(message, selector = "0x3493B4F2")]
+        pub fn add_note(&mut self, pid: u32, note: Vec<u8>) -> Result<u32>{
+            self.ensure_not_paused()?;
+            let author = Self::env().caller();
+            let p = self.products.get(&pid).ok_or(Error::PidNotExists)?;
+            if p.get_owner() != author{
+                return Err(Error::InvalidOwner)
+            }
+            if note.len() > MAX_NOTE_LEN{
+                return Err(Error::NoteTooLong)
+            }
+            let index = self.note_count(pid);
+            if index >= MAX_NOTES{
+                return Err(Error::TooManyNotes)
+            }
+            self.note_counts.insert(pid, index + 1);
+            self.notes.insert((pid, index), (author, Self::env().block_timestamp(), note));
+            Self::env().emit_event(NoteAdded {
+                pid,
+                author,
+                index,
+            });
+            Ok(index)
+        }
+
+        #[ink(message, selector = "0x10272EC4")]
+        pub fn note_count(&self, pid: u32) -> u32{
+            self.note_counts.get(&pid).copied().unwrap_or(0)
+        }
+
+        /// Returns the `index`-th note of `pid` as `(author, timestamp,
+        /// note)`, `0` being the oldest.
+        #[ink(message, selector = "0xA114B5B1")]
+        pub fn note_at(&self, pid: u32, index: u32) -> Option<(AccountId, Timestamp, Vec<u8>)>{
+            self.notes.get(&(pid, index)).cloned()
+        }
+
+        /// Registers `device` as the sensor of `pid`, the only account that
+        /// can record its readings. Setting the zero address removes the
+        /// sensor. Owner only.
+        #[ink(message, selector = "0x8FA67C25")]
+        pub fn set_sensor(&mut self, pid: u32, device: AccountId) -> Result<()>{
+            self.ensure_not_paused()?;
+            let p = self.products.get(&pid).ok_or(Error::PidNotExists)?;
+            if p.get_owner() != Self::env().caller(){
+                return Err(Error::InvalidOwner)
+            }
+            if device == AccountId::from([0x0; 32]){
+                self.sensors.take(&pid);
+            } else {
+                self.sensors.insert(pid, device);
+            }
+            Ok(())
+        }
+
+        #[ink(message, selector = "0x50FF6150")]
+        pub fn sensor_of(&self, pid: u32) -> Option<AccountId>{
+            self.sensors.get(&pid).copied()
+        }
+
+        /// Sets the inclusive range readings of `pid` are expected in, a
+        /// reading outside it emits `ReadingOutOfRange`. `None` stops the
+        /// checks. Owner only.
+        #[ink(message, selector = "0xE4D94281")]
+        pub fn set_reading_range(&mut self, pid: u32, range: Option<(i32, i32)>) -> Result<()>{
+            self.ensure_not_paused()?;
+            let p = self.products.get(&pid).ok_or(Error::PidNotExists)?;
+            if p.get_owner() != Self::env().caller(){
+                return Err(Error::InvalidOwner)
+            }
+            match range{
+                Some((min, max)) if min > max => return Err(Error::InvalidRange),
+                Some(range) => {
+                    self.reading_ranges.insert(pid, range);
+                }
+                None => {
+                    self.reading_ranges.take(&pid);
+                }
+            }
+            Ok(())
+        }
+
+        /// Records a reading of the sensor of `pid`, `unit` being an
+        /// application defined code such as `0` for degrees Celsius. Only the
+        /// latest `max_readings` readings are kept, recording one more evicts
+        /// the oldest.
+        #[ink(message, selector = "0x5A8D95D2")]
+        pub fn record_reading(&mut self, pid: u32, value: i32, unit: u8) -> Result<()>{
+            self.ensure_not_paused()?;
+            if !self.products.contains_key(&pid){
+                return Err(Error::PidNotExists)
+            }
+            if self.sensors.get(&pid) != Some(&Self::env().caller()){
+                return Err(Error::NotSensor)
+            }
+            let index = self.reading_counts.get(&pid).copied().unwrap_or(0);
+            let count = index.checked_add(1).ok_or(Error::ArithmeticOverflow)?;
+            self.reading_counts.insert(pid, count);
+            self.readings.insert((pid, index), (Self::env().block_timestamp(), value, unit));
+            if index >= self.max_readings{
+                self.readings.take(&(pid, index - self.max_readings));
+            }
+            if let Some((min, max)) = self.reading_ranges.get(&pid).copied(){
+                if value < min || value > max{
+                    Self::env().emit_event(ReadingOutOfRange {
+                        pid,
+                        value,
+                        unit,
+                        min,
+                        max,
+                    });
+                }
+            }
+            Ok(())
+        }
+
+        /// Returns the number of readings of `pid` currently kept.
+        #[ink(message, selector = "0xBE737B66")]
+        pub fn reading_count(&self, pid: u32) -> u32{
+            self.reading_counts.get(&pid).copied().unwrap_or(0).min(self.max_readings)
+        }
+
+        /// Returns up to `limit` kept readings of `pid` as `(timestamp,
+        /// value, unit)`, oldest first, skipping the first `offset`. `limit`
+        /// is capped at `MAX_PAGE_SIZE`.
+        #[ink(message, selector = "0xE931B2EB")]
+        pub fn readings_of(&self, pid: u32, offset: u32, limit: u32) -> Vec<(Timestamp, i32, u8)>{
+            let count = self.reading_counts.get(&pid).copied().unwrap_or(0);
+            let start = count.saturating_sub(self.max_readings).saturating_add(offset);
+            let end = start.saturating_add(limit.min(MAX_PAGE_SIZE)).min(count);
+            (start..end)
+                .filter_map(|index| self.readings.get(&(pid, index)).copied())
+                .collect()
+        }
+
+        /// Sets how many readings are kept per product. Lowering it hides the
+        /// older readings at once, raising it does not bring back evicted
+        /// ones. Admin only.
+        #[ink(message, selector = "0xABD32B57")]
+        pub fn set_max_readings(&mut self, max_readings: u32) -> Result<()>{
+            self.as_admin(AdminActionKind::SetMaxReadings, None, None, |this| {
+                if max_readings == 0{
+                    return Err(Error::InvalidRange)
+                }
+                this.max_readings = max_readings;
+                Ok(())
+            })
+        }
+
+        #[ink(message, selector = "0xC51B75A3")]
+        pub fn get_max_readings(&self) -> u32{
+            self.max_readings
+        }
+
+        /// Appends `owner` to the history of `pid`. Co-owners of the previous
+        /// owner lose their shares.
+        ///
+        /// The length saturates on purpose: it runs after the ownership
+        /// change was written, and a product changing hands `u32::MAX` times
+        /// only has its latest owner overwrite the last entry.
+        fn record_owner(&mut self, pid: u32, owner: AccountId){
+            self.clear_co_owners(pid);
+            let len = self.history_lens.entry(pid).or_insert(0);
+            let index = (*len).min(u32::MAX - 1);
+            *len = index + 1;
+            self.history.insert((pid, index), (owner, Self::env().block_timestamp()));
+        }
+
+        #[ink(message, selector = "0x57B8A8A7")]
+        pub fn get_admin(&self) -> AccountId{
+            self.admin
+        }
+
+        /// Returns the account nominated as the next admin, if any.
+        #[ink(message, selector = "0xBCD31D76")]
+        pub fn get_pending_admin(&self) -> Option<AccountId>{
+            self.pending_admin
+        }
+
+        /// Nominates `new_admin`. The current admin stays in charge until
+        /// `new_admin` calls `accept_admin`; a new nomination replaces the
+        /// previous one.
+        #[ink(message, selector = "0xD26C8E14")]
+        pub fn transfer_admin(&mut self, new_admin: AccountId) -> Result<()>{
+            self.as_admin(AdminActionKind::TransferAdmin, Some(new_admin), None, |this| {
+                Self::ensure_not_zero(new_admin)?;
+                this.pending_admin = Some(new_admin);
+                Ok(())
+            })
+        }
+
+        #[ink(message, selector = "0x3830821D")]
+        pub fn accept_admin(&mut self) -> Result<()>{
+            let caller = Self::env().caller();
+            if self.pending_admin != Some(caller){
+                return Err(Error::NotAdmin)
+            }
+            let previous = core::mem::replace(&mut self.admin, caller);
+            self.pending_admin = None;
+            Self::emit_admin_action(AdminActionKind::AcceptAdmin, Some(previous), None);
+            Ok(())
+        }
+
+        /// Stops all product changes. Queries keep working.
+        #[ink(message, selector = "0x81E0C604")]
+        pub fn pause(&mut self) -> Result<()>{
+            self.as_admin(AdminActionKind::Pause, None, None, |this| {
+                this.paused = true;
+                Self::env().emit_event(Paused { admin: this.admin });
+                Ok(())
+            })
+        }
+
+        #[ink(message, selector = "0x67616649")]
+        pub fn unpause(&mut self) -> Result<()>{
+            self.as_admin(AdminActionKind::Unpause, None, None, |this| {
+                this.paused = false;
+                Self::env().emit_event(Unpaused { admin: this.admin });
+                Ok(())
+            })
+        }
+
+        #[ink(message, selector = "0xFA7D505B")]
+        pub fn is_paused(&self) -> bool{
+            self.paused
+        }
+
+        /// Returns the layout version this instance was deployed with.
+        #[ink(message, selector = "0x2CD46380")]
+        pub fn get_schema_version(&self) -> u32{
+            SCHEMA_VERSION
+        }
+
+        /// Announces that the registry is going to be terminated, giving
+        /// holders `TERMINATION_DELAY` blocks to react. Admin only.
+        #[ink(message, selector = "0x8A30A56C")]
+        pub fn announce_termination(&mut self) -> Result<()>{
+            self.as_admin(AdminActionKind::AnnounceTermination, None, None, |this| {
+                let announced_at = Self::env().block_number();
+                this.termination_announced_at = Some(announced_at);
+                Self::env().emit_event(TerminationAnnounced {
+                    announced_at,
+                });
+                Ok(())
+            })
+        }
+
+        /// Removes the contract and sends its balance to `beneficiary`. The
+        /// contract must be paused and the termination announced at least
+        /// `TERMINATION_DELAY` blocks earlier. Fails with `FundsEscrowed`
+        /// while proceeds wait to be withdrawn or an auction is running,
+        /// since the balance would take them along. Admin only.
+        #[ink(message, selector = "0x476D839F")]
+        pub fn terminate(&mut self, beneficiary: AccountId) -> Result<()>{
+            self.as_admin(AdminActionKind::Terminate, Some(beneficiary), None, |this| {
+                if !this.paused{
+                    return Err(Error::NotPaused)
+                }
+                let announced_at = this.termination_announced_at.ok_or(Error::TerminationNotAnnounced)?;
+                if Self::env().block_number() < announced_at.saturating_add(TERMINATION_DELAY){
+                    return Err(Error::TerminationTooEarly)
+                }
+                if this.escrowed > 0 || !this.auctions.is_empty(){
+                    return Err(Error::FundsEscrowed)
+                }
+                Ok(())
+            })?;
+            Self::env().terminate_contract(beneficiary)
+        }
+
+        /// Sets the contract notified of every ownership change, `None`
+        /// stops the notifications. Admin only.
+        #[ink(message, selector = "0xA55C4B84")]
+        pub fn set_registry(&mut self, registry: Option<AccountId>) -> Result<()>{
+            self.as_admin(AdminActionKind::SetRegistry, registry, None, |this| {
+                this.config.registry = registry;
+                Self::emit_config_changed(ConfigField::Registry);
+                Ok(())
+            })
+        }
+
+        #[ink(message, selector = "0x15A5D20A")]
+        pub fn get_registry(&self) -> Option<AccountId>{
+            self.config.registry
+        }
+
+        /// Sets whether an ownership change goes through when notifying the
+        /// registry fails. Admin only.
+        #[ink(message, selector = "0x051367B4")]
+        pub fn set_registry_policy(&mut self, policy: RegistryPolicy) -> Result<()>{
+            self.as_admin(AdminActionKind::SetRegistryPolicy, None, None, |this| {
+                this.registry_policy = policy;
+                Ok(())
+            })
+        }
+
+        #[ink(message, selector = "0xCB9BF060")]
+        pub fn get_registry_policy(&self) -> RegistryPolicy{
+            self.registry_policy
+        }
+
+        /// Notifies the registry, if any, that `pid` went from `old_owner` to
+        /// `new_owner`. Runs after every storage write of the calling
+        /// message; under `RegistryPolicy::Revert` a failing call traps so
+        /// the whole message is reverted.
+        fn notify_registry(&self, pid: u32, old_owner: AccountId, new_owner: AccountId){
+            if let Some(registry) = self.config.registry{
+                let result = build_call::<Environment>()
+                    .callee(registry)
+                    .gas_limit(0)
+                    .transferred_value(0)
+                    .exec_input(
+                        ExecutionInput::new(Selector::new(OWNERSHIP_CHANGED_SELECTOR))
+                            .push_arg(pid)
+                            .push_arg(old_owner)
+                            .push_arg(new_owner)
+                    )
+                    .returns::<()>()
+                    .fire();
+                if result.is_err() && self.registry_policy == RegistryPolicy::Revert{
+                    panic!("registry notification failed")
+                }
+            }
+        }
+
+        #[ink(message, selector = "0x60F84C1B")]
+        pub fn add_manufacturer(&mut self, account: AccountId) -> Result<()>{
+            self.as_admin(AdminActionKind::AddManufacturer, Some(account), None, |this| {
+                Self::ensure_not_zero(account)?;
+                this.manufacturers.insert(account, true);
+                Ok(())
+            })
+        }
+
+        /// Stops `account` from creating products. Products it already owns
+        /// are left untouched.
+        #[ink(message, selector = "0xD623CB5B")]
+        pub fn remove_manufacturer(&mut self, account: AccountId) -> Result<()>{
+            self.as_admin(AdminActionKind::RemoveManufacturer, Some(account), None, |this| {
+                this.manufacturers.take(&account);
+                Ok(())
+            })
+        }
+
+        #[ink(message, selector = "0xBFE25BF5")]
+        pub fn is_manufacturer(&self, account: AccountId) -> bool{
+            self.manufacturers.get(&account).copied().unwrap_or(false)
+        }
+
+        /// Registers `account` as a certifier. Admin only.
+        #[ink(message, selector = "0x4CDC9F8F")]
+        pub fn add_certifier(&mut self, account: AccountId) -> Result<()>{
+            self.as_admin(AdminActionKind::AddCertifier, Some(account), None, |this| {
+                Self::ensure_not_zero(account)?;
+                this.certifiers.insert(account, true);
+                Ok(())
+            })
+        }
+
+        /// Stops `account` from certifying products. Certificates it already
+        /// issued are left untouched.
+        #[ink(message, selector = "0xED04C119")]
+        pub fn remove_certifier(&mut self, account: AccountId) -> Result<()>{
+            self.as_admin(AdminActionKind::RemoveCertifier, Some(account), None, |this| {
+                this.certifiers.take(&account);
+                Ok(())
+            })
+        }
+
+        #[ink(message, selector = "0x858DF09C")]
+        pub fn is_certifier(&self, account: AccountId) -> bool{
+            self.certifiers.get(&account).copied().unwrap_or(false)
+        }
+
+        /// Attaches the certificate `cert_code`, backed by the off-chain
+        /// document hashing to `document_hash`, to `pid`. Each certifier can
+        /// issue a given code once per product.
+        #[ink(message, selector = "0xF3823E07")]
+        pub fn certify(&mut self, pid: u32, cert_code: u16, document_hash: Hash) -> Result<()>{
+            self.ensure_not_paused()?;
+            let certifier = Self::env().caller();
+            if !self.is_certifier(certifier){
+                return Err(Error::NotCertifier)
+            }
+            if !self.products.contains_key(&pid){
+                return Err(Error::PidNotExists)
+            }
+            let certifications = self.certifications.entry(pid).or_insert_with(Vec::new);
+            if certifications.iter().any(|(by, code, _)| *by == certifier && *code == cert_code){
+                return Err(Error::DuplicateCertification)
+            }
+            certifications.push((certifier, cert_code, document_hash));
+            Self::env().emit_event(ProductCertified {
+                pid,
+                certifier,
+                cert_code,
+                document_hash,
+            });
+            Ok(())
+        }
+
+        #[ink(message, selector = "0x015995C8")]
+        pub fn certifications_of(&self, pid: u32) -> Vec<(AccountId, u16, Hash)>{
+            self.certifications.get(&pid).cloned().unwrap_or_default()
+        }
+
+        /// Withdraws the certificate `cert_code` from `pid`. A certifier
+        /// revokes the one it issued, the admin revokes it from every issuer.
+        /// Revoked certificates move to `revoked_certifications_of`.
+        #[ink(message, selector = "0x5E59886D")]
+        pub fn revoke_certification(&mut self, pid: u32, cert_code: u16) -> Result<()>{
+            self.ensure_not_paused()?;
+            let caller = Self::env().caller();
+            if !self.products.contains_key(&pid){
+                return Err(Error::PidNotExists)
+            }
+            let certifications = self.certifications_of(pid);
+            let issued = |by: &AccountId, code: &u16| *code == cert_code && (*by == caller || caller == self.admin);
+            if !certifications.iter().any(|(_, code, _)| *code == cert_code){
+                return Err(Error::CertificationNotFound)
+            }
+            if !certifications.iter().any(|(by, code, _)| issued(by, code)){
+                return Err(Error::NotIssuer)
+            }
+            let (revoked, kept): (Vec<_>, Vec<_>) = certifications
+                .into_iter()
+                .partition(|(by, code, _)| issued(by, code));
+            self.certifications.insert(pid, kept);
+            for (certifier, cert_code, _) in revoked.iter(){
+                Self::env().emit_event(CertificationRevoked {
+                    pid,
+                    certifier: *certifier,
+                    cert_code: *cert_code,
+                    revoked_by: caller,
+                });
+            }
+            self.revoked_certifications.entry(pid).or_insert_with(Vec::new).extend(revoked);
+            Ok(())
+        }
+
+        #[ink(message, selector = "0x68F68659")]
+        pub fn revoked_certifications_of(&self, pid: u32) -> Vec<(AccountId, u16, Hash)>{
+            self.revoked_certifications.get(&pid).cloned().unwrap_or_default()
+        }
+
+        /// Sets the account resolving disputes. Admin only.
+        #[ink(message, selector = "0xB03E7015")]
+        pub fn set_arbiter(&mut self, arbiter: AccountId) -> Result<()>{
+            self.as_admin(AdminActionKind::SetArbiter, Some(arbiter), None, |this| {
+                Self::ensure_not_zero(arbiter)?;
+                this.config.arbiter = Some(arbiter);
+                Self::emit_config_changed(ConfigField::Arbiter);
+                Ok(())
+            })
+        }
+
+        #[ink(message, selector = "0x35235F5C")]
+        pub fn get_arbiter(&self) -> Option<AccountId>{
+            self.config.arbiter
+        }
+
+        /// Disputes the last accepted delegation of `pid`, e.g. because the
+        /// product received is counterfeit. Either the current owner or the
+        /// previous one may raise it within `DISPUTE_WINDOW` blocks of the
+        /// acceptance. `reason_hash` points to the off-chain claim.
+        #[ink(message, selector = "0x973C7100")]
+        pub fn raise_dispute(&mut self, pid: u32, reason_hash: Hash) -> Result<()>{
+            self.ensure_not_paused()?;
+            let caller = Self::env().caller();
+            let p = self.products.get(&pid).ok_or(Error::PidNotExists)?;
+            let (owner, state) = (p.get_owner(), p.get_state());
+            let (previous_owner, accepted_at) = self.last_acceptance.get(&pid).copied().ok_or(Error::DisputeWindowClosed)?;
+            if caller != owner && caller != previous_owner{
+                return Err(Error::InvalidOwner)
+            }
+            if Self::env().block_number() > accepted_at.saturating_add(DISPUTE_WINDOW){
+                return Err(Error::DisputeWindowClosed)
+            }
+            state.transition(Action::Dispute)?;
+            self.change_product(pid, StoredProduct::dispute)?;
+            self.approvals.take(&pid);
+            Self::env().emit_event(DisputeRaised {
+                pid,
+                raised_by: caller,
+                reason_hash,
+            });
+            Ok(())
+        }
+
+        /// Resolves the dispute on `pid`, handing it back to the previous
+        /// owner or confirming the current one. Arbiter only.
+        #[ink(message, selector = "0x539B8B08")]
+        pub fn resolve_dispute(&mut self, pid: u32, return_to_previous_owner: bool) -> Result<()>{
+            let _guard = ReentrancyGuard::enter()?;
+            if self.config.arbiter != Some(Self::env().caller()){
+                return Err(Error::NotArbiter)
+            }
+            let p = self.products.get(&pid).ok_or(Error::PidNotExists)?;
+            p.get_state().transition(Action::ResolveDispute)?;
+            let current_owner = p.get_owner();
+            let (previous_owner, _) = self.last_acceptance.take(&pid).expect("disputed products have an acceptance");
+            let owner = if return_to_previous_owner { previous_owner } else { current_owner };
+            self.change_product(pid, |p| p.resolve_dispute(owner)).expect("dispute was checked");
+            if owner != current_owner{
+                self.index_remove(current_owner, pid);
+                self.index_add(owner, pid);
+                self.record_owner(pid, owner);
+                self.notify_registry(pid, current_owner, owner);
+            }
+            Self::env().emit_event(DisputeResolved {
+                pid,
+                owner,
+                id: self.id(pid),
+                returned_to_previous_owner: return_to_previous_owner,
+            });
+            Ok(())
+        }
+
+        /// Sets the account, typically an oracle contract, reporting on
+        /// physical inspections. With `freeze_on_failure` a failed
+        /// verification also freezes the product. Admin only.
+        #[ink(message, selector = "0xAD28346F")]
+        pub fn set_oracle(&mut self, oracle: AccountId, freeze_on_failure: bool) -> Result<()>{
+            self.as_admin(AdminActionKind::SetOracle, Some(oracle), None, |this| {
+                Self::ensure_not_zero(oracle)?;
+                this.config.oracle = Some(oracle);
+                this.freeze_on_failed_verification = freeze_on_failure;
+                Self::emit_config_changed(ConfigField::Oracle);
+                Ok(())
+            })
+        }
+
+        #[ink(message, selector = "0x84896D00")]
+        pub fn get_oracle(&self) -> Option<AccountId>{
+            self.config.oracle
+        }
+
+        /// Asks the oracle to verify `pid`. The product cannot change hands
+        /// until the oracle submits its report. Owner only.
+        #[ink(message, selector = "0x6A3FEC45")]
+        pub fn request_verification(&mut self, pid: u32) -> Result<()>{
+            self.ensure_not_paused()?;
+            if self.config.oracle.is_none(){
+                return Err(Error::OracleNotSet)
+            }
+            let owner = Self::env().caller();
+            let p = self.products.get(&pid).ok_or(Error::PidNotExists)?;
+            if p.get_owner() != owner{
+                return Err(Error::InvalidOwner)
+            }
+            if p.is_frozen(){
+                return Err(Error::ProductFrozen)
+            }
+            p.get_state().transition(Action::RequestVerification)?;
+            self.change_product(pid, StoredProduct::request_verification)?;
+            Self::env().emit_event(VerificationRequested {
+                pid,
+                owner,
+            });
+            Ok(())
+        }
+
+        /// Reports the outcome of the inspection of `pid`, `report_hash`
+        /// anchoring the off-chain report. Oracle only.
+        #[ink(message, selector = "0x1B0BAA6E")]
+        pub fn submit_verification(&mut self, pid: u32, passed: bool, report_hash: Hash) -> Result<()>{
+            if self.config.oracle != Some(Self::env().caller()){
+                return Err(Error::NotOracle)
+            }
+            let freeze = !passed && self.freeze_on_failed_verification;
+            self.products.get(&pid).ok_or(Error::PidNotExists)?.get_state().transition(Action::SubmitVerification)?;
+            self.change_product(pid, |p| p.record_verification(passed, report_hash))?;
+            if passed{
+                Self::env().emit_event(Verified {
+                    pid,
+                    report_hash,
+                });
+                return Ok(())
+            }
+            if freeze{
+                self.product_mut(pid).expect("report was checked").set_frozen(true);
+            }
+            Self::env().emit_event(VerificationFailed {
+                pid,
+                report_hash,
+                frozen: freeze,
+            });
+            if freeze{
+                Self::env().emit_event(ProductFrozen { pid });
+            }
+            Ok(())
+        }
+
+        /// Returns whether the latest verification of `pid` passed, with its
+        /// report hash, `None` before the first one.
+        #[ink(message, selector = "0x20BBC038")]
+        pub fn verification_of(&self, pid: u32) -> Result<Option<(bool, Hash)>>{
+            self.products
+                .get(&pid)
+                .map(|p| p.verification)
+                .ok_or(Error::PidNotExists)
+        }
+
+        /// Bars `account` from creating, being delegated or receiving
+        /// products, or lifts the bar. Admin only.
+        #[ink(message, selector = "0x06D1F6ED")]
+        pub fn set_blacklisted(&mut self, account: AccountId, blacklisted: bool) -> Result<()>{
+            self.as_admin(AdminActionKind::SetBlacklisted, Some(account), None, |this| {
+                if blacklisted{
+                    this.blacklist.insert(account, true);
+                } else {
+                    this.blacklist.take(&account);
+                }
+                Ok(())
+            })
+        }
+
+        #[ink(message, selector = "0x5663FE67")]
+        pub fn is_blacklisted(&self, account: AccountId) -> bool{
+            self.blacklist.get(&account).copied().unwrap_or(false)
+        }
+
+        fn ensure_not_blacklisted(&self, account: AccountId) -> Result<()>{
+            if self.is_blacklisted(account){
+                return Err(Error::Blacklisted)
+            }
+            Ok(())
+        }
+
+        fn ensure_manufacturer(&self) -> Result<()>{
+            if !self.is_manufacturer(Self::env().caller()){
+                return Err(Error::NotManufacturer)
+            }
+            Ok(())
+        }
+
+        fn ensure_not_paused(&self) -> Result<()>{
+            if self.paused{
+                return Err(Error::Paused)
+            }
+            Ok(())
+        }
+
+        fn ensure_admin(&self) -> Result<()>{
+            if Self::env().caller() != self.admin{
+                return Err(Error::NotAdmin)
+            }
+            Ok(())
+        }
+
+        /// Runs `action` on behalf of the admin, failing with `NotAdmin`
+        /// for anyone else. The `AdminAction` event is only emitted once
+        /// `action` succeeded, so a rejected call leaves no audit entry.
+        /// Every admin-gated message goes through here.
+        fn as_admin<T>(
+            &mut self,
+            kind: AdminActionKind,
+            subject: Option<AccountId>,
+            pid: Option<u32>,
+            action: impl FnOnce(&mut Self) -> Result<T>,
+        ) -> Result<T>{
+            self.ensure_admin()?;
+            let result = action(self)?;
+            Self::emit_admin_action(kind, subject, pid);
+            Ok(result)
+        }
+
+        fn emit_admin_action(kind: AdminActionKind, subject: Option<AccountId>, pid: Option<u32>){
+            Self::env().emit_event(AdminAction {
+                admin: Self::env().caller(),
+                action: kind as u8,
+                subject,
+                pid,
+            });
+        }
+
+        /// Rejects the default account id as the target of a transfer.
+        fn ensure_not_zero(account: AccountId) -> Result<()>{
+            if account == AccountId::from([0x0; 32]){
+                return Err(Error::ZeroAddress)
+            }
+            Ok(())
+        }
+
+        /// Records `pid` as owned by `owner` in `owned_products` and
+        /// `owned_counts`. Together with `index_remove` it is the only
+        /// writer of either, every ownership change calls the pair; see
+        /// `check_owner_index`.
+        fn index_add(&mut self, owner: AccountId, pid: u32){
+            self.owned_products.entry(owner).or_insert_with(Vec::new).push(pid);
+            // bounded by the number of pids, so it never actually saturates
+            let count = self.owned_counts.entry(owner).or_insert(0);
+            *count = count.saturating_add(1);
+        }
+
+        fn remove_pending_delegation(&mut self, delegate: AccountId, pid: u32){
+            if let Some(pids) = self.pending_delegations.get_mut(&delegate){
+                if let Some(index) = pids.iter().position(|p| *p == pid){
+                    pids.swap_remove(index);
+                }
+            }
+        }
+
+        /// Runs `change` on the stored `pid` and emits `StateChanged` when it
+        /// moves the product to another state. Every state change goes
+        /// through here once the caller checked it.
+        fn change_product<T>(&mut self, pid: u32, change: impl FnOnce(&mut StoredProduct) -> T) -> T{
+            let p = self.product_mut(pid).expect("product was checked");
+            let from = p.state;
+            let result = change(p);
+            if p.state != from{
+                Self::env().emit_event(StateChanged {
+                    pid,
+                    from: from as u8,
+                    to: p.state as u8,
+                    actor: Self::env().caller(),
+                });
+            }
+            result
+        }
+
+        /// Drops `pid` from storage and every index, it is unknown afterwards.
+        /// Its logs, certifications and warranty claims included, stay
+        /// readable. Returns the hash identifier it had, which no longer
+        /// resolves.
+        fn retire(&mut self, pid: u32, owner: AccountId, code: u16) -> Hash{
+            self.touch_products();
+            let id = self.ids.take(&pid).expect("every product has an id");
+            self.pids_by_id.take(&id);
+            let lot_id = self.products.take(&pid).and_then(|p| p.get_lot_id());
+            self.metadata.take(&pid);
+            self.metadata_uris.take(&pid);
+            self.approvals.take(&pid);
+            self.last_acceptance.take(&pid);
+            self.clear_co_owners(pid);
+            self.index_remove(owner, pid);
+            if let Some(pids) = self.code_index.get_mut(&code){
+                pids.retain(|p| *p != pid);
+            }
+            if let Some(pids) = lot_id.and_then(|lot_id| self.lots.get_mut(&lot_id)){
+                pids.retain(|p| *p != pid);
+            }
+            id
+        }
+
+        /// Drops `pid` from the products indexed for `owner`, doing nothing
+        /// if it is not there.
+        fn index_remove(&mut self, owner: AccountId, pid: u32){
+            if let Some(pids) = self.owned_products.get_mut(&owner){
+                if let Some(index) = pids.iter().position(|p| *p == pid){
+                    pids.swap_remove(index);
+                    if let Some(count) = self.owned_counts.get_mut(&owner){
+                        *count = count.saturating_sub(1);
+                    }
+                }
+            }
+        }
+
+        /// Cross-checks the owner index against the products: every live
+        /// product is listed exactly once, under its owner, every listed pid
+        /// is live and owned by the account listing it, and every count
+        /// matches its list. Returns the first inconsistency found.
+        #[cfg(test)]
+        fn check_owner_index(&self) -> core::result::Result<(), String>{
+            for (pid, p) in self.products.iter(){
+                let listed = self.owned_products.get(&p.get_owner()).map_or(0, |pids| pids.iter().filter(|listed| *listed == pid).count());
+                if listed != 1{
+                    return Err(format!("pid {} is listed {} times under its owner", pid, listed))
+                }
+            }
+            for (owner, pids) in self.owned_products.iter(){
+                for pid in pids{
+                    match self.products.get(pid){
+                        Some(p) if p.get_owner() == *owner => {}
+                        Some(_) => return Err(format!("pid {} is listed under an account not owning it", pid)),
+                        None => return Err(format!("pid {} is listed but not live", pid)),
+                    }
+                }
+                if self.count_of(*owner) as usize != pids.len(){
+                    return Err(format!("count of {:?} is {} for {} pids", owner, self.count_of(*owner), pids.len()))
+                }
+            }
+            for (owner, count) in self.owned_counts.iter(){
+                if *count != 0 && !self.owned_products.contains_key(owner){
+                    return Err(format!("{:?} is counted without a list", owner))
+                }
+            }
+            Ok(())
+        }
+
+            
+    }
+
+    impl PSP34 for ProductFactory {
+        #[ink(message)]
+        fn balance_of(&self, owner: AccountId) -> u32{
+            self.count_of(owner)
+        }
+
+        #[ink(message)]
+        fn owner_of(&self, id: Id) -> Option<AccountId>{
+            id.pid().and_then(|pid| self.products.get(&pid)).map(|p| p.owner)
+        }
+
+        #[ink(message)]
+        fn allowance(&self, owner: AccountId, operator: AccountId, id: Option<Id>) -> bool{
+            if self.is_approved_for_all(owner, operator){
+                return true
+            }
+            match id.and_then(|id| id.pid()){
+                Some(pid) => self.owner_of(Id::U32(pid)) == Some(owner)
+                    && self.approvals.get(&pid) == Some(&operator),
+                None => false,
+            }
+        }
+
+        #[ink(message)]
+        fn approve(&mut self, operator: AccountId, id: Option<Id>, approved: bool) -> core::result::Result<(), PSP34Error>{
+            self.ensure_not_paused()?;
+            let caller = Self::env().caller();
+            if operator == caller{
+                return Err(PSP34Error::SelfApprove)
+            }
+            let id = match id{
+                Some(id) => id,
+                None => return Ok(self.set_approval_for_all(operator, approved)?),
+            };
+            let pid = id.pid().ok_or(PSP34Error::TokenNotExists)?;
+            Ok(self.set_approval(pid, operator, approved)?)
+        }
+
+        #[ink(message)]
+        fn transfer(&mut self, to: AccountId, id: Id, _data: Vec<u8>) -> core::result::Result<(), PSP34Error>{
+            let _guard = ReentrancyGuard::enter()?;
+            self.ensure_not_paused()?;
+            let pid = id.pid().ok_or(PSP34Error::TokenNotExists)?;
+            let owner = self.transferring_owner(pid, Self::env().caller())?;
+            Ok(self.move_product(pid, owner, to)?)
+        }
+
+        #[ink(message)]
+        fn total_supply(&self) -> Balance{
+            Balance::from(self.products.len())
+        }
+    }
+
+    impl ProductRegistry for ProductFactory {
+        #[ink(message, payable)]
+        fn create_product(&mut self, code: u16, quantity: Option<u32>, warranty_until: Option<Timestamp>) -> Result<u32>{
+            ProductFactory::create_product(self, code, quantity, warranty_until)
+        }
+
+        #[ink(message)]
+        fn delegate_product(&mut self, pid: u32, delegate_to: AccountId, deadline: Option<Timestamp>, price: Option<Balance>, memo: Option<Vec<u8>>) -> Result<()>{
+            ProductFactory::delegate_product(self, pid, delegate_to, deadline, price, memo)
+        }
+
+        #[ink(message, payable)]
+        fn accept_product(&mut self, pid: u32) -> Result<()>{
+            ProductFactory::accept_product(self, pid)
+        }
+
+        #[ink(message)]
+        fn get_product(&self, pid: u32) -> Result<Product>{
+            ProductFactory::get_product(self, pid)
+        }
+
+        #[ink(message)]
+        fn product_count(&self) -> u32{
+            ProductFactory::product_count(self)
+        }
+    }
+
+    impl Default for ProductFactory {
+        fn default() -> Self {
+            Self::new(0, 0)
+        }
+    }
+    
+/// Unit tests in Rust are normally defined within such a `#[cfg(test)]`
+/// module and test functions are marked with a `#[test]` attribute.
+/// The below code is technically just normal Rust code.
+    #[cfg(test)]
+    mod tests {
+        /// Imports all the definitions from the outer scope so we can use them here.
+        use super::*;
+        
+        /// Imports `ink_lang` so we can use `#[ink::test]`.
+        use ink_lang as ink;
+
+        use ink_env::{
+            call,
+            test,
+        };
+
+        type Event = <ProductFactory as ::ink_lang::BaseEvent>::Type;
+
+        #[ink::test]
+        fn create_product_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.product_count(), 0);
+            assert_eq!(product_factory.create_product(1, None, None), Ok(0));
+
+            assert_eq!(product_factory.get_last().unwrap().owner, accounts.alice);
+            assert_eq!(product_factory.get_last().unwrap().state, State::Owned);
+        }
+
+        #[ink::test]
+        fn create_products_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.create_product(1, None, None).unwrap();
+
+            assert_eq!(product_factory.create_products(vec![5, 6, 7]), Ok(vec![1, 2, 3]));
+            assert_eq!(product_factory.get_product(1).unwrap().code, 5);
+            assert_eq!(product_factory.get_product(3).unwrap().code, 7);
+            assert_eq!(product_factory.get_product(3).unwrap().owner, accounts.alice);
+            assert_eq!(ink_env::test::recorded_events().count(), 4);
+
+            assert_eq!(product_factory.create_products(Vec::new()), Ok(Vec::new()));
+            assert_eq!(
+                product_factory.create_products(vec![1; MAX_BATCH_SIZE + 1]),
+                Err(Error::BatchTooLarge)
+            );
+            assert_eq!(product_factory.product_count(), 4);
+            assert_eq!(
+                product_factory.create_products((100..100 + MAX_BATCH_SIZE as u16).collect()).map(|pids| pids.len()),
+                Ok(MAX_BATCH_SIZE)
+            );
+        }
+
+        #[ink::test]
+        fn get_products_test() {
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.get_products(0, 10, false), Vec::new());
+
+            for code in 0..5 {
+                product_factory.create_product(code, None, None).unwrap();
+            }
+            let page = product_factory.get_products(0, 2, false);
+            assert_eq!(page.iter().map(|(pid, _)| *pid).collect::<Vec<_>>(), vec![0, 1]);
+            let page = product_factory.get_products(3, 10, false);
+            assert_eq!(page.iter().map(|(pid, _)| *pid).collect::<Vec<_>>(), vec![3, 4]);
+            assert_eq!(page[1].1.code, 4);
+            assert_eq!(product_factory.get_products(5, 10, false), Vec::new());
+            assert_eq!(product_factory.get_products(u32::MAX, u32::MAX, false), Vec::new());
+            assert_eq!(product_factory.get_products(0, 0, false), Vec::new());
+
+            product_factory.create_products((100..100 + MAX_BATCH_SIZE as u16).collect()).unwrap();
+            assert_eq!(
+                product_factory.get_products(0, u32::MAX, false).len(),
+                MAX_PAGE_SIZE as usize
+            );
+        }
+
+        #[ink::test]
+        fn create_product_with_metadata_test() {
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            let name = vec![b'n'; MAX_NAME_LEN];
+            let description = vec![b'd'; MAX_DESCRIPTION_LEN];
+
+            assert_eq!(
+                product_factory.create_product_with_metadata(1, name.clone(), description.clone()),
+                Ok(0)
+            );
+            let product = product_factory.get_product(0).unwrap();
+            assert_eq!(product.get_name(), name);
+            assert_eq!(product.get_description(), description);
+
+            assert_eq!(
+                product_factory.create_product_with_metadata(1, vec![0; MAX_NAME_LEN + 1], Vec::new()),
+                Err(Error::MetadataTooLong)
+            );
+            assert_eq!(
+                product_factory.create_product_with_metadata(1, Vec::new(), vec![0; MAX_DESCRIPTION_LEN + 1]),
+                Err(Error::MetadataTooLong)
+            );
+            assert_eq!(product_factory.product_count(), 1);
+
+            product_factory.create_product(2, None, None).unwrap();
+            assert_eq!(product_factory.get_product(1).unwrap().get_name(), Vec::<u8>::new());
+        }
+
+        #[ink::test]
+        fn unique_code_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.add_manufacturer(accounts.bob).unwrap();
+            assert_eq!(product_factory.create_product(42, None, None), Ok(0));
+            assert_eq!(product_factory.create_product(43, None, None), Ok(1));
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.create_product(42, None, None), Err(Error::DuplicateCode));
+            assert_eq!(
+                product_factory.create_product_with_metadata(43, Vec::new(), Vec::new()),
+                Err(Error::DuplicateCode)
+            );
+            assert_eq!(product_factory.create_products(vec![44, 42]), Err(Error::DuplicateCode));
+            assert_eq!(product_factory.create_products(vec![44, 44]), Err(Error::DuplicateCode));
+            assert_eq!(product_factory.product_count(), 2);
+            assert_eq!(product_factory.create_products(vec![44, 45]), Ok(vec![2, 3]));
+
+            // Burning does not free the code.
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.burn_product(0), Ok(()));
+            assert_eq!(product_factory.create_product(42, None, None), Err(Error::DuplicateCode));
+        }
+
+        #[ink::test]
+        fn get_products_by_code_test() {
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.get_products_by_code(7), Vec::<u32>::new());
+
+            product_factory.create_product(7, None, None).unwrap();
+            product_factory.create_product_with_metadata(8, Vec::new(), Vec::new()).unwrap();
+            product_factory.create_products(vec![9, 10]).unwrap();
+            assert_eq!(product_factory.get_products_by_code(7), vec![0]);
+            assert_eq!(product_factory.get_products_by_code(8), vec![1]);
+            assert_eq!(product_factory.get_products_by_code(9), vec![2]);
+            assert_eq!(product_factory.get_products_by_code(10), vec![3]);
+            assert_eq!(product_factory.get_products_by_code(11), Vec::<u32>::new());
+
+            assert_eq!(product_factory.burn_product(2), Ok(()));
+            assert_eq!(product_factory.get_products_by_code(9), Vec::<u32>::new());
+        }
+
+        #[ink::test]
+        fn product_count_test() {
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.product_count(), 0);
+            product_factory.create_product(1, None, None).unwrap();
+            product_factory.create_product(2, None, None).unwrap();
+            assert_eq!(product_factory.product_count(), 2);
+        }
+
+        #[ink::test]
+        fn create_product_returns_pid_test() {
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.create_product(1, None, None), Ok(0));
+            assert_eq!(product_factory.create_product(2, None, None), Ok(1));
+            assert_eq!(product_factory.create_product(3, None, None), Ok(2));
+            assert_eq!(product_factory.get_product(1).unwrap().code, 2);
+            assert_eq!(product_factory.get_product(2).unwrap().code, 3);
+        }
+
+        #[ink::test]
+        fn pids_stable_and_dense_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.add_manufacturer(accounts.bob).unwrap();
+            for code in 0..5 {
+                assert_eq!(product_factory.create_product(code, None, None), Ok(u32::from(code)));
+            }
+            assert_eq!(product_factory.product_count(), 5);
+
+            assert_eq!(product_factory.delegate_product(2, accounts.bob, None, None, None), Ok(()));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.accept_product(2), Ok(()));
+
+            // Mutating a product must not move it nor any other product.
+            for pid in 0..5 {
+                assert_eq!(product_factory.get_product(pid).unwrap().code, pid as u16);
+            }
+            assert_eq!(product_factory.get_product(2).unwrap().owner, accounts.bob);
+            assert_eq!(product_factory.get_product(5), Err(Error::PidNotExists));
+            assert_eq!(product_factory.create_product(9, None, None), Ok(5));
+        }
+
+        #[ink::test]
+        fn state_transition_test() {
+            let states = [
+                State::Owned,
+                State::PendingDelegation,
+                State::Recalled,
+                State::ComponentLocked,
+                State::Disputed,
+                State::Listed,
+                State::Auctioned,
+                State::Lent,
+                State::PendingVerification,
+            ];
+            let actions = [
+                Action::Delegate,
+                Action::Accept,
+                Action::Cancel,
+                Action::Transfer,
+                Action::Burn,
+                Action::Recall,
+                Action::ResolveRecall,
+                Action::Split,
+                Action::Merge,
+                Action::Assemble,
+                Action::Disassemble,
+                Action::Release,
+                Action::Dispute,
+                Action::ResolveDispute,
+                Action::List,
+                Action::Unlist,
+                Action::Buy,
+                Action::StartAuction,
+                Action::FinalizeAuction,
+                Action::UpdateCode,
+                Action::Lend,
+                Action::Return,
+                Action::RequestVerification,
+                Action::SubmitVerification,
+            ];
+            let expected = [
+                // Owned
+                [
+                    Ok(State::PendingDelegation),
+                    Err(Error::InvalidState),
+                    Err(Error::InvalidState),
+                    Ok(State::Owned),
+                    Ok(State::Owned),
+                    Ok(State::Recalled),
+                    Err(Error::InvalidState),
+                    Ok(State::Owned),
+                    Ok(State::Owned),
+                    Ok(State::ComponentLocked),
+                    Ok(State::Owned),
+                    Err(Error::InvalidState),
+                    Ok(State::Disputed),
+                    Err(Error::InvalidState),
+                    Ok(State::Listed),
+                    Err(Error::InvalidState),
+                    Err(Error::NotListed),
+                    Ok(State::Auctioned),
+                    Err(Error::InvalidState),
+                    Ok(State::Owned),
+                    Ok(State::Lent),
+                    Err(Error::InvalidState),
+                    Ok(State::PendingVerification),
+                    Err(Error::InvalidState),
+                ],
+                // PendingDelegation
+                [
+                    Err(Error::InvalidState),
+                    Ok(State::Owned),
+                    Ok(State::Owned),
+                    Err(Error::InvalidState),
+                    Err(Error::InvalidState),
+                    Ok(State::Recalled),
+                    Err(Error::InvalidState),
+                    Err(Error::InvalidState),
+                    Err(Error::InvalidState),
+                    Err(Error::InvalidState),
+                    Err(Error::InvalidState),
+                    Err(Error::InvalidState),
+                    Err(Error::InvalidState),
+                    Err(Error::InvalidState),
+                    Err(Error::InvalidState),
+                    Err(Error::InvalidState),
+                    Err(Error::NotListed),
+                    Err(Error::InvalidState),
+                    Err(Error::InvalidState),
+                    Err(Error::InvalidState),
+                    Err(Error::InvalidState),
+                    Err(Error::InvalidState),
+                    Err(Error::InvalidState),
+                    Err(Error::InvalidState),
+                ],
+                // Recalled
+                [
+                    Err(Error::ProductRecalled),
+                    Err(Error::ProductRecalled),
+                    Err(Error::ProductRecalled),
+                    Err(Error::ProductRecalled),
+                    Err(Error::ProductRecalled),
+                    Err(Error::ProductRecalled),
+                    Ok(State::Owned),
+                    Err(Error::ProductRecalled),
+                    Err(Error::ProductRecalled),
+                    Err(Error::ProductRecalled),
+                    Err(Error::ProductRecalled),
+                    Err(Error::ProductRecalled),
+                    Err(Error::ProductRecalled),
+                    Err(Error::ProductRecalled),
+                    Err(Error::ProductRecalled),
+                    Err(Error::ProductRecalled),
+                    Err(Error::ProductRecalled),
+                    Err(Error::ProductRecalled),
+                    Err(Error::ProductRecalled),
+                    Err(Error::ProductRecalled),
+                    Err(Error::ProductRecalled),
+                    Err(Error::ProductRecalled),
+                    Err(Error::ProductRecalled),
+                    Err(Error::ProductRecalled),
+                ],
+                // ComponentLocked
+                [
+                    Err(Error::ComponentLocked),
+                    Err(Error::ComponentLocked),
+                    Err(Error::ComponentLocked),
+                    Err(Error::ComponentLocked),
+                    Err(Error::ComponentLocked),
+                    Err(Error::ComponentLocked),
+                    Err(Error::ComponentLocked),
+                    Err(Error::ComponentLocked),
+                    Err(Error::ComponentLocked),
+                    Err(Error::ComponentLocked),
+                    Err(Error::ComponentLocked),
+                    Ok(State::Owned),
+                    Err(Error::ComponentLocked),
+                    Err(Error::ComponentLocked),
+                    Err(Error::ComponentLocked),
+                    Err(Error::ComponentLocked),
+                    Err(Error::ComponentLocked),
+                    Err(Error::ComponentLocked),
+                    Err(Error::ComponentLocked),
+                    Err(Error::ComponentLocked),
+                    Err(Error::ComponentLocked),
+                    Err(Error::ComponentLocked),
+                    Err(Error::ComponentLocked),
+                    Err(Error::ComponentLocked),
+                ],
+                // Disputed
+                [
+                    Err(Error::ProductDisputed),
+                    Err(Error::ProductDisputed),
+                    Err(Error::ProductDisputed),
+                    Err(Error::ProductDisputed),
+                    Err(Error::ProductDisputed),
+                    Err(Error::ProductDisputed),
+                    Err(Error::ProductDisputed),
+                    Err(Error::ProductDisputed),
+                    Err(Error::ProductDisputed),
+                    Err(Error::ProductDisputed),
+                    Err(Error::ProductDisputed),
+                    Err(Error::ProductDisputed),
+                    Err(Error::ProductDisputed),
+                    Ok(State::Owned),
+                    Err(Error::ProductDisputed),
+                    Err(Error::ProductDisputed),
+                    Err(Error::ProductDisputed),
+                    Err(Error::ProductDisputed),
+                    Err(Error::ProductDisputed),
+                    Err(Error::ProductDisputed),
+                    Err(Error::ProductDisputed),
+                    Err(Error::ProductDisputed),
+                    Err(Error::ProductDisputed),
+                    Err(Error::ProductDisputed),
+                ],
+                // Listed
+                [
+                    Err(Error::ProductListed),
+                    Err(Error::ProductListed),
+                    Err(Error::ProductListed),
+                    Err(Error::ProductListed),
+                    Err(Error::ProductListed),
+                    Ok(State::Recalled),
+                    Err(Error::ProductListed),
+                    Err(Error::ProductListed),
+                    Err(Error::ProductListed),
+                    Err(Error::ProductListed),
+                    Err(Error::ProductListed),
+                    Err(Error::ProductListed),
+                    Err(Error::ProductListed),
+                    Err(Error::ProductListed),
+                    Err(Error::ProductListed),
+                    Ok(State::Owned),
+                    Ok(State::Owned),
+                    Err(Error::ProductListed),
+                    Err(Error::ProductListed),
+                    Err(Error::ProductListed),
+                    Err(Error::ProductListed),
+                    Err(Error::ProductListed),
+                    Err(Error::ProductListed),
+                    Err(Error::ProductListed),
+                ],
+                // Auctioned
+                [
+                    Err(Error::ProductAuctioned),
+                    Err(Error::ProductAuctioned),
+                    Err(Error::ProductAuctioned),
+                    Err(Error::ProductAuctioned),
+                    Err(Error::ProductAuctioned),
+                    Err(Error::ProductAuctioned),
+                    Err(Error::ProductAuctioned),
+                    Err(Error::ProductAuctioned),
+                    Err(Error::ProductAuctioned),
+                    Err(Error::ProductAuctioned),
+                    Err(Error::ProductAuctioned),
+                    Err(Error::ProductAuctioned),
+                    Err(Error::ProductAuctioned),
+                    Err(Error::ProductAuctioned),
+                    Err(Error::ProductAuctioned),
+                    Err(Error::ProductAuctioned),
+                    Err(Error::ProductAuctioned),
+                    Err(Error::ProductAuctioned),
+                    Ok(State::Owned),
+                    Err(Error::ProductAuctioned),
+                    Err(Error::ProductAuctioned),
+                    Err(Error::ProductAuctioned),
+                    Err(Error::ProductAuctioned),
+                    Err(Error::ProductAuctioned),
+                ],
+                // Lent
+                [
+                    Err(Error::ProductLent),
+                    Err(Error::ProductLent),
+                    Err(Error::ProductLent),
+                    Err(Error::ProductLent),
+                    Err(Error::ProductLent),
+                    Err(Error::ProductLent),
+                    Err(Error::ProductLent),
+                    Err(Error::ProductLent),
+                    Err(Error::ProductLent),
+                    Err(Error::ProductLent),
+                    Err(Error::ProductLent),
+                    Err(Error::ProductLent),
+                    Err(Error::ProductLent),
+                    Err(Error::ProductLent),
+                    Err(Error::ProductLent),
+                    Err(Error::ProductLent),
+                    Err(Error::ProductLent),
+                    Err(Error::ProductLent),
+                    Err(Error::ProductLent),
+                    Err(Error::ProductLent),
+                    Err(Error::ProductLent),
+                    Ok(State::Owned),
+                    Err(Error::ProductLent),
+                    Err(Error::ProductLent),
+                ],
+                // PendingVerification
+                [
+                    Err(Error::VerificationPending),
+                    Err(Error::VerificationPending),
+                    Err(Error::VerificationPending),
+                    Err(Error::VerificationPending),
+                    Err(Error::VerificationPending),
+                    Err(Error::VerificationPending),
+                    Err(Error::VerificationPending),
+                    Err(Error::VerificationPending),
+                    Err(Error::VerificationPending),
+                    Err(Error::VerificationPending),
+                    Err(Error::VerificationPending),
+                    Err(Error::VerificationPending),
+                    Err(Error::VerificationPending),
+                    Err(Error::VerificationPending),
+                    Err(Error::VerificationPending),
+                    Err(Error::VerificationPending),
+                    Err(Error::VerificationPending),
+                    Err(Error::VerificationPending),
+                    Err(Error::VerificationPending),
+                    Err(Error::VerificationPending),
+                    Err(Error::VerificationPending),
+                    Err(Error::VerificationPending),
+                    Err(Error::VerificationPending),
+                    Ok(State::Owned),
+                ],
+            ];
+            for (state, row) in states.iter().zip(expected.iter()) {
+                for (action, result) in actions.iter().zip(row.iter()) {
+                    assert_eq!(&state.transition(*action), result, "{:?} -> {:?}", state, action);
+                }
+            }
+        }
+
+        #[ink::test]
+        fn state_encoding_test() {
+            assert_eq!(scale::Encode::encode(&State::Owned), vec![0u8]);
+            assert_eq!(scale::Encode::encode(&State::PendingDelegation), vec![1u8]);
+            assert_eq!(scale::Encode::encode(&State::Recalled), vec![2u8]);
+            assert_eq!(scale::Encode::encode(&State::ComponentLocked), vec![3u8]);
+            assert_eq!(scale::Encode::encode(&State::Disputed), vec![4u8]);
+            assert_eq!(scale::Encode::encode(&State::Listed), vec![5u8]);
+            assert_eq!(scale::Encode::encode(&State::Auctioned), vec![6u8]);
+            assert_eq!(scale::Encode::encode(&State::Lent), vec![7u8]);
+            assert_eq!(scale::Encode::encode(&State::PendingVerification), vec![8u8]);
+            assert_eq!(
+                <State as scale::Decode>::decode(&mut &[1u8][..]),
+                Ok(State::PendingDelegation)
+            );
+        }
+
+        #[ink::test]
+        fn get_last_empty_test() {
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.get_last(), None);
+            product_factory.create_product(1, None, None).unwrap();
+            assert_eq!(product_factory.get_last().map(|p| p.code), Some(1));
+        }
+
+        #[ink::test]
+        fn get_last_after_burn_test() {
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.create_products(vec![1, 2, 3]).unwrap();
+            assert_eq!(product_factory.burn_product(2), Ok(()));
+            assert_eq!(product_factory.get_last().map(|p| p.code), Some(2));
+            assert_eq!(product_factory.burn_product(0), Ok(()));
+            assert_eq!(product_factory.get_last().map(|p| p.code), Some(2));
+            assert_eq!(product_factory.burn_product(1), Ok(()));
+            assert_eq!(product_factory.get_last(), None);
+        }
+
+        #[ink::test]
+        fn get_product_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.create_product(1, None, None).unwrap();
+            product_factory.create_product(2, None, None).unwrap();
+
+            let product = product_factory.get_product(1).expect("Product must exist");
+            assert_eq!(product.code, 2);
+            assert_eq!(product.owner, accounts.alice);
+            assert_eq!(product.state, State::Owned);
+
+            assert_eq!(product_factory.get_product(2), Err(Error::PidNotExists));
+            assert_eq!(product_factory.get_product(u32::MAX), Err(Error::PidNotExists));
+        }
+
+        #[ink::test]
+        fn event_topics_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.create_product(7, None, None).unwrap();
+            product_factory.create_product(8, None, None).unwrap();
+            assert_eq!(product_factory.delegate_product(1, accounts.bob, None, None, None), Ok(()));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.accept_product(1), Ok(()));
+
+            // A field topic is the hash of the value prefixed with
+            // `<contract>::<event>::<field>`.
+            fn topic<T: scale::Encode>(prefix: &[u8], value: &T) -> Hash {
+                let mut output = <ink_env::hash::Blake2x256 as ink_env::hash::HashOutput>::Type::default();
+                ink_env::hash_encoded::<ink_env::hash::Blake2x256, _>(
+                    &ink_env::topics::PrefixedValue { prefix, value },
+                    &mut output,
+                );
+                Hash::from(output)
+            }
+            let topics = |index: usize| -> Vec<Hash> {
+                ink_env::test::recorded_events().collect::<Vec<_>>()[index]
+                    .topics
+                    .iter()
+                    .map(|topic| topic.decode::<Hash>().expect("Cannot decode topic"))
+                    .collect()
+            };
+            let created = topics(1);
+            assert_eq!(created.len(), 4);
+            assert_eq!(created[0], topics(0)[0]);
+            assert_eq!(
+                created[1..],
+                [
+                    topic(b"ProductFactory::ProductCreated::pid", &1u32),
+                    topic(b"ProductFactory::ProductCreated::owner", &accounts.alice),
+                    topic(b"ProductFactory::ProductCreated::id", &product_factory.id_of(1).unwrap()),
+                ]
+            );
+            // Each state change is recorded right before the event of its action.
+            let state_changed = topics(2);
+            assert_eq!(
+                state_changed[1..],
+                [
+                    topic(b"ProductFactory::StateChanged::pid", &1u32),
+                    topic(b"ProductFactory::StateChanged::actor", &accounts.alice),
+                ]
+            );
+            let delegated = topics(3);
+            assert_eq!(
+                delegated[1..],
+                [
+                    topic(b"ProductFactory::ProductDelegated::pid", &1u32),
+                    topic(b"ProductFactory::ProductDelegated::from", &accounts.alice),
+                    topic(b"ProductFactory::ProductDelegated::to", &accounts.bob),
+                ]
+            );
+            let accepted = topics(5);
+            assert_eq!(
+                accepted[1..],
+                [
+                    topic(b"ProductFactory::ProductAccepted::pid", &1u32),
+                    topic(b"ProductFactory::ProductAccepted::previous_owner", &accounts.alice),
+                    topic(b"ProductFactory::ProductAccepted::new_owner", &accounts.bob),
+                ]
+            );
+            assert_ne!(accepted[0], delegated[0]);
+        }
+
+        #[ink::test]
+        fn product_created_event_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.create_product(7, None, None).unwrap();
+            product_factory.create_product(8, None, None).unwrap();
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(emitted_events.len(), 2);
+            // The event signature plus the indexed pid, owner and id.
+            assert_eq!(emitted_events[1].topics.len(), 4);
+
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events[1].data[..])
+                .expect("Cannot decode event");
+            if let Event::ProductCreated(ProductCreated { pid, code, owner, .. }) = decoded {
+                assert_eq!(pid, 1);
+                assert_eq!(code, 8);
+                assert_eq!(owner, accounts.alice);
+            } else {
+                panic!("Expected a ProductCreated event")
+            }
+        }
+
+        #[ink::test]
+        fn delegate_product_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.product_count(), 0);
+            product_factory.create_product(1, None, None).unwrap();
+
+            assert_eq!(product_factory.get_last().unwrap().owner, accounts.alice);
+            assert_eq!(product_factory.get_last().unwrap().state, State::Owned);
+
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, None, None, None), Ok(()));
+            assert_eq!(product_factory.get_last().unwrap().owner, accounts.alice);
+            assert_eq!(product_factory.get_last().unwrap().state, State::PendingDelegation);
+            assert_eq!(product_factory.get_last().unwrap().get_delegate(), Some(accounts.bob));
+
+            assert_eq!(
+                product_factory.delegate_product(1, accounts.bob, None, None, None), 
+                Err(Error::PidNotExists)
+            );
+
+            assert_eq!(
+                product_factory.delegate_product(0, accounts.bob, None, None, None), 
+                Err(Error::InvalidState)
+            );
+
+            set_sender(accounts.bob);
+            assert_eq!(
+                product_factory.delegate_product(0, accounts.bob, None, None, None), 
+                Err(Error::InvalidOwner)
+            );
+
+
+        }
+
+        #[ink::test]
+        fn product_delegated_event_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.create_product(1, None, None).unwrap();
+            assert_eq!(ink_env::test::recorded_events().count(), 1);
+
+            assert_eq!(
+                product_factory.delegate_product(1, accounts.bob, None, None, None),
+                Err(Error::PidNotExists)
+            );
+            assert_eq!(ink_env::test::recorded_events().count(), 1);
+
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, None, None, None), Ok(()));
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            // The StateChanged event comes first, then ProductDelegated.
+            assert_eq!(emitted_events.len(), 3);
+            // The event signature plus the indexed pid, from and to accounts.
+            assert_eq!(emitted_events[2].topics.len(), 4);
+
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events[2].data[..])
+                .expect("Cannot decode event");
+            if let Event::ProductDelegated(ProductDelegated { pid, from, to, .. }) = decoded {
+                assert_eq!(pid, 0);
+                assert_eq!(from, accounts.alice);
+                assert_eq!(to, accounts.bob);
+            } else {
+                panic!("Expected a ProductDelegated event")
+            }
+
+            assert_eq!(
+                product_factory.delegate_product(0, accounts.bob, None, None, None),
+                Err(Error::InvalidState)
+            );
+            assert_eq!(ink_env::test::recorded_events().count(), 3);
+
+            set_sender(accounts.bob);
+            assert_eq!(
+                product_factory.delegate_product(0, accounts.charlie, None, None, None),
+                Err(Error::InvalidOwner)
+            );
+            assert_eq!(ink_env::test::recorded_events().count(), 3);
+        }
+
+        #[ink::test]
+        fn self_delegation_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.create_product(1, None, None).unwrap();
+
+            assert_eq!(
+                product_factory.delegate_product(0, accounts.alice, None, None, None),
+                Err(Error::SelfDelegation)
+            );
+            assert_eq!(product_factory.get_last().unwrap().state, State::Owned);
+            assert_eq!(product_factory.get_last().unwrap().get_delegate(), None);
+            assert_eq!(ink_env::test::recorded_events().count(), 1);
+        }
+
+        #[ink::test]
+        fn zero_address_delegation_test() {
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.create_product(1, None, None).unwrap();
+
+            assert_eq!(
+                product_factory.delegate_product(0, AccountId::from([0x0; 32]), None, None, None),
+                Err(Error::ZeroAddress)
+            );
+            assert_eq!(product_factory.get_last().unwrap().state, State::Owned);
+            assert_eq!(product_factory.get_last().unwrap().get_delegate(), None);
+        }
+
+        #[ink::test]
+        fn delegate_products_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.create_products(vec![1, 2, 3, 4]), Ok(vec![0, 1, 2, 3]));
+
+            assert_eq!(product_factory.delegate_products(vec![0, 2], accounts.bob), Ok(()));
+            for pid in [0, 2].iter() {
+                let product = product_factory.get_product(*pid).unwrap();
+                assert_eq!(product.state, State::PendingDelegation);
+                assert_eq!(product.delegate_to, Some(accounts.bob));
+            }
+            assert_eq!(ink_env::test::recorded_events().count(), 8);
+        }
+
+        #[ink::test]
+        fn delegate_products_rollback_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.create_products(vec![1, 2, 3]), Ok(vec![0, 1, 2]));
+
+            assert_eq!(
+                product_factory.delegate_products(vec![0, 7, 2], accounts.bob),
+                Err(Error::BatchItemFailed { pid: 7, error: Box::new(Error::PidNotExists) })
+            );
+            assert_eq!(
+                product_factory.delegate_products(vec![0, 1, 0], accounts.bob),
+                Err(Error::BatchItemFailed { pid: 0, error: Box::new(Error::InvalidState) })
+            );
+            assert_eq!(
+                product_factory.delegate_products(vec![0; MAX_BATCH_SIZE + 1], accounts.bob),
+                Err(Error::BatchTooLarge)
+            );
+            for pid in 0..3 {
+                let product = product_factory.get_product(pid).unwrap();
+                assert_eq!(product.state, State::Owned);
+                assert_eq!(product.delegate_to, None);
+            }
+            assert_eq!(ink_env::test::recorded_events().count(), 3);
+
+            set_sender(accounts.bob);
+            assert_eq!(
+                product_factory.delegate_products(vec![1], accounts.charlie),
+                Err(Error::BatchItemFailed { pid: 1, error: Box::new(Error::InvalidOwner) })
+            );
+        }
+
+        #[ink::test]
+        fn accept_product_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.product_count(), 0);
+            product_factory.create_product(1, None, None).unwrap();
+
+            assert_eq!(product_factory.get_last().unwrap().owner, accounts.alice);
+            assert_eq!(product_factory.get_last().unwrap().state, State::Owned);
+
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, None, None, None), Ok(()));
+            assert_eq!(product_factory.get_last().unwrap().owner, accounts.alice);
+            assert_eq!(product_factory.get_last().unwrap().state, State::PendingDelegation);
+            assert_eq!(product_factory.get_last().unwrap().get_delegate(), Some(accounts.bob));
+            
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.accept_product(0), Ok(()));
+
+            assert_eq!(product_factory.get_last().unwrap().owner, accounts.bob);
+            assert_eq!(product_factory.get_last().unwrap().state, State::Owned);
+            assert_eq!(product_factory.get_last().unwrap().get_delegate(), None);
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(emitted_events.len(), 5);
+            // The event signature plus the indexed pid, previous and new owners.
+            assert_eq!(emitted_events[4].topics.len(), 4);
+
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events[4].data[..])
+                .expect("Cannot decode event");
+            if let Event::ProductAccepted(ProductAccepted { pid, previous_owner, new_owner, id }) = decoded {
+                assert_eq!(pid, 0);
+                assert_eq!(previous_owner, accounts.alice);
+                assert_eq!(new_owner, accounts.bob);
+                assert_eq!(product_factory.pid_of(id), Some(0));
+            } else {
+                panic!("Expected a ProductAccepted event")
+            }
+
+
+            assert_eq!(
+                product_factory.accept_product(0),
+                Err(Error::InvalidDelegate));
+
+        }
+
+        #[ink::test]
+        fn reject_delegation_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.create_product(1, None, None).unwrap();
+
+            assert_eq!(
+                product_factory.reject_delegation(1),
+                Err(Error::PidNotExists)
+            );
+            assert_eq!(
+                product_factory.reject_delegation(0),
+                Err(Error::InvalidDelegate)
+            );
+
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, None, None, None), Ok(()));
+
+            set_sender(accounts.charlie);
+            assert_eq!(
+                product_factory.reject_delegation(0),
+                Err(Error::InvalidDelegate)
+            );
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.reject_delegation(0), Ok(()));
+            assert_eq!(product_factory.get_last().unwrap().owner, accounts.alice);
+            assert_eq!(product_factory.get_last().unwrap().state, State::Owned);
+            assert_eq!(product_factory.get_last().unwrap().get_delegate(), None);
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(emitted_events.len(), 5);
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events[4].data[..])
+                .expect("Cannot decode event");
+            if let Event::DelegationRejected(DelegationRejected { pid, owner, delegate }) = decoded {
+                assert_eq!(pid, 0);
+                assert_eq!(owner, accounts.alice);
+                assert_eq!(delegate, accounts.bob);
+            } else {
+                panic!("Expected a DelegationRejected event")
+            }
+
+            assert_eq!(
+                product_factory.reject_delegation(0),
+                Err(Error::InvalidDelegate)
+            );
+            assert_eq!(
+                product_factory.accept_product(0),
+                Err(Error::InvalidDelegate)
+            );
+        }
+
+        #[ink::test]
+        fn revoke_delegation_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.create_product(1, None, None).unwrap();
+
+            assert_eq!(
+                product_factory.revoke_delegation(1),
+                Err(Error::PidNotExists)
+            );
+            assert_eq!(
+                product_factory.revoke_delegation(0),
+                Err(Error::InvalidState)
+            );
+
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, None, None, None), Ok(()));
+
+            set_sender(accounts.bob);
+            assert_eq!(
+                product_factory.revoke_delegation(0),
+                Err(Error::InvalidOwner)
+            );
+
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.revoke_delegation(0), Ok(()));
+            assert_eq!(product_factory.get_last().unwrap().owner, accounts.alice);
+            assert_eq!(product_factory.get_last().unwrap().state, State::Owned);
+            assert_eq!(product_factory.get_last().unwrap().get_delegate(), None);
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(emitted_events.len(), 5);
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events[4].data[..])
+                .expect("Cannot decode event");
+            if let Event::DelegationRevoked(DelegationRevoked { pid, owner, delegate }) = decoded {
+                assert_eq!(pid, 0);
+                assert_eq!(owner, accounts.alice);
+                assert_eq!(delegate, accounts.bob);
+            } else {
+                panic!("Expected a DelegationRevoked event")
+            }
+
+            set_sender(accounts.bob);
+            assert_eq!(
+                product_factory.accept_product(0),
+                Err(Error::InvalidDelegate)
+            );
+        }
+
+        #[ink::test]
+        fn products_of_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.products_of(accounts.alice, false), Vec::<u32>::new());
+            product_factory.create_product(1, None, None).unwrap();
+            product_factory.create_product(2, None, None).unwrap();
+            product_factory.create_product(3, None, None).unwrap();
+            assert_eq!(product_factory.products_of(accounts.alice, false), vec![0, 1, 2]);
+            assert_eq!(product_factory.products_of(accounts.bob, false), Vec::<u32>::new());
+
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, None, None, None), Ok(()));
+            // A pending delegation does not move the product yet.
+            assert_eq!(product_factory.products_of(accounts.alice, false), vec![0, 1, 2]);
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.accept_product(0), Ok(()));
+            assert_eq!(product_factory.products_of(accounts.alice, false), vec![2, 1]);
+            assert_eq!(product_factory.products_of(accounts.bob, false), vec![0]);
+        }
+
+        #[ink::test]
+        fn accept_products_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.create_products(vec![1, 2, 3]), Ok(vec![0, 1, 2]));
+            assert_eq!(product_factory.delegate_products(vec![0, 2], accounts.bob), Ok(()));
+            assert_eq!(product_factory.delegate_product(1, accounts.charlie, None, None, None), Ok(()));
+
+            set_sender(accounts.bob);
+            assert_eq!(
+                product_factory.accept_products(vec![0, 1, 2]),
+                Err(Error::BatchItemFailed { pid: 1, error: Box::new(Error::InvalidDelegate) })
+            );
+            assert_eq!(
+                product_factory.accept_products(vec![0, 2, 0]),
+                Err(Error::BatchItemFailed { pid: 0, error: Box::new(Error::InvalidDelegate) })
+            );
+            for pid in 0..3 {
+                let product = product_factory.get_product(pid).unwrap();
+                assert_eq!(product.owner, accounts.alice);
+                assert_eq!(product.state, State::PendingDelegation);
+            }
+
+            assert_eq!(product_factory.accept_products(vec![2, 0]), Ok(()));
+            assert_eq!(product_factory.get_product(0).unwrap().owner, accounts.bob);
+            assert_eq!(product_factory.get_product(1).unwrap().owner, accounts.alice);
+            assert_eq!(product_factory.get_product(2).unwrap().owner, accounts.bob);
+            assert_eq!(product_factory.products_of(accounts.bob, false), vec![2, 0]);
+        }
+
+        #[ink::test]
+        fn transfer_product_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.create_product(1, None, None).unwrap();
+            product_factory.create_product(2, None, None).unwrap();
+
+            assert_eq!(
+                product_factory.transfer_product(2, accounts.bob),
+                Err(Error::PidNotExists)
+            );
+            assert_eq!(
+                product_factory.transfer_product(0, accounts.alice),
+                Err(Error::SelfTransfer)
+            );
+            assert_eq!(
+                product_factory.transfer_product(0, AccountId::from([0x0; 32])),
+                Err(Error::ZeroAddress)
+            );
+            assert_eq!(product_factory.delegate_product(1, accounts.charlie, None, None, None), Ok(()));
+            assert_eq!(
+                product_factory.transfer_product(1, accounts.bob),
+                Err(Error::InvalidState)
+            );
+
+            assert_eq!(product_factory.transfer_product(0, accounts.bob), Ok(()));
+            let product = product_factory.get_product(0).unwrap();
+            assert_eq!(product.owner, accounts.bob);
+            assert_eq!(product.state, State::Owned);
+            assert_eq!(product.delegate_to, None);
+            assert_eq!(product_factory.products_of(accounts.alice, false), vec![1]);
+            assert_eq!(product_factory.products_of(accounts.bob, false), vec![0]);
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(emitted_events.len(), 6);
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events[4].data[..])
+                .expect("Cannot decode event");
+            if let Event::ProductTransferred(ProductTransferred { pid, from, to, id }) = decoded {
+                assert_eq!(pid, 0);
+                assert_eq!(from, accounts.alice);
+                assert_eq!(to, accounts.bob);
+                assert_eq!(product_factory.pid_of(id), Some(0));
+            } else {
+                panic!("Expected a ProductTransferred event")
+            }
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events[5].data[..])
+                .expect("Cannot decode event");
+            if let Event::Transfer(Transfer { from, to, id }) = decoded {
+                assert_eq!((from, to, id), (Some(accounts.alice), Some(accounts.bob), Id::U32(0)));
+            } else {
+                panic!("Expected a Transfer event")
+            }
+
+            assert_eq!(
+                product_factory.transfer_product(0, accounts.charlie),
+                Err(Error::InvalidOwner)
+            );
+        }
+
+        #[ink::test]
+        fn delegation_deadline_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.create_product(1, None, None).unwrap();
+            product_factory.create_product(2, None, None).unwrap();
+            let deadline = ink_env::block_timestamp::<Environment>().unwrap() + 10;
+
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, Some(deadline), None, None), Ok(()));
+            assert_eq!(product_factory.delegate_product(1, accounts.bob, Some(deadline), None, None), Ok(()));
+            assert_eq!(
+                product_factory.get_product(0).unwrap().delegation_deadline,
+                Some(deadline)
+            );
+
+            set_sender(accounts.bob);
+            // Accepting right at the deadline is still allowed.
+            advance_block_by(2);
+            assert_eq!(ink_env::block_timestamp::<Environment>().unwrap(), deadline);
+            assert_eq!(product_factory.accept_product(0), Ok(()));
+            assert_eq!(product_factory.get_product(0).unwrap().delegation_deadline, None);
+
+            advance_block_by(1);
+            assert_eq!(product_factory.accept_product(1), Err(Error::DelegationExpired));
+            let product = product_factory.get_product(1).unwrap();
+            assert_eq!(product.owner, accounts.alice);
+            assert_eq!(product.state, State::PendingDelegation);
+
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.revoke_delegation(1), Ok(()));
+            assert_eq!(product_factory.get_product(1).unwrap().delegation_deadline, None);
+        }
+
+        #[ink::test]
+        fn expire_delegation_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.create_product(1, None, None).unwrap();
+            product_factory.create_product(2, None, None).unwrap();
+
+            assert_eq!(product_factory.expire_delegation(2), Err(Error::PidNotExists));
+            assert_eq!(product_factory.expire_delegation(0), Err(Error::InvalidState));
+
+            let deadline = ink_env::block_timestamp::<Environment>().unwrap() + 5;
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, Some(deadline), None, None), Ok(()));
+            assert_eq!(product_factory.delegate_product(1, accounts.bob, None, None, None), Ok(()));
+
+            set_sender(accounts.charlie);
+            assert_eq!(product_factory.expire_delegation(0), Err(Error::NotExpired));
+            advance_block_by(1);
+            assert_eq!(product_factory.expire_delegation(0), Err(Error::NotExpired));
+            advance_block_by(1);
+            assert_eq!(product_factory.expire_delegation(1), Err(Error::NotExpired));
+            assert_eq!(product_factory.expire_delegation(0), Ok(()));
+
+            let product = product_factory.get_product(0).unwrap();
+            assert_eq!(product.owner, accounts.alice);
+            assert_eq!(product.state, State::Owned);
+            assert_eq!(product.delegate_to, None);
+            assert_eq!(product.delegation_deadline, None);
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events[7].data[..])
+                .expect("Cannot decode event");
+            if let Event::DelegationExpired(DelegationExpired { pid, owner, delegate }) = decoded {
+                assert_eq!(pid, 0);
+                assert_eq!(owner, accounts.alice);
+                assert_eq!(delegate, accounts.bob);
+            } else {
+                panic!("Expected a DelegationExpired event")
+            }
+
+            assert_eq!(product_factory.expire_delegation(0), Err(Error::InvalidState));
+        }
+
+        #[ink::test]
+        fn burn_product_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.add_manufacturer(accounts.bob).unwrap();
+            product_factory.create_product(1, None, None).unwrap();
+            product_factory.create_product(2, None, None).unwrap();
+
+            assert_eq!(product_factory.burn_product(2), Err(Error::PidNotExists));
+            assert_eq!(product_factory.delegate_product(1, accounts.bob, None, None, None), Ok(()));
+            assert_eq!(product_factory.burn_product(1), Err(Error::InvalidState));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.burn_product(0), Err(Error::InvalidOwner));
+
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.burn_product(0), Ok(()));
+            assert_eq!(product_factory.products_of(accounts.alice, false), vec![1]);
+            assert_eq!(product_factory.product_count(), 2);
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(emitted_events.len(), 6);
+            assert_eq!(emitted_events[5].topics.len(), 3);
+
+            assert_eq!(product_factory.get_product(0), Err(Error::PidNotExists));
+            assert_eq!(product_factory.burn_product(0), Err(Error::PidNotExists));
+            assert_eq!(
+                product_factory.delegate_product(0, accounts.bob, None, None, None),
+                Err(Error::PidNotExists)
+            );
+            assert_eq!(
+                product_factory.transfer_product(0, accounts.bob),
+                Err(Error::PidNotExists)
+            );
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.accept_product(0), Err(Error::PidNotExists));
+            // Burned pids are never handed out again.
+            assert_eq!(product_factory.create_product(3, None, None), Ok(2));
+        }
+
+        #[ink::test]
+        fn metadata_uri_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.create_product(1, None, None).unwrap();
+            let uri = b"ipfs://bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi".to_vec();
+
+            assert_eq!(product_factory.get_metadata_uri(0), Ok(None));
+            assert_eq!(product_factory.get_metadata_uri(1), Err(Error::PidNotExists));
+            assert_eq!(product_factory.set_metadata_uri(1, uri.clone()), Err(Error::PidNotExists));
+            assert_eq!(
+                product_factory.set_metadata_uri(0, vec![0; MAX_URI_LEN + 1]),
+                Err(Error::MetadataTooLong)
+            );
+
+            assert_eq!(product_factory.set_metadata_uri(0, uri.clone()), Ok(()));
+            assert_eq!(product_factory.get_metadata_uri(0), Ok(Some(uri.clone())));
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(emitted_events.len(), 2);
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events[1].data[..])
+                .expect("Cannot decode event");
+            if let Event::MetadataUpdated(MetadataUpdated { pid, uri: emitted_uri }) = decoded {
+                assert_eq!(pid, 0);
+                assert_eq!(emitted_uri, uri);
+            } else {
+                panic!("Expected a MetadataUpdated event")
+            }
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.set_metadata_uri(0, Vec::new()), Err(Error::InvalidOwner));
+
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, None, None, None), Ok(()));
+            assert_eq!(product_factory.set_metadata_uri(0, Vec::new()), Err(Error::InvalidState));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.set_metadata_uri(0, Vec::new()), Err(Error::InvalidOwner));
+            assert_eq!(product_factory.get_metadata_uri(0), Ok(Some(uri)));
+        }
+
+        #[ink::test]
+        fn freeze_metadata_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.create_product(1, None, None).unwrap();
+
+            assert_eq!(
+                product_factory.update_metadata(0, b"bike".to_vec(), b"red".to_vec()),
+                Ok(())
+            );
+            let product = product_factory.get_product(0).unwrap();
+            assert_eq!(product.get_name(), b"bike".to_vec());
+            assert_eq!(product.get_description(), b"red".to_vec());
+            assert_eq!(
+                product_factory.update_metadata(0, vec![0; MAX_NAME_LEN + 1], Vec::new()),
+                Err(Error::MetadataTooLong)
+            );
+
+            set_sender(accounts.bob);
+            assert_eq!(
+                product_factory.update_metadata(0, Vec::new(), Vec::new()),
+                Err(Error::InvalidOwner)
+            );
+            assert_eq!(product_factory.freeze_metadata(0), Err(Error::InvalidOwner));
+
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.freeze_metadata(0), Ok(()));
+            assert_eq!(
+                product_factory.update_metadata(0, b"car".to_vec(), Vec::new()),
+                Err(Error::MetadataFrozen)
+            );
+            assert_eq!(
+                product_factory.set_metadata_uri(0, b"ipfs://x".to_vec()),
+                Err(Error::MetadataFrozen)
+            );
+
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, None, None, None), Ok(()));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.accept_product(0), Ok(()));
+            assert_eq!(
+                product_factory.update_metadata(0, b"car".to_vec(), Vec::new()),
+                Err(Error::MetadataFrozen)
+            );
+            assert_eq!(product_factory.freeze_metadata(0), Ok(()));
+            assert_eq!(product_factory.get_product(0).unwrap().get_name(), b"bike".to_vec());
+        }
+
+        #[ink::test]
+        fn creation_time_test() {
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.create_product(1, None, None).unwrap();
+            advance_block_by(3);
+            product_factory.create_product(2, None, None).unwrap();
+
+            let first = product_factory.get_product(0).unwrap();
+            let second = product_factory.get_product(1).unwrap();
+            assert_eq!(first.get_created_block(), 0);
+            assert_eq!(second.get_created_block(), 3);
+            assert!(second.get_created_at() > first.get_created_at());
+            assert_eq!(
+                second.get_created_at(),
+                ink_env::block_timestamp::<Environment>().unwrap()
+            );
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events[1].data[..])
+                .expect("Cannot decode event");
+            if let Event::ProductCreated(ProductCreated { created_at, created_block, .. }) = decoded {
+                assert_eq!(created_at, second.get_created_at());
+                assert_eq!(created_block, 3);
+            } else {
+                panic!("Expected a ProductCreated event")
+            }
+        }
+
+        #[ink::test]
+        fn ownership_history_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.history_len(0), 0);
+            product_factory.create_product(1, None, None).unwrap();
+            let created_at = ink_env::block_timestamp::<Environment>().unwrap();
+
+            advance_block_by(1);
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, None, None, None), Ok(()));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.accept_product(0), Ok(()));
+            let accepted_at = ink_env::block_timestamp::<Environment>().unwrap();
+
+            advance_block_by(1);
+            assert_eq!(product_factory.transfer_product(0, accounts.charlie), Ok(()));
+            let transferred_at = ink_env::block_timestamp::<Environment>().unwrap();
+
+            assert_eq!(product_factory.history_len(0), 3);
+            assert_eq!(product_factory.history_entry(0, 0), Some((accounts.alice, created_at)));
+            assert_eq!(product_factory.history_entry(0, 1), Some((accounts.bob, accepted_at)));
+            assert_eq!(
+                product_factory.history_entry(0, 2),
+                Some((accounts.charlie, transferred_at))
+            );
+            assert_eq!(product_factory.history_entry(0, 3), None);
+            assert!(created_at < accepted_at && accepted_at < transferred_at);
+        }
+
+        #[ink::test]
+        fn get_history_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.get_history(0, 0), Err(Error::PidNotExists));
+            product_factory.create_product(1, None, None).unwrap();
+
+            // One creation plus enough transfers to overflow a single page.
+            let transfers = MAX_PAGE_SIZE + 20;
+            for i in 0..transfers {
+                let (from, to) = if i % 2 == 0 {
+                    (accounts.alice, accounts.bob)
+                } else {
+                    (accounts.bob, accounts.alice)
+                };
+                set_sender(from);
+                assert_eq!(product_factory.transfer_product(0, to), Ok(()));
+            }
+            assert_eq!(product_factory.history_len(0), transfers + 1);
+
+            let first_page = product_factory.get_history(0, 0).unwrap();
+            assert_eq!(first_page.len(), MAX_PAGE_SIZE as usize);
+            assert_eq!(first_page[0].0, accounts.alice);
+            assert_eq!(first_page[1].0, accounts.bob);
+
+            let second_page = product_factory.get_history(0, MAX_PAGE_SIZE).unwrap();
+            assert_eq!(second_page.len(), 21);
+            assert_eq!(second_page.last().unwrap().0, accounts.alice);
+            assert_eq!(product_factory.get_history(0, transfers + 1), Ok(Vec::new()));
+        }
+
+        #[ink::test]
+        fn admin_transfer_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            assert_eq!(product_factory.get_admin(), accounts.alice);
+            assert_eq!(product_factory.get_pending_admin(), None);
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.transfer_admin(accounts.bob), Err(Error::NotAdmin));
+            assert_eq!(product_factory.accept_admin(), Err(Error::NotAdmin));
+
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.transfer_admin(AccountId::from([0x0; 32])), Err(Error::ZeroAddress));
+            assert_eq!(product_factory.transfer_admin(accounts.bob), Ok(()));
+            assert_eq!(product_factory.get_pending_admin(), Some(accounts.bob));
+            assert_eq!(product_factory.get_admin(), accounts.alice);
+
+            set_sender(accounts.charlie);
+            assert_eq!(product_factory.accept_admin(), Err(Error::NotAdmin));
+            assert_eq!(product_factory.get_admin(), accounts.alice);
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.accept_admin(), Ok(()));
+            assert_eq!(product_factory.get_admin(), accounts.bob);
+            assert_eq!(product_factory.get_pending_admin(), None);
+            assert_eq!(product_factory.accept_admin(), Err(Error::NotAdmin));
+
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.transfer_admin(accounts.charlie), Err(Error::NotAdmin));
+        }
+
+        #[ink::test]
+        fn pause_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.create_product(1, None, None).unwrap();
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, None, None, None), Ok(()));
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.pause(), Err(Error::NotAdmin));
+
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.pause(), Ok(()));
+            assert!(product_factory.is_paused());
+            assert_eq!(product_factory.create_product(2, None, None), Err(Error::Paused));
+            assert_eq!(product_factory.revoke_delegation(0), Err(Error::Paused));
+            assert_eq!(product_factory.get_product(0).unwrap().state, State::PendingDelegation);
+            assert_eq!(product_factory.products_of(accounts.alice, false), vec![0]);
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.accept_product(0), Err(Error::Paused));
+            assert_eq!(product_factory.unpause(), Err(Error::NotAdmin));
+
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.unpause(), Ok(()));
+            assert!(!product_factory.is_paused());
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.accept_product(0), Ok(()));
+            assert_eq!(product_factory.get_product(0).unwrap().owner, accounts.bob);
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(emitted_events.len(), 9);
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events[3].data[..])
+                .expect("Cannot decode event");
+            if let Event::Paused(Paused { admin }) = decoded {
+                assert_eq!(admin, accounts.alice);
+            } else {
+                panic!("Expected a Paused event")
+            }
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events[5].data[..])
+                .expect("Cannot decode event");
+            if let Event::Unpaused(Unpaused { admin }) = decoded {
+                assert_eq!(admin, accounts.alice);
+            } else {
+                panic!("Expected an Unpaused event")
+            }
+        }
+
+        #[ink::test]
+        fn manufacturers_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            assert!(product_factory.is_manufacturer(accounts.alice));
+            assert!(!product_factory.is_manufacturer(accounts.bob));
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.create_product(1, None, None), Err(Error::NotManufacturer));
+            assert_eq!(
+                product_factory.create_product_with_metadata(1, Vec::new(), Vec::new()),
+                Err(Error::NotManufacturer)
+            );
+            assert_eq!(product_factory.create_products(vec![1]), Err(Error::NotManufacturer));
+            assert_eq!(product_factory.add_manufacturer(accounts.bob), Err(Error::NotAdmin));
+
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.add_manufacturer(AccountId::from([0x0; 32])), Err(Error::ZeroAddress));
+            assert_eq!(product_factory.add_manufacturer(accounts.bob), Ok(()));
+            assert!(product_factory.is_manufacturer(accounts.bob));
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.create_product(1, None, None), Ok(0));
+            assert_eq!(product_factory.remove_manufacturer(accounts.bob), Err(Error::NotAdmin));
+
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.remove_manufacturer(accounts.bob), Ok(()));
+            assert!(!product_factory.is_manufacturer(accounts.bob));
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.create_product(2, None, None), Err(Error::NotManufacturer));
+            assert_eq!(product_factory.products_of(accounts.bob, false), vec![0]);
+            assert_eq!(product_factory.transfer_product(0, accounts.charlie), Ok(()));
+        }
+
+        #[ink::test]
+        fn operator_approval_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.create_product(1, None, None).unwrap();
+            product_factory.create_product(2, None, None).unwrap();
+            assert_eq!(product_factory.get_approved(0), None);
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.approve(0, accounts.bob), Err(Error::InvalidOwner));
+            assert_eq!(product_factory.delegate_product_from(0, accounts.alice, accounts.charlie), Err(Error::NotApproved));
+
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.approve(9, accounts.bob), Err(Error::PidNotExists));
+            assert_eq!(product_factory.approve(0, accounts.bob), Ok(()));
+            assert_eq!(product_factory.approve(1, accounts.bob), Ok(()));
+            assert_eq!(product_factory.get_approved(0), Some(accounts.bob));
+
+            set_sender(accounts.charlie);
+            assert_eq!(product_factory.delegate_product_from(0, accounts.alice, accounts.charlie), Err(Error::NotApproved));
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.delegate_product_from(0, accounts.bob, accounts.charlie), Err(Error::InvalidOwner));
+            assert_eq!(product_factory.delegate_product_from(0, accounts.alice, accounts.charlie), Ok(()));
+            assert_eq!(product_factory.get_product(0).unwrap().delegate_to, Some(accounts.charlie));
+            assert_eq!(product_factory.delegate_product_from(0, accounts.alice, accounts.charlie), Err(Error::InvalidState));
+
+            // Ownership changes clear the approval.
+            set_sender(accounts.charlie);
+            assert_eq!(product_factory.accept_product(0), Ok(()));
+            assert_eq!(product_factory.get_approved(0), None);
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.transfer_product(1, accounts.django), Ok(()));
+            assert_eq!(product_factory.get_approved(1), None);
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.delegate_product_from(1, accounts.django, accounts.eve), Err(Error::NotApproved));
+
+            set_sender(accounts.django);
+            assert_eq!(product_factory.approve(1, accounts.bob), Ok(()));
+            assert_eq!(product_factory.approve(1, AccountId::from([0x0; 32])), Ok(()));
+            assert_eq!(product_factory.get_approved(1), None);
+
+            // the approved operator transfers just as through PSP34 `transfer`
+            assert_eq!(product_factory.approve(1, accounts.bob), Ok(()));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.transfer_product(1, accounts.eve), Ok(()));
+            assert_eq!(product_factory.get_product(1).unwrap().owner, accounts.eve);
+            assert_eq!(product_factory.transfer_product(1, accounts.bob), Err(Error::InvalidOwner));
+
+            let approvals = ink_env::test::recorded_events()
+                .filter_map(|ev| match <Event as scale::Decode>::decode(&mut &ev.data[..]) {
+                    Ok(Event::Approval(Approval { owner, operator, id, approved })) => Some((owner, operator, id, approved)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(
+                approvals,
+                vec![
+                    (accounts.alice, accounts.bob, Some(Id::U32(0)), true),
+                    (accounts.alice, accounts.bob, Some(Id::U32(1)), true),
+                    (accounts.django, accounts.bob, Some(Id::U32(1)), true),
+                    (accounts.django, accounts.bob, Some(Id::U32(1)), false),
+                    (accounts.django, accounts.bob, Some(Id::U32(1)), true),
+                ]
+            );
+        }
+
+        #[ink::test]
+        fn approval_for_all_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.add_manufacturer(accounts.django).unwrap();
+            product_factory.create_product(1, None, None).unwrap();
+            product_factory.create_product(2, None, None).unwrap();
+            product_factory.create_product(3, None, None).unwrap();
+            set_sender(accounts.django);
+            product_factory.create_product(4, None, None).unwrap();
+
+            set_sender(accounts.alice);
+            assert!(!product_factory.is_approved_for_all(accounts.alice, accounts.bob));
+            assert_eq!(product_factory.set_approval_for_all(accounts.bob, true), Ok(()));
+            assert!(product_factory.is_approved_for_all(accounts.alice, accounts.bob));
+            assert!(!product_factory.is_approved_for_all(accounts.bob, accounts.alice));
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.delegate_product(0, accounts.charlie, None, None, None), Ok(()));
+            assert_eq!(product_factory.get_product(0).unwrap().delegate_to, Some(accounts.charlie));
+            assert_eq!(product_factory.delegate_product_from(1, accounts.alice, accounts.charlie), Ok(()));
+            assert_eq!(product_factory.transfer_product(2, accounts.eve), Ok(()));
+            assert_eq!(product_factory.get_product(2).unwrap().owner, accounts.eve);
+            assert_eq!(product_factory.delegate_product(3, accounts.charlie, None, None, None), Err(Error::InvalidOwner));
+            assert_eq!(product_factory.transfer_product(3, accounts.charlie), Err(Error::InvalidOwner));
+
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.revoke_delegation(1), Ok(()));
+            assert_eq!(product_factory.revoke_delegation(0), Ok(()));
+
+            // batches resolve the owner of every pid the same way
+            set_sender(accounts.bob);
+            assert_eq!(
+                product_factory.delegate_products(vec![0, 3], accounts.charlie),
+                Err(Error::batch_item_failed(3, Error::InvalidOwner))
+            );
+            assert_eq!(product_factory.get_product(0).unwrap().delegate_to, None);
+            assert_eq!(product_factory.delegate_products(vec![0, 1], accounts.charlie), Ok(()));
+            assert_eq!(product_factory.get_product(1).unwrap().delegate_to, Some(accounts.charlie));
+
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.revoke_delegation(1), Ok(()));
+            assert_eq!(product_factory.set_approval_for_all(accounts.bob, false), Ok(()));
+            assert!(!product_factory.is_approved_for_all(accounts.alice, accounts.bob));
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.delegate_product(1, accounts.charlie, None, None, None), Err(Error::InvalidOwner));
+            assert_eq!(product_factory.transfer_product(1, accounts.charlie), Err(Error::InvalidOwner));
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events[5].data[..])
+                .expect("Cannot decode event");
+            if let Event::ApprovalForAll(ApprovalForAll { owner, operator, approved }) = decoded {
+                assert_eq!(owner, accounts.alice);
+                assert_eq!(operator, accounts.bob);
+                assert!(approved);
+            } else {
+                panic!("Expected an ApprovalForAll event")
+            }
+        }
+
+        #[ink::test]
+        fn creation_fee_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let contract = ink_env::account_id::<Environment>().expect("Cannot get contract id");
+            test::set_account_balance::<Environment>(contract, 0)
+                .expect("Cannot set contract balance");
+            let mut product_factory = ProductFactory::new(100, 0);
+            assert_eq!(product_factory.get_creation_fee(), 100);
+
+            // a failed creation hands the fee back
+            test::set_account_balance::<Environment>(accounts.alice, 0)
+                .expect("Cannot set creator balance");
+            set_sender_paying(accounts.alice, 99);
+            assert_eq!(product_factory.create_product(1, None, None), Err(Error::InsufficientFee));
+            set_sender_paying(accounts.alice, 99);
+            assert_eq!(
+                product_factory.create_product_with_metadata(1, Vec::new(), Vec::new()),
+                Err(Error::InsufficientFee)
+            );
+            assert_eq!(test::get_account_balance::<Environment>(accounts.alice), Ok(198));
+            assert_eq!(test::get_account_balance::<Environment>(contract), Ok(0));
+            set_sender_with_value(accounts.alice, 100);
+            assert_eq!(product_factory.create_product(1, None, None), Ok(0));
+            set_sender_paying(accounts.alice, 100);
+            assert_eq!(product_factory.create_products(vec![2, 3]), Err(Error::InsufficientFee));
+            set_sender_with_value(accounts.alice, 200);
+            assert_eq!(product_factory.create_products(vec![2, 3]), Ok(vec![1, 2]));
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.set_creation_fee(0), Err(Error::NotAdmin));
+            set_sender_with_value(accounts.alice, 0);
+            assert_eq!(product_factory.create_product(4, None, None), Err(Error::InsufficientFee));
+            assert_eq!(product_factory.set_creation_fee(0), Ok(()));
+            assert_eq!(product_factory.get_creation_fee(), 0);
+            assert_eq!(product_factory.create_product(4, None, None), Ok(3));
+        }
+
+        #[ink::test]
+        fn withdraw_fees_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(100, 0);
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get contract id");
+            test::set_account_balance::<Environment>(contract, 1000)
+                .expect("Cannot set contract balance");
+            test::set_account_balance::<Environment>(accounts.eve, 0)
+                .expect("Cannot set recipient balance");
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.withdraw_fees(accounts.bob, 1), Err(Error::NotAdmin));
+
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.withdraw_fees(accounts.eve, 1001), Err(Error::TransferFailed));
+            assert_eq!(product_factory.withdraw_fees(accounts.eve, 300), Ok(()));
+            assert_eq!(test::get_account_balance::<Environment>(accounts.eve), Ok(300));
+            assert_eq!(test::get_account_balance::<Environment>(contract), Ok(700));
+            assert_eq!(product_factory.withdraw_fees(accounts.eve, 0), Ok(()));
+            assert_eq!(test::get_account_balance::<Environment>(accounts.eve), Ok(1000));
+            assert_eq!(test::get_account_balance::<Environment>(contract), Ok(0));
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(emitted_events.len(), 4);
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events[2].data[..])
+                .expect("Cannot decode event");
+            if let Event::FeesWithdrawn(FeesWithdrawn { to, amount }) = decoded {
+                assert_eq!(to, accounts.eve);
+                assert_eq!(amount, 700);
+            } else {
+                panic!("Expected a FeesWithdrawn event")
+            }
+        }
+
+        #[ink::test]
+        fn creation_refund_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(100, 0);
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get contract id");
+            test::set_account_balance::<Environment>(accounts.alice, 0)
+                .expect("Cannot set caller balance");
+
+            // The off-chain environment does not credit the contract with
+            // the transferred value, so the balance below stands in for it.
+            set_sender_with_value(accounts.alice, 100);
+            assert_eq!(product_factory.create_product(1, None, None), Ok(0));
+            assert_eq!(test::get_account_balance::<Environment>(accounts.alice), Ok(0));
+
+            test::set_account_balance::<Environment>(contract, 200)
+                .expect("Cannot set contract balance");
+            set_sender_with_value(accounts.alice, 200);
+            assert_eq!(product_factory.create_product(2, None, None), Ok(1));
+            assert_eq!(test::get_account_balance::<Environment>(accounts.alice), Ok(100));
+            assert_eq!(test::get_account_balance::<Environment>(contract), Ok(100));
+
+            // a failed creation hands the whole fee back
+            set_sender_paying(accounts.alice, 200);
+            assert_eq!(product_factory.create_products(vec![3, 1]), Err(Error::DuplicateCode));
+            assert_eq!(product_factory.product_count(), 2);
+            set_sender_paying(accounts.alice, 100);
+            assert_eq!(product_factory.create_lot(2, 1), Err(Error::DuplicateCode));
+            set_sender_paying(accounts.alice, 100);
+            assert_eq!(
+                product_factory.create_product_with_metadata(4, vec![0; MAX_NAME_LEN + 1], Vec::new()),
+                Err(Error::MetadataTooLong)
+            );
+            set_sender_paying(accounts.alice, 100);
+            assert_eq!(product_factory.create_product(5, Some(0), None), Err(Error::InvalidQuantity));
+            assert_eq!(product_factory.get_products_by_code(3), Vec::<u32>::new());
+            assert_eq!(test::get_account_balance::<Environment>(accounts.alice), Ok(600));
+            assert_eq!(test::get_account_balance::<Environment>(contract), Ok(100));
+        }
+
+        #[ink::test]
+        fn psp34_balance_and_owner_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(PSP34::total_supply(&product_factory), 0);
+            assert_eq!(PSP34::balance_of(&product_factory, accounts.alice), 0);
+            product_factory.create_products(vec![1, 2, 3]).unwrap();
+
+            assert_eq!(PSP34::total_supply(&product_factory), 3);
+            assert_eq!(PSP34::balance_of(&product_factory, accounts.alice), 3);
+            assert_eq!(PSP34::balance_of(&product_factory, accounts.bob), 0);
+            assert_eq!(PSP34::owner_of(&product_factory, Id::U32(1)), Some(accounts.alice));
+            assert_eq!(PSP34::owner_of(&product_factory, Id::U32(3)), None);
+            assert_eq!(PSP34::owner_of(&product_factory, Id::U8(1)), None);
+
+            assert_eq!(product_factory.burn_product(1), Ok(()));
+            assert_eq!(PSP34::total_supply(&product_factory), 2);
+            assert_eq!(PSP34::balance_of(&product_factory, accounts.alice), 2);
+            assert_eq!(PSP34::owner_of(&product_factory, Id::U32(1)), None);
+        }
+
+        #[ink::test]
+        fn psp34_transfer_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.create_products(vec![1, 2]).unwrap();
+
+            assert_eq!(
+                PSP34::transfer(&mut product_factory, accounts.bob, Id::U32(0), Vec::new()),
+                Ok(())
+            );
+            assert_eq!(PSP34::owner_of(&product_factory, Id::U32(0)), Some(accounts.bob));
+            assert_eq!(product_factory.products_of(accounts.bob, false), vec![0]);
+            assert_eq!(product_factory.history_len(0), 2);
+            assert_eq!(
+                PSP34::transfer(&mut product_factory, accounts.bob, Id::U32(0), Vec::new()),
+                Err(PSP34Error::NotApproved)
+            );
+            assert_eq!(
+                PSP34::transfer(&mut product_factory, accounts.bob, Id::U32(9), Vec::new()),
+                Err(PSP34Error::TokenNotExists)
+            );
+            assert_eq!(
+                PSP34::transfer(&mut product_factory, accounts.bob, Id::U64(1), Vec::new()),
+                Err(PSP34Error::TokenNotExists)
+            );
+
+            // A product pending delegation cannot move.
+            assert_eq!(product_factory.delegate_product(1, accounts.charlie, None, None, None), Ok(()));
+            assert_eq!(
+                PSP34::transfer(&mut product_factory, accounts.bob, Id::U32(1), Vec::new()),
+                Err(PSP34Error::Custom(String::from("InvalidState")))
+            );
+            assert_eq!(PSP34::owner_of(&product_factory, Id::U32(1)), Some(accounts.alice));
+        }
+
+        #[ink::test]
+        fn psp34_approve_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.create_products(vec![1, 2, 3]).unwrap();
+
+            assert_eq!(
+                PSP34::approve(&mut product_factory, accounts.alice, None, true),
+                Err(PSP34Error::SelfApprove)
+            );
+            assert_eq!(
+                PSP34::approve(&mut product_factory, accounts.bob, Some(Id::U32(0)), true),
+                Ok(())
+            );
+            assert!(PSP34::allowance(&product_factory, accounts.alice, accounts.bob, Some(Id::U32(0))));
+            assert!(!PSP34::allowance(&product_factory, accounts.alice, accounts.bob, Some(Id::U32(1))));
+            assert!(!PSP34::allowance(&product_factory, accounts.alice, accounts.bob, None));
+            assert_eq!(product_factory.get_approved(0), Some(accounts.bob));
+
+            assert_eq!(PSP34::approve(&mut product_factory, accounts.charlie, None, true), Ok(()));
+            assert!(PSP34::allowance(&product_factory, accounts.alice, accounts.charlie, None));
+            assert!(PSP34::allowance(&product_factory, accounts.alice, accounts.charlie, Some(Id::U32(2))));
+
+            set_sender(accounts.bob);
+            assert_eq!(
+                PSP34::approve(&mut product_factory, accounts.django, Some(Id::U32(1)), true),
+                Err(PSP34Error::NotApproved)
+            );
+            assert_eq!(
+                PSP34::transfer(&mut product_factory, accounts.bob, Id::U32(1), Vec::new()),
+                Err(PSP34Error::NotApproved)
+            );
+            assert_eq!(
+                PSP34::transfer(&mut product_factory, accounts.bob, Id::U32(0), Vec::new()),
+                Ok(())
+            );
+            assert_eq!(product_factory.get_approved(0), None);
+
+            set_sender(accounts.charlie);
+            assert_eq!(
+                PSP34::transfer(&mut product_factory, accounts.django, Id::U32(2), Vec::new()),
+                Ok(())
+            );
+            assert_eq!(PSP34::owner_of(&product_factory, Id::U32(2)), Some(accounts.django));
+
+            set_sender(accounts.alice);
+            assert_eq!(PSP34::approve(&mut product_factory, accounts.charlie, None, false), Ok(()));
+            assert!(!PSP34::allowance(&product_factory, accounts.alice, accounts.charlie, Some(Id::U32(1))));
+            set_sender(accounts.charlie);
+            assert_eq!(
+                PSP34::transfer(&mut product_factory, accounts.django, Id::U32(1), Vec::new()),
+                Err(PSP34Error::NotApproved)
+            );
+        }
+
+        #[ink::test]
+        fn pending_for_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.create_products(vec![1, 2, 3, 4]).unwrap();
+            assert_eq!(product_factory.pending_for(accounts.bob), Vec::<u32>::new());
+
+            assert_eq!(product_factory.delegate_products(vec![0, 1, 2], accounts.bob), Ok(()));
+            assert_eq!(product_factory.delegate_product(3, accounts.charlie, Some(10), None, None), Ok(()));
+            assert_eq!(product_factory.pending_for(accounts.bob), vec![0, 1, 2]);
+            assert_eq!(product_factory.pending_for(accounts.charlie), vec![3]);
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.accept_product(0), Ok(()));
+            assert_eq!(product_factory.reject_delegation(2), Ok(()));
+            assert_eq!(product_factory.pending_for(accounts.bob), vec![1]);
+
+            // Re-delegating moves the pid to the new delegate's list.
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.revoke_delegation(1), Ok(()));
+            assert_eq!(product_factory.delegate_product(1, accounts.charlie, None, None, None), Ok(()));
+            assert_eq!(product_factory.pending_for(accounts.bob), Vec::<u32>::new());
+            assert_eq!(product_factory.pending_for(accounts.charlie), vec![3, 1]);
+
+            advance_block_by(3);
+            assert_eq!(product_factory.expire_delegation(3), Ok(()));
+            assert_eq!(product_factory.pending_for(accounts.charlie), vec![1]);
+        }
+
+        #[ink::test]
+        fn delegated_by_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            product_factory.add_manufacturer(accounts.bob).unwrap();
+            clear_transferred_value();
+            product_factory.create_products(vec![1, 2, 3]).unwrap();
+            set_sender(accounts.bob);
+            product_factory.create_products(vec![4, 5]).unwrap();
+            assert_eq!(product_factory.delegated_by(accounts.bob), Vec::new());
+
+            assert_eq!(product_factory.delegate_product(3, accounts.charlie, None, None, None), Ok(()));
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.delegate_product(0, accounts.charlie, None, None, None), Ok(()));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.delegate_product(4, accounts.django, None, None, None), Ok(()));
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.delegate_product(2, accounts.bob, None, None, None), Ok(()));
+
+            assert_eq!(product_factory.delegated_by(accounts.alice), vec![(0, accounts.charlie), (2, accounts.bob)]);
+            assert_eq!(product_factory.delegated_by(accounts.bob), vec![(3, accounts.charlie), (4, accounts.django)]);
+
+            set_sender(accounts.charlie);
+            assert_eq!(product_factory.accept_product(0), Ok(()));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.revoke_delegation(4), Ok(()));
+            assert_eq!(product_factory.delegated_by(accounts.alice), vec![(2, accounts.bob)]);
+            assert_eq!(product_factory.delegated_by(accounts.bob), vec![(3, accounts.charlie)]);
+            assert_eq!(product_factory.delegated_by(accounts.charlie), Vec::new());
+        }
+
+        #[ink::test]
+        fn count_of_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.count_of(accounts.alice), 0);
+            product_factory.create_product(1, None, None).unwrap();
+            product_factory.create_products(vec![2, 3]).unwrap();
+            assert_eq!(product_factory.count_of(accounts.alice), 3);
+            assert_eq!(product_factory.count_of(accounts.bob), 0);
+
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, None, None, None), Ok(()));
+            assert_eq!(product_factory.count_of(accounts.alice), 3);
+            assert_eq!(product_factory.count_of(accounts.bob), 0);
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.accept_product(0), Ok(()));
+            assert_eq!(product_factory.count_of(accounts.alice), 2);
+            assert_eq!(product_factory.count_of(accounts.bob), 1);
+
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.transfer_product(1, accounts.bob), Ok(()));
+            assert_eq!(product_factory.burn_product(2), Ok(()));
+            assert_eq!(product_factory.count_of(accounts.alice), 0);
+            assert_eq!(product_factory.count_of(accounts.bob), 2);
+        }
+
+        #[ink::test]
+        fn recall_product_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.create_products(vec![1, 2]).unwrap();
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, None, None, None), Ok(()));
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.recall_product(0), Err(Error::NotAdmin));
+
+            // Recalling mid-handshake cancels the delegation.
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.recall_product(0), Ok(()));
+            assert_eq!(product_factory.recall_product(0), Err(Error::ProductRecalled));
+            let product = product_factory.get_product(0).unwrap();
+            assert_eq!(product.state, State::Recalled);
+            assert_eq!(product.owner, accounts.alice);
+            assert_eq!(product.delegate_to, None);
+            assert_eq!(product_factory.pending_for(accounts.bob), Vec::<u32>::new());
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.accept_product(0), Err(Error::InvalidDelegate));
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, None, None, None), Err(Error::ProductRecalled));
+            assert_eq!(product_factory.transfer_product(0, accounts.bob), Err(Error::ProductRecalled));
+            assert_eq!(product_factory.burn_product(0), Err(Error::ProductRecalled));
+            assert_eq!(product_factory.resolve_recall(1), Err(Error::InvalidState));
+
+            assert_eq!(product_factory.resolve_recall(0), Ok(()));
+            assert_eq!(product_factory.get_product(0).unwrap().state, State::Owned);
+            assert_eq!(product_factory.transfer_product(0, accounts.bob), Ok(()));
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(emitted_events.len(), 12);
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events[5].data[..])
+                .expect("Cannot decode event");
+            if let Event::ProductRecalled(ProductRecalled { pid, owner, delegate }) = decoded {
+                assert_eq!(pid, 0);
+                assert_eq!(owner, accounts.alice);
+                assert_eq!(delegate, Some(accounts.bob));
+            } else {
+                panic!("Expected a ProductRecalled event")
+            }
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events[8].data[..])
+                .expect("Cannot decode event");
+            if let Event::RecallResolved(RecallResolved { pid, owner }) = decoded {
+                assert_eq!(pid, 0);
+                assert_eq!(owner, accounts.alice);
+            } else {
+                panic!("Expected a RecallResolved event")
+            }
+        }
+
+        #[ink::test]
+        fn freeze_product_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.create_products(vec![1, 2]).unwrap();
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, None, None, None), Ok(()));
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.freeze_product(0), Err(Error::NotAdmin));
+
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.freeze_product(9), Err(Error::PidNotExists));
+            assert_eq!(product_factory.freeze_product(0), Ok(()));
+            assert_eq!(product_factory.freeze_product(1), Ok(()));
+            assert!(product_factory.get_product(0).unwrap().frozen);
+            assert_eq!(product_factory.delegate_product(1, accounts.bob, None, None, None), Err(Error::ProductFrozen));
+            assert_eq!(product_factory.transfer_product(1, accounts.bob), Err(Error::ProductFrozen));
+            assert_eq!(product_factory.burn_product(1), Err(Error::ProductFrozen));
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.accept_product(0), Err(Error::ProductFrozen));
+            let product = product_factory.get_product(0).unwrap();
+            assert_eq!(product.state, State::PendingDelegation);
+            assert_eq!(product.delegate_to, Some(accounts.bob));
+
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.unfreeze_product(0), Ok(()));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.accept_product(0), Ok(()));
+            assert_eq!(product_factory.get_product(0).unwrap().owner, accounts.bob);
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(emitted_events.len(), 12);
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events[4].data[..])
+                .expect("Cannot decode event");
+            if let Event::ProductFrozen(ProductFrozen { pid }) = decoded {
+                assert_eq!(pid, 0);
+            } else {
+                panic!("Expected a ProductFrozen event")
+            }
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events[8].data[..])
+                .expect("Cannot decode event");
+            if let Event::ProductUnfrozen(ProductUnfrozen { pid }) = decoded {
+                assert_eq!(pid, 0);
+            } else {
+                panic!("Expected a ProductUnfrozen event")
+            }
+        }
+
+        #[ink::test]
+        fn blacklist_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.add_manufacturer(accounts.eve).unwrap();
+            product_factory.create_products(vec![1, 2, 3]).unwrap();
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, None, None, None), Ok(()));
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.set_blacklisted(accounts.bob, false), Err(Error::NotAdmin));
+
+            // Blacklisted after being named delegate, before accepting.
+            set_sender(accounts.alice);
+            assert!(!product_factory.is_blacklisted(accounts.bob));
+            assert_eq!(product_factory.set_blacklisted(accounts.bob, true), Ok(()));
+            assert_eq!(product_factory.set_blacklisted(accounts.eve, true), Ok(()));
+            assert!(product_factory.is_blacklisted(accounts.bob));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.accept_product(0), Err(Error::Blacklisted));
+            assert_eq!(product_factory.get_product(0).unwrap().owner, accounts.alice);
+
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.delegate_product(1, accounts.bob, None, None, None), Err(Error::Blacklisted));
+            assert_eq!(product_factory.transfer_product(1, accounts.bob), Err(Error::Blacklisted));
+            assert_eq!(product_factory.transfer_product(1, accounts.charlie), Ok(()));
+            set_sender(accounts.eve);
+            assert_eq!(product_factory.create_product(4, None, None), Err(Error::Blacklisted));
+
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.set_blacklisted(accounts.bob, false), Ok(()));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.accept_product(0), Ok(()));
+        }
+
+        #[ink::test]
+        fn split_product_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.create_product(1, Some(0), None), Err(Error::InvalidQuantity));
+            assert_eq!(product_factory.create_product(1, Some(500), None), Ok(0));
+            assert_eq!(product_factory.create_product(2, None, None), Ok(1));
+            assert_eq!(product_factory.get_product(1).unwrap().quantity, 1);
+
+            assert_eq!(product_factory.split_product(0, 0), Err(Error::InvalidQuantity));
+            assert_eq!(product_factory.split_product(0, 500), Err(Error::InsufficientQuantity));
+            assert_eq!(product_factory.split_product(1, 1), Err(Error::InsufficientQuantity));
+            assert_eq!(product_factory.set_metadata_uri(0, b"ipfs://lot".to_vec()), Ok(()));
+            assert_eq!(product_factory.split_product(0, 120), Ok(2));
+            assert_eq!(product_factory.split_product(2, 20), Ok(3));
+
+            let quantities = (0..4)
+                .filter_map(|pid| product_factory.get_product(pid).ok())
+                .filter(|p| p.code == 1)
+                .map(|p| p.quantity)
+                .collect::<Vec<_>>();
+            assert_eq!(quantities, vec![380, 100, 20]);
+            assert_eq!(quantities.iter().sum::<u32>(), 500);
+            let split = product_factory.get_product(3).unwrap();
+            assert_eq!(split.owner, accounts.alice);
+            assert_eq!(split.get_metadata_uri(), Some(b"ipfs://lot".to_vec()));
+            assert_eq!(product_factory.get_products_by_code(1), vec![0, 2, 3]);
+            assert_eq!(product_factory.products_of(accounts.alice, false), vec![0, 1, 2, 3]);
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.split_product(0, 1), Err(Error::InvalidOwner));
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, None, None, None), Ok(()));
+            assert_eq!(product_factory.split_product(0, 1), Err(Error::InvalidState));
+            assert_eq!(product_factory.get_product(0).unwrap().quantity, 380);
+        }
+
+        #[ink::test]
+        fn merge_products_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.create_product(1, Some(10), None), Ok(0));
+            assert_eq!(product_factory.create_product(2, None, None), Ok(1));
+            assert_eq!(product_factory.split_product(0, 3), Ok(2));
+            assert_eq!(product_factory.split_product(0, 2), Ok(3));
+            assert_eq!(product_factory.transfer_product(3, accounts.bob), Ok(()));
+
+            assert_eq!(product_factory.merge_products(0, 0), Err(Error::SelfMerge));
+            assert_eq!(product_factory.merge_products(0, 1), Err(Error::CodeMismatch));
+            assert_eq!(product_factory.merge_products(0, 3), Err(Error::InvalidOwner));
+            assert_eq!(product_factory.delegate_product(2, accounts.bob, None, None, None), Ok(()));
+            assert_eq!(product_factory.merge_products(0, 2), Err(Error::InvalidState));
+            assert_eq!(product_factory.revoke_delegation(2), Ok(()));
+
+            assert_eq!(product_factory.merge_products(0, 2), Ok(()));
+            assert_eq!(product_factory.get_product(0).unwrap().quantity, 8);
+            assert_eq!(product_factory.get_product(2), Err(Error::PidNotExists));
+            assert_eq!(product_factory.get_products_by_code(1), vec![0, 3]);
+            assert_eq!(product_factory.products_of(accounts.alice, false), vec![0, 1]);
+            assert_eq!(product_factory.count_of(accounts.alice), 2);
+            assert_eq!(product_factory.merge_products(0, 2), Err(Error::PidNotExists));
+            assert_eq!(product_factory.split_product(2, 1), Err(Error::PidNotExists));
+            assert_eq!(product_factory.transfer_product(2, accounts.bob), Err(Error::PidNotExists));
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events.last().unwrap().data[..])
+                .expect("Cannot decode event");
+            if let Event::ProductsMerged(ProductsMerged { target_pid, source_pid, quantity }) = decoded {
+                assert_eq!((target_pid, source_pid, quantity), (0, 2, 8));
+            } else {
+                panic!("Expected a ProductsMerged event")
+            }
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events[emitted_events.len() - 2].data[..])
+                .expect("Cannot decode event");
+            if let Event::ProductBurned(ProductBurned { pid, owner, .. }) = decoded {
+                assert_eq!((pid, owner), (2, accounts.alice));
+            } else {
+                panic!("Expected a ProductBurned event")
+            }
+        }
+
+        #[ink::test]
+        fn assemble_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            // bicycle, frame, two wheels and a saddle owned by bob
+            for code in 1..=5{
+                assert_eq!(product_factory.create_product(code, None, None), Ok(code as u32 - 1));
+            }
+            assert_eq!(product_factory.transfer_product(4, accounts.bob), Ok(()));
+
+            assert_eq!(product_factory.assemble(0, Vec::new()), Err(Error::NoComponents));
+            assert_eq!(product_factory.assemble(0, vec![1, 0]), Err(Error::InvalidComponent));
+            assert_eq!(product_factory.assemble(0, vec![1, 1]), Err(Error::InvalidComponent));
+            assert_eq!(product_factory.assemble(0, vec![1, 2, 4]), Err(Error::InvalidOwner));
+            assert_eq!(product_factory.assemble(0, vec![1, 9]), Err(Error::PidNotExists));
+            assert_eq!(product_factory.delegate_product(3, accounts.bob, None, None, None), Ok(()));
+            assert_eq!(product_factory.assemble(0, vec![1, 2, 3]), Err(Error::InvalidState));
+            assert_eq!(product_factory.get_product(1).unwrap().state, State::Owned);
+
+            assert_eq!(product_factory.assemble(0, vec![1, 2]), Ok(()));
+            assert_eq!(product_factory.components_of(0), vec![1, 2]);
+            assert_eq!(product_factory.components_of(1), Vec::<u32>::new());
+            assert_eq!(product_factory.get_product(1).unwrap().state, State::ComponentLocked);
+            assert_eq!(product_factory.assemble(3, vec![1]), Err(Error::InvalidState));
+            assert_eq!(product_factory.assemble(1, vec![3]), Err(Error::ComponentLocked));
+
+            assert_eq!(product_factory.delegate_product(1, accounts.bob, None, None, None), Err(Error::ComponentLocked));
+            assert_eq!(product_factory.transfer_product(2, accounts.bob), Err(Error::ComponentLocked));
+            assert_eq!(product_factory.burn_product(1), Err(Error::ComponentLocked));
+            assert_eq!(product_factory.burn_product(0), Err(Error::HasComponents));
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.accept_product(3), Ok(()));
+            assert_eq!(product_factory.transfer_product(3, accounts.alice), Ok(()));
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.assemble(0, vec![3]), Ok(()));
+            assert_eq!(product_factory.components_of(0), vec![1, 2, 3]);
+
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, None, None, None), Ok(()));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.accept_product(0), Ok(()));
+            assert_eq!(product_factory.get_product(0).unwrap().owner, accounts.bob);
+            assert_eq!(product_factory.components_of(0), vec![1, 2, 3]);
+            for pid in 1..=3{
+                assert_eq!(product_factory.get_product(pid).unwrap().state, State::ComponentLocked);
+            }
+        }
+
+        #[ink::test]
+        fn disassemble_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            for code in 1..=3{
+                assert_eq!(product_factory.create_product(code, None, None), Ok(code as u32 - 1));
+            }
+            assert_eq!(product_factory.disassemble(0), Err(Error::NoComponents));
+            assert_eq!(product_factory.assemble(0, vec![1, 2]), Ok(()));
+            assert_eq!(product_factory.transfer_product(0, accounts.bob), Ok(()));
+            assert_eq!(product_factory.disassemble(0), Err(Error::InvalidOwner));
+            assert_eq!(product_factory.disassemble(1), Err(Error::ComponentLocked));
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.delegate_product(0, accounts.charlie, None, None, None), Ok(()));
+            assert_eq!(product_factory.disassemble(0), Err(Error::InvalidState));
+            assert_eq!(product_factory.revoke_delegation(0), Ok(()));
+            assert_eq!(product_factory.disassemble(0), Ok(()));
+            assert_eq!(product_factory.components_of(0), Vec::<u32>::new());
+            for pid in 1..=2{
+                let component = product_factory.get_product(pid).unwrap();
+                assert_eq!(component.state, State::Owned);
+                assert_eq!(component.owner, accounts.bob);
+            }
+            assert_eq!(product_factory.products_of(accounts.bob, false), vec![0, 1, 2]);
+            assert_eq!(product_factory.products_of(accounts.alice, false), Vec::<u32>::new());
+            assert_eq!(product_factory.history_len(1), 2);
+            assert_eq!(product_factory.disassemble(0), Err(Error::NoComponents));
+            assert_eq!(product_factory.transfer_product(1, accounts.charlie), Ok(()));
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events[emitted_events.len() - 3].data[..])
+                .expect("Cannot decode event");
+            if let Event::Disassembled(Disassembled { parent_pid, owner, components }) = decoded {
+                assert_eq!((parent_pid, owner, components), (0, accounts.bob, vec![1, 2]));
+            } else {
+                panic!("Expected a Disassembled event")
+            }
+        }
+
+        #[ink::test]
+        fn certify_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.create_product(1, None, None), Ok(0));
+            let safety = Hash::from([1; 32]);
+            let organic = Hash::from([2; 32]);
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.add_certifier(accounts.bob), Err(Error::NotAdmin));
+            assert_eq!(product_factory.certify(0, 1, safety), Err(Error::NotCertifier));
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.add_certifier(accounts.bob), Ok(()));
+            assert_eq!(product_factory.add_certifier(accounts.charlie), Ok(()));
+            assert!(product_factory.is_certifier(accounts.bob));
+            assert_eq!(product_factory.certify(0, 1, safety), Err(Error::NotCertifier));
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.certify(7, 1, safety), Err(Error::PidNotExists));
+            assert_eq!(product_factory.certify(0, 1, safety), Ok(()));
+            assert_eq!(product_factory.certify(0, 1, organic), Err(Error::DuplicateCertification));
+            assert_eq!(product_factory.certify(0, 2, organic), Ok(()));
+            set_sender(accounts.charlie);
+            assert_eq!(product_factory.certify(0, 1, organic), Ok(()));
+            assert_eq!(
+                product_factory.certifications_of(0),
+                vec![(accounts.bob, 1, safety), (accounts.bob, 2, organic), (accounts.charlie, 1, organic)]
+            );
+
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.remove_certifier(accounts.charlie), Ok(()));
+            set_sender(accounts.charlie);
+            assert_eq!(product_factory.certify(0, 3, organic), Err(Error::NotCertifier));
+            assert_eq!(product_factory.certifications_of(0).len(), 3);
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events[emitted_events.len() - 2].data[..])
+                .expect("Cannot decode event");
+            if let Event::ProductCertified(ProductCertified { pid, certifier, cert_code, document_hash }) = decoded {
+                assert_eq!((pid, certifier, cert_code, document_hash), (0, accounts.charlie, 1, organic));
+            } else {
+                panic!("Expected a ProductCertified event")
+            }
+        }
+
+        #[ink::test]
+        fn revoke_certification_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.create_product(1, None, None), Ok(0));
+            let document = Hash::from([1; 32]);
+            assert_eq!(product_factory.add_certifier(accounts.bob), Ok(()));
+            assert_eq!(product_factory.add_certifier(accounts.charlie), Ok(()));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.certify(0, 1, document), Ok(()));
+            assert_eq!(product_factory.certify(0, 2, document), Ok(()));
+            set_sender(accounts.charlie);
+            assert_eq!(product_factory.certify(0, 2, document), Ok(()));
+
+            // stranger
+            assert_eq!(product_factory.revoke_certification(0, 1), Err(Error::NotIssuer));
+            assert_eq!(product_factory.revoke_certification(0, 3), Err(Error::CertificationNotFound));
+            assert_eq!(product_factory.revoke_certification(9, 1), Err(Error::PidNotExists));
+            set_sender(accounts.eve);
+            assert_eq!(product_factory.revoke_certification(0, 1), Err(Error::NotIssuer));
+            assert_eq!(product_factory.certifications_of(0).len(), 3);
+
+            // issuer
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.revoke_certification(0, 2), Ok(()));
+            assert_eq!(
+                product_factory.certifications_of(0),
+                vec![(accounts.bob, 1, document), (accounts.charlie, 2, document)]
+            );
+            assert_eq!(product_factory.revoked_certifications_of(0), vec![(accounts.bob, 2, document)]);
+            assert_eq!(product_factory.revoke_certification(0, 2), Err(Error::NotIssuer));
+
+            // admin
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.revoke_certification(0, 2), Ok(()));
+            assert_eq!(product_factory.revoke_certification(0, 2), Err(Error::CertificationNotFound));
+            assert_eq!(product_factory.certifications_of(0), vec![(accounts.bob, 1, document)]);
+            assert_eq!(
+                product_factory.revoked_certifications_of(0),
+                vec![(accounts.bob, 2, document), (accounts.charlie, 2, document)]
+            );
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events.last().unwrap().data[..])
+                .expect("Cannot decode event");
+            if let Event::CertificationRevoked(CertificationRevoked { pid, certifier, cert_code, revoked_by }) = decoded {
+                assert_eq!((pid, certifier, cert_code, revoked_by), (0, accounts.charlie, 2, accounts.alice));
+            } else {
+                panic!("Expected a CertificationRevoked event")
+            }
+
+            // burning keeps both logs, like the history
+            assert_eq!(product_factory.burn_product(0), Ok(()));
+            assert_eq!(product_factory.certifications_of(0), vec![(accounts.bob, 1, document)]);
+            assert_eq!(
+                product_factory.revoked_certifications_of(0),
+                vec![(accounts.bob, 2, document), (accounts.charlie, 2, document)]
+            );
+            assert_eq!(product_factory.revoke_certification(0, 1), Err(Error::PidNotExists));
+        }
+
+        #[ink::test]
+        fn dispute_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            let reason = Hash::from([7; 32]);
+            assert_eq!(product_factory.set_arbiter(accounts.eve), Ok(()));
+            for pid in 0..3{
+                assert_eq!(product_factory.create_product(pid as u16 + 1, None, None), Ok(pid));
+            }
+            assert_eq!(product_factory.raise_dispute(0, reason), Err(Error::DisputeWindowClosed));
+            for pid in 0..3{
+                assert_eq!(product_factory.delegate_product(pid, accounts.bob, None, None, None), Ok(()));
+            }
+            set_sender(accounts.bob);
+            for pid in 0..3{
+                assert_eq!(product_factory.accept_product(pid), Ok(()));
+            }
+
+            // the previous owner disputes, the arbiter hands the product back
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.raise_dispute(0, reason), Ok(()));
+            assert_eq!(product_factory.get_product(0).unwrap().state, State::Disputed);
+            assert_eq!(product_factory.raise_dispute(0, reason), Err(Error::ProductDisputed));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.transfer_product(0, accounts.charlie), Err(Error::ProductDisputed));
+            assert_eq!(product_factory.delegate_product(0, accounts.charlie, None, None, None), Err(Error::ProductDisputed));
+            assert_eq!(product_factory.resolve_dispute(0, true), Err(Error::NotArbiter));
+            set_sender(accounts.eve);
+            assert_eq!(product_factory.resolve_dispute(1, true), Err(Error::InvalidState));
+            assert_eq!(product_factory.resolve_dispute(0, true), Ok(()));
+            let product = product_factory.get_product(0).unwrap();
+            assert_eq!((product.state, product.owner), (State::Owned, accounts.alice));
+            assert_eq!(product_factory.products_of(accounts.alice, false), vec![0]);
+            assert_eq!(product_factory.history_len(0), 3);
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.raise_dispute(0, reason), Err(Error::DisputeWindowClosed));
+
+            // the current owner disputes, the arbiter confirms it
+            set_sender(accounts.charlie);
+            assert_eq!(product_factory.raise_dispute(1, reason), Err(Error::InvalidOwner));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.raise_dispute(1, reason), Ok(()));
+            set_sender(accounts.eve);
+            assert_eq!(product_factory.resolve_dispute(1, false), Ok(()));
+            let product = product_factory.get_product(1).unwrap();
+            assert_eq!((product.state, product.owner), (State::Owned, accounts.bob));
+            assert_eq!(product_factory.history_len(1), 2);
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events.last().unwrap().data[..])
+                .expect("Cannot decode event");
+            if let Event::DisputeResolved(DisputeResolved { pid, owner, returned_to_previous_owner, .. }) = decoded {
+                assert_eq!((pid, owner, returned_to_previous_owner), (1, accounts.bob, false));
+            } else {
+                panic!("Expected a DisputeResolved event")
+            }
+
+            // the window closes DISPUTE_WINDOW blocks after the acceptance
+            advance_block_by(DISPUTE_WINDOW + 1);
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.raise_dispute(2, reason), Err(Error::DisputeWindowClosed));
+        }
+
+        #[ink::test]
+        fn paid_delegation_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let contract = ink_env::account_id::<Environment>().expect("Cannot get contract id");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            for code in 1..=4{
+                assert_eq!(product_factory.create_product(code, None, None), Ok(code as u32 - 1));
+            }
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, None, Some(100), None), Ok(()));
+            assert_eq!(product_factory.delegate_product(1, accounts.bob, None, Some(0), None), Ok(()));
+            assert_eq!(product_factory.delegate_product(2, accounts.bob, None, None, None), Ok(()));
+            assert_eq!(product_factory.delegate_product(3, accounts.charlie, None, Some(50), None), Ok(()));
+            assert_eq!(product_factory.get_product(0).unwrap().price, 100);
+
+            // underpaying leaves the delegation pending and hands the value back
+            test::set_account_balance::<Environment>(accounts.bob, 0)
+                .expect("Cannot set buyer balance");
+            set_sender_paying(accounts.bob, 99);
+            assert_eq!(product_factory.accept_product(0), Err(Error::InsufficientPayment));
+            assert_eq!(test::get_account_balance::<Environment>(accounts.bob), Ok(99));
+            set_sender_paying(accounts.bob, 99);
+            assert_eq!(product_factory.accept_products(vec![0, 1]), Err(Error::InsufficientPayment));
+            assert_eq!(test::get_account_balance::<Environment>(accounts.bob), Ok(198));
+            assert_eq!(test::get_account_balance::<Environment>(contract), Ok(0));
+            let product = product_factory.get_product(0).unwrap();
+            assert_eq!((product.state, product.owner), (State::PendingDelegation, accounts.alice));
+            assert_eq!(product_factory.withdrawable_of(accounts.alice), 0);
+
+            // paid path
+            set_sender_with_value(accounts.bob, 100);
+            assert_eq!(product_factory.accept_product(0), Ok(()));
+            let product = product_factory.get_product(0).unwrap();
+            assert_eq!((product.owner, product.price), (accounts.bob, 0));
+            assert_eq!(product_factory.withdrawable_of(accounts.alice), 100);
+
+            // free path, price 0 and None alike
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.accept_products(vec![1, 2]), Ok(()));
+            assert_eq!(product_factory.products_of(accounts.bob, false), vec![0, 1, 2]);
+            assert_eq!(product_factory.withdrawable_of(accounts.alice), 100);
+
+            // proceeds of several sales add up, and resales credit the reseller
+            set_sender_with_value(accounts.charlie, 50);
+            assert_eq!(product_factory.accept_product(3), Ok(()));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.delegate_product(0, accounts.charlie, None, Some(70), None), Ok(()));
+            set_sender_with_value(accounts.charlie, 70);
+            assert_eq!(product_factory.accept_product(0), Ok(()));
+            assert_eq!(product_factory.withdrawable_of(accounts.alice), 150);
+            assert_eq!(product_factory.withdrawable_of(accounts.bob), 70);
+
+            // The off-chain environment does not credit the contract with
+            // the transferred value, so the balance below stands in for it.
+            test::set_account_balance::<Environment>(contract, 300)
+                .expect("Cannot set contract balance");
+            test::set_account_balance::<Environment>(accounts.alice, 0)
+                .expect("Cannot set seller balance");
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.withdraw_fees(accounts.eve, 0), Ok(()));
+            assert_eq!(test::get_account_balance::<Environment>(contract), Ok(220));
+            assert_eq!(product_factory.withdraw(), Ok(()));
+            assert_eq!(test::get_account_balance::<Environment>(accounts.alice), Ok(150));
+            assert_eq!(product_factory.withdrawable_of(accounts.alice), 0);
+            assert_eq!(product_factory.withdraw(), Err(Error::NothingToWithdraw));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.withdraw(), Ok(()));
+            assert_eq!(test::get_account_balance::<Environment>(contract), Ok(0));
+        }
+
+        #[ink::test]
+        fn buy_product_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let contract = ink_env::account_id::<Environment>().expect("Cannot get contract id");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            for code in 1..=3{
+                assert_eq!(product_factory.create_product(code, None, None), Ok(code as u32 - 1));
+            }
+            assert_eq!(product_factory.buy_product(0), Err(Error::NotListed));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.list_for_sale(0, 100), Err(Error::InvalidOwner));
+            set_sender(accounts.alice);
+            for pid in 0..3{
+                assert_eq!(product_factory.list_for_sale(pid, 100), Ok(()));
+            }
+            assert_eq!(product_factory.get_product(0).unwrap().state, State::Listed);
+            assert_eq!(product_factory.list_for_sale(0, 50), Err(Error::ProductListed));
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, None, None, None), Err(Error::ProductListed));
+            assert_eq!(product_factory.transfer_product(0, accounts.bob), Err(Error::ProductListed));
+            assert_eq!(product_factory.buy_product(0), Err(Error::SelfTransfer));
+
+            // underpayment is handed back
+            test::set_account_balance::<Environment>(accounts.bob, 0)
+                .expect("Cannot set buyer balance");
+            set_sender_paying(accounts.bob, 99);
+            assert_eq!(product_factory.buy_product(0), Err(Error::InsufficientPayment));
+            assert_eq!(product_factory.get_product(0).unwrap().owner, accounts.alice);
+            assert_eq!(test::get_account_balance::<Environment>(accounts.bob), Ok(99));
+            assert_eq!(test::get_account_balance::<Environment>(contract), Ok(0));
+
+            // exact payment
+            set_sender_with_value(accounts.bob, 100);
+            assert_eq!(product_factory.buy_product(0), Ok(()));
+            let product = product_factory.get_product(0).unwrap();
+            assert_eq!((product.state, product.owner, product.price), (State::Owned, accounts.bob, 0));
+            assert_eq!(product_factory.withdrawable_of(accounts.alice), 100);
+            set_sender_paying(accounts.bob, 100);
+            assert_eq!(product_factory.buy_product(0), Err(Error::NotListed));
+
+            // overpayment is refunded. The off-chain environment does not
+            // credit the contract with the transferred value, so the balance
+            // below stands in for it.
+            test::set_account_balance::<Environment>(contract, 250)
+                .expect("Cannot set contract balance");
+            test::set_account_balance::<Environment>(accounts.charlie, 0)
+                .expect("Cannot set buyer balance");
+            set_sender_with_value(accounts.charlie, 150);
+            assert_eq!(product_factory.buy_product(1), Ok(()));
+            assert_eq!(test::get_account_balance::<Environment>(accounts.charlie), Ok(50));
+            assert_eq!(product_factory.get_product(1).unwrap().owner, accounts.charlie);
+            assert_eq!(product_factory.withdrawable_of(accounts.alice), 200);
+            assert_eq!(product_factory.products_of(accounts.alice, false), vec![2]);
+
+            // the owner unlists before the buyer's call lands
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.unlist(2), Ok(()));
+            assert_eq!(product_factory.unlist(2), Err(Error::InvalidState));
+            test::set_account_balance::<Environment>(accounts.bob, 0)
+                .expect("Cannot set buyer balance");
+            let balance = test::get_account_balance::<Environment>(contract).expect("Cannot get contract balance");
+            set_sender_paying(accounts.bob, 100);
+            assert_eq!(product_factory.buy_product(2), Err(Error::NotListed));
+            assert_eq!(product_factory.get_product(2).unwrap().owner, accounts.alice);
+            assert_eq!(product_factory.withdrawable_of(accounts.alice), 200);
+            assert_eq!(test::get_account_balance::<Environment>(accounts.bob), Ok(100));
+            assert_eq!(test::get_account_balance::<Environment>(contract), Ok(balance));
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.delegate_product(2, accounts.bob, None, None, None), Ok(()));
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events[emitted_events.len() - 5].data[..])
+                .expect("Cannot decode event");
+            if let Event::ProductSold(ProductSold { pid, seller, buyer, price, .. }) = decoded {
+                assert_eq!((pid, seller, buyer, price), (1, accounts.alice, accounts.charlie, 100));
+            } else {
+                panic!("Expected a ProductSold event")
+            }
+        }
+
+        /// The off-chain balance below the carried value stands for a
+        /// contract that cannot hand a failed payment back, which traps.
+        #[ink::test]
+        #[should_panic(expected = "the contract holds the value the call carried")]
+        fn unrefundable_payment_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let contract = ink_env::account_id::<Environment>().expect("Cannot get contract id");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.create_product(1, None, None).unwrap();
+            assert_eq!(product_factory.list_for_sale(0, 100), Ok(()));
+            test::set_account_balance::<Environment>(contract, 20)
+                .expect("Cannot set contract balance");
+            set_sender_with_value(accounts.bob, 150);
+            let _ = product_factory.buy_product(0);
+        }
+
+        #[ink::test]
+        fn auction_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.create_product(1, None, None), Ok(0));
+            assert_eq!(product_factory.create_product(2, None, None), Ok(1));
+            assert_eq!(product_factory.bid(0), Err(Error::NotAuctioned));
+            assert_eq!(product_factory.start_auction(0, 100, 0), Err(Error::InvalidDuration));
+            assert_eq!(product_factory.start_auction(0, 100, 10), Ok(()));
+            assert_eq!(product_factory.start_auction(1, 100, 5), Ok(()));
+            assert_eq!(product_factory.get_product(0).unwrap().state, State::Auctioned);
+            assert_eq!(product_factory.get_auction(0).unwrap().end_block, 10);
+            assert_eq!(product_factory.transfer_product(0, accounts.bob), Err(Error::ProductAuctioned));
+            assert_eq!(product_factory.list_for_sale(0, 1), Err(Error::ProductAuctioned));
+            assert_eq!(product_factory.recall_product(0), Err(Error::ProductAuctioned));
+            assert_eq!(product_factory.bid(0), Err(Error::SelfTransfer));
+
+            set_sender_paying(accounts.bob, 99);
+            assert_eq!(product_factory.bid(0), Err(Error::BidTooLow));
+            set_sender_with_value(accounts.bob, 100);
+            assert_eq!(product_factory.bid(0), Ok(()));
+            set_sender_paying(accounts.charlie, 100);
+            assert_eq!(product_factory.bid(0), Err(Error::BidTooLow));
+            set_sender_with_value(accounts.charlie, 150);
+            assert_eq!(product_factory.bid(0), Ok(()));
+            assert_eq!(product_factory.withdrawable_of(accounts.bob), 100);
+            set_sender_with_value(accounts.bob, 200);
+            assert_eq!(product_factory.bid(0), Ok(()));
+            assert_eq!(product_factory.withdrawable_of(accounts.bob), 100);
+            assert_eq!(product_factory.withdrawable_of(accounts.charlie), 150);
+            assert_eq!(product_factory.get_auction(0).unwrap().highest_bid, Some((accounts.bob, 200)));
+            assert_eq!(product_factory.escrowed, 450);
+
+            set_sender(accounts.eve);
+            assert_eq!(product_factory.finalize_auction(0), Err(Error::AuctionNotEnded));
+            advance_block_by(10);
+            assert_eq!(product_factory.finalize_auction(0), Err(Error::AuctionNotEnded));
+            advance_block_by(1);
+            // a late bid is handed back rather than kept outside escrow
+            let contract = ink_env::account_id::<Environment>().expect("Cannot get contract id");
+            let balance = test::get_account_balance::<Environment>(contract).expect("Cannot get contract balance");
+            test::set_account_balance::<Environment>(accounts.charlie, 0)
+                .expect("Cannot set bidder balance");
+            set_sender_paying(accounts.charlie, 300);
+            assert_eq!(product_factory.bid(0), Err(Error::AuctionEnded));
+            assert_eq!(test::get_account_balance::<Environment>(accounts.charlie), Ok(300));
+            assert_eq!(test::get_account_balance::<Environment>(contract), Ok(balance));
+
+            // the highest bidder wins and the seller is credited
+            set_sender(accounts.eve);
+            assert_eq!(product_factory.finalize_auction(0), Ok(()));
+            let product = product_factory.get_product(0).unwrap();
+            assert_eq!((product.state, product.owner), (State::Owned, accounts.bob));
+            assert_eq!(product_factory.products_of(accounts.bob, false), vec![0]);
+            assert_eq!(product_factory.withdrawable_of(accounts.alice), 200);
+            assert_eq!(product_factory.escrowed, 450);
+            assert_eq!(product_factory.get_auction(0), None);
+            assert_eq!(product_factory.finalize_auction(0), Err(Error::NotAuctioned));
+
+            // without bids the product goes back to its seller
+            assert_eq!(product_factory.finalize_auction(1), Ok(()));
+            let product = product_factory.get_product(1).unwrap();
+            assert_eq!((product.state, product.owner), (State::Owned, accounts.alice));
+            assert_eq!(product_factory.withdrawable_of(accounts.alice), 200);
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events.last().unwrap().data[..])
+                .expect("Cannot decode event");
+            if let Event::AuctionFinalized(AuctionFinalized { pid, winner, amount, .. }) = decoded {
+                assert_eq!((pid, winner, amount), (1, None, 0));
+            } else {
+                panic!("Expected an AuctionFinalized event")
+            }
+        }
+
+        #[ink::test]
+        fn royalty_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.create_product(1, Some(10), None), Ok(0));
+            assert_eq!(product_factory.set_royalty(0, MAX_ROYALTY_BPS + 1), Err(Error::RoyaltyTooHigh));
+            assert_eq!(product_factory.set_royalty(0, 250), Ok(()));
+            assert_eq!(product_factory.split_product(0, 5), Ok(1));
+            assert_eq!(product_factory.get_product(1).unwrap().royalty_bps, 250);
+
+            // first sale, the creator is the seller
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, None, Some(1001), None), Ok(()));
+            set_sender_with_value(accounts.bob, 1001);
+            assert_eq!(product_factory.accept_product(0), Ok(()));
+            assert_eq!(product_factory.withdrawable_of(accounts.alice), 1001);
+            assert_eq!(product_factory.set_royalty(0, 500), Err(Error::NotCreator));
+
+            // resale, 2.5% of 999 is 24.975: the creator gets 24, the seller 975
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.list_for_sale(0, 999), Ok(()));
+            set_sender_with_value(accounts.charlie, 999);
+            assert_eq!(product_factory.buy_product(0), Ok(()));
+            assert_eq!(product_factory.withdrawable_of(accounts.alice), 1025);
+            assert_eq!(product_factory.withdrawable_of(accounts.bob), 975);
+
+            // third owner, auctioned sale of 333 at 10%
+            set_sender(accounts.eve);
+            assert_eq!(product_factory.set_royalty(0, 1000), Err(Error::NotCreator));
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.set_royalty(0, 1000), Ok(()));
+            set_sender(accounts.charlie);
+            assert_eq!(product_factory.start_auction(0, 1, 1), Ok(()));
+            set_sender_with_value(accounts.eve, 333);
+            assert_eq!(product_factory.bid(0), Ok(()));
+            advance_block_by(2);
+            assert_eq!(product_factory.finalize_auction(0), Ok(()));
+            assert_eq!(product_factory.withdrawable_of(accounts.alice), 1058);
+            assert_eq!(product_factory.withdrawable_of(accounts.charlie), 300);
+            assert_eq!(product_factory.escrowed, 1001 + 999 + 333);
+
+            // without royalty the seller gets everything
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.transfer_product(1, accounts.bob), Ok(()));
+            assert_eq!(product_factory.set_royalty(1, 0), Ok(()));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.list_for_sale(1, 7), Ok(()));
+            set_sender_with_value(accounts.django, 7);
+            assert_eq!(product_factory.buy_product(1), Ok(()));
+            assert_eq!(product_factory.withdrawable_of(accounts.bob), 982);
+            assert_eq!(product_factory.withdrawable_of(accounts.alice), 1058);
+        }
+
+        #[ink::test]
+        fn get_creator_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.get_creator(0), Err(Error::PidNotExists));
+            assert_eq!(product_factory.create_product(1, Some(2), None), Ok(0));
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, None, None, None), Ok(()));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.accept_product(0), Ok(()));
+            assert_eq!(product_factory.transfer_product(0, accounts.charlie), Ok(()));
+            assert_eq!(product_factory.get_product(0).unwrap().owner, accounts.charlie);
+            assert_eq!(product_factory.get_creator(0), Ok(accounts.alice));
+
+            set_sender(accounts.charlie);
+            assert_eq!(product_factory.split_product(0, 1), Ok(1));
+            assert_eq!(product_factory.get_creator(1), Ok(accounts.alice));
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events[emitted_events.len() - 2].data[..])
+                .expect("Cannot decode event");
+            if let Event::ProductCreated(ProductCreated { pid, owner, creator, .. }) = decoded {
+                assert_eq!((pid, owner, creator), (1, accounts.charlie, accounts.alice));
+            } else {
+                panic!("Expected a ProductCreated event")
+            }
+        }
+
+        #[ink::test]
+        fn terminate_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let contract = ink_env::account_id::<Environment>().expect("Cannot get contract id");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.create_products(vec![1, 2]).unwrap();
+            assert_eq!(product_factory.start_auction(0, 0, 5), Ok(()));
+            assert_eq!(product_factory.list_for_sale(1, 100), Ok(()));
+            assert_eq!(product_factory.terminate(accounts.eve), Err(Error::NotPaused));
+            assert_eq!(product_factory.pause(), Ok(()));
+            assert_eq!(product_factory.terminate(accounts.eve), Err(Error::TerminationNotAnnounced));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.announce_termination(), Err(Error::NotAdmin));
+            assert_eq!(product_factory.terminate(accounts.bob), Err(Error::NotAdmin));
+
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.announce_termination(), Ok(()));
+            advance_block_by(TERMINATION_DELAY - 1);
+            assert_eq!(product_factory.terminate(accounts.eve), Err(Error::TerminationTooEarly));
+            advance_block_by(1);
+            assert_eq!(product_factory.unpause(), Ok(()));
+            assert_eq!(product_factory.terminate(accounts.eve), Err(Error::NotPaused));
+            assert_eq!(product_factory.pause(), Ok(()));
+
+            // running auctions and unwithdrawn proceeds hold it up
+            assert_eq!(product_factory.terminate(accounts.eve), Err(Error::FundsEscrowed));
+            assert_eq!(product_factory.unpause(), Ok(()));
+            assert_eq!(product_factory.finalize_auction(0), Ok(()));
+            set_sender_paying(accounts.bob, 100);
+            assert_eq!(product_factory.buy_product(1), Ok(()));
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.pause(), Ok(()));
+            assert_eq!(product_factory.terminate(accounts.eve), Err(Error::FundsEscrowed));
+            assert_eq!(product_factory.withdraw(), Ok(()));
+            assert_eq!(product_factory.escrowed, 0);
+
+            test::set_account_balance::<Environment>(contract, 100)
+                .expect("Cannot set contract balance");
+            let beneficiary = accounts.eve;
+            let mut product_factory = std::panic::AssertUnwindSafe(product_factory);
+            let should_terminate = move || {
+                product_factory.terminate(beneficiary).expect("termination was checked");
+            };
+            test::assert_contract_termination::<Environment, _>(should_terminate, beneficiary, 100);
+        }
+
+        #[ink::test]
+        fn registry_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.get_registry(), None);
+            assert_eq!(product_factory.get_registry_policy(), RegistryPolicy::Ignore);
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.set_registry(Some(accounts.django)), Err(Error::NotAdmin));
+            assert_eq!(product_factory.set_registry_policy(RegistryPolicy::Revert), Err(Error::NotAdmin));
+
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.set_registry(Some(accounts.django)), Ok(()));
+            assert_eq!(product_factory.set_registry_policy(RegistryPolicy::Revert), Ok(()));
+            assert_eq!(product_factory.get_registry(), Some(accounts.django));
+            assert_eq!(product_factory.get_registry_policy(), RegistryPolicy::Revert);
+
+            // The off-chain environment cannot call other contracts, so only
+            // the disabled path is exercised here.
+            assert_eq!(product_factory.set_registry(None), Ok(()));
+            assert_eq!(product_factory.create_product(1, None, None), Ok(0));
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, None, None, None), Ok(()));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.accept_product(0), Ok(()));
+            assert_eq!(product_factory.transfer_product(0, accounts.charlie), Ok(()));
+            set_sender(accounts.charlie);
+            assert_eq!(product_factory.burn_product(0), Ok(()));
+        }
+
+        /// Hands a new product over to `to` using nothing but the
+        /// `ProductRegistry` interface, like a calling contract would.
+        fn hand_over<R>(registry: &mut R, code: u16, owner: AccountId, to: AccountId) -> Result<u32>
+        where
+            R: ProductRegistry<
+                CreateProductOut = Result<u32>,
+                DelegateProductOut = Result<()>,
+                AcceptProductOut = Result<()>,
+                ProductCountOut = u32,
+            >,
+        {
+            let count = registry.product_count();
+            let pid = registry.create_product(code, None, None)?;
+            assert_eq!(registry.product_count(), count + 1);
+            registry.delegate_product(pid, to, None, None, None)?;
+            set_sender(to);
+            registry.accept_product(pid)?;
+            set_sender(owner);
+            Ok(pid)
+        }
+
+        #[ink::test]
+        fn product_registry_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(hand_over(&mut product_factory, 1, accounts.alice, accounts.bob), Ok(0));
+            assert_eq!(hand_over(&mut product_factory, 2, accounts.alice, accounts.charlie), Ok(1));
+            assert_eq!(hand_over(&mut product_factory, 1, accounts.alice, accounts.bob), Err(Error::DuplicateCode));
+            assert_eq!(ProductRegistry::get_product(&product_factory, 0).map(|p| p.owner), Ok(accounts.bob));
+            assert_eq!(ProductRegistry::get_product(&product_factory, 1).map(|p| p.owner), Ok(accounts.charlie));
+            assert_eq!(ProductRegistry::get_product(&product_factory, 2).map(|p| p.owner), Err(Error::PidNotExists));
+        }
+
+        #[ink::test]
+        fn hash_id_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.id_of(0), None);
+            assert_eq!(product_factory.create_product(1, Some(2), None), Ok(0));
+            let id = product_factory.id_of(0).unwrap();
+            let mut expected = <ink_env::hash::Blake2x256 as ink_env::hash::HashOutput>::Type::default();
+            ink_env::hash_encoded::<ink_env::hash::Blake2x256, _>(&(accounts.alice, 1u16, 0u64), &mut expected);
+            assert_eq!(id, Hash::from(expected));
+
+            // a split mints the same (creator, code) again
+            assert_eq!(product_factory.transfer_product(0, accounts.bob), Ok(()));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.split_product(0, 1), Ok(1));
+            let split_id = product_factory.id_of(1).unwrap();
+            assert_eq!(product_factory.get_creator(1), Ok(accounts.alice));
+            assert_ne!(split_id, id);
+
+            assert_eq!(product_factory.pid_of(split_id), Some(1));
+            assert_eq!(product_factory.get_product_by_id(id).map(|p| p.code), Ok(1));
+            assert_eq!(product_factory.get_product_by_id(Hash::from([0; 32])), Err(Error::PidNotExists));
+            assert_eq!(product_factory.ids_of(accounts.bob), vec![id, split_id]);
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events[emitted_events.len() - 2].data[..])
+                .expect("Cannot decode event");
+            if let Event::ProductCreated(ProductCreated { pid, id, .. }) = decoded {
+                assert_eq!((pid, id), (1, split_id));
+            } else {
+                panic!("Expected a ProductCreated event")
+            }
+
+            // a burned product's id resolves no longer
+            assert_eq!(product_factory.burn_product(1), Ok(()));
+            assert_eq!(product_factory.id_of(1), None);
+            assert_eq!(product_factory.pid_of(split_id), None);
+            assert_eq!(product_factory.get_product_by_id(split_id), Err(Error::PidNotExists));
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events.last().unwrap().data[..])
+                .expect("Cannot decode event");
+            if let Event::ProductBurned(ProductBurned { pid, id, .. }) = decoded {
+                assert_eq!((pid, id), (1, split_id));
+            } else {
+                panic!("Expected a ProductBurned event")
+            }
+        }
+
+        #[ink::test]
+        fn update_code_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.create_product(1, None, None), Ok(0));
+            assert_eq!(product_factory.create_product(2, None, None), Ok(1));
+            assert_eq!(product_factory.update_code(0, 2), Err(Error::DuplicateCode));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.update_code(0, 3), Err(Error::InvalidOwner));
+
+            // early correction, the mistyped code stays used up
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.update_code(0, 3), Ok(()));
+            assert_eq!(product_factory.get_product(0).unwrap().get_code(), 3);
+            assert_eq!(product_factory.get_products_by_code(1), Vec::<u32>::new());
+            assert_eq!(product_factory.get_products_by_code(3), vec![0]);
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events.last().unwrap().data[..])
+                .expect("Cannot decode event");
+            if let Event::CodeUpdated(CodeUpdated { pid, old_code, new_code }) = decoded {
+                assert_eq!((pid, old_code, new_code), (0, 1, 3));
+            } else {
+                panic!("Expected a CodeUpdated event")
+            }
+            assert_eq!(product_factory.create_product(1, None, None), Err(Error::DuplicateCode));
+            assert_eq!(product_factory.update_code(1, 1), Err(Error::DuplicateCode));
+
+            // pending delegation, then a completed transfer
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, None, None, None), Ok(()));
+            assert_eq!(product_factory.update_code(0, 4), Err(Error::InvalidState));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.accept_product(0), Ok(()));
+            assert_eq!(product_factory.update_code(0, 4), Err(Error::InvalidState));
+            assert_eq!(product_factory.get_product(0).unwrap().get_code(), 3);
+        }
+
+        #[ink::test]
+        fn delegate_to_any_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.create_product(1, None, None), Ok(0));
+            assert_eq!(product_factory.create_product(2, None, None), Ok(1));
+            assert_eq!(product_factory.delegate_to_any(0, Vec::new(), None, None), Err(Error::InvalidCandidates));
+            assert_eq!(
+                product_factory.delegate_to_any(0, vec![accounts.bob; MAX_CANDIDATES + 1], None, None),
+                Err(Error::InvalidCandidates)
+            );
+            assert_eq!(
+                product_factory.delegate_to_any(0, vec![accounts.bob, accounts.bob], None, None),
+                Err(Error::InvalidCandidates)
+            );
+            assert_eq!(
+                product_factory.delegate_to_any(0, vec![accounts.bob, accounts.alice], None, None),
+                Err(Error::SelfDelegation)
+            );
+            assert_eq!(product_factory.delegate_to_any(0, vec![accounts.bob, accounts.charlie], None, None), Ok(()));
+            assert_eq!(product_factory.pending_for(accounts.bob), vec![0]);
+            assert_eq!(product_factory.pending_for(accounts.charlie), vec![0]);
+            assert_eq!(product_factory.delegated_by(accounts.alice), vec![(0, accounts.bob), (0, accounts.charlie)]);
+
+            // a non-candidate, and nothing pending on pid 1
+            set_sender(accounts.eve);
+            assert_eq!(product_factory.accept_product(0), Err(Error::NotCandidate));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.accept_product(1), Err(Error::InvalidDelegate));
+
+            set_sender(accounts.charlie);
+            assert_eq!(product_factory.accept_product(0), Ok(()));
+            let p = product_factory.get_product(0).unwrap();
+            assert_eq!(p.get_owner(), accounts.charlie);
+            assert_eq!(p.get_candidates(), Vec::new());
+            assert_eq!(product_factory.pending_for(accounts.bob), Vec::<u32>::new());
+            assert_eq!(product_factory.pending_for(accounts.charlie), Vec::<u32>::new());
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.accept_product(0), Err(Error::InvalidDelegate));
+
+            // rejections drop candidates until none is left
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.delegate_to_any(1, vec![accounts.bob, accounts.django], None, None), Ok(()));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.reject_delegation(1), Ok(()));
+            assert_eq!(product_factory.get_product(1).unwrap().get_candidates(), vec![accounts.django]);
+            assert_eq!(product_factory.accept_product(1), Err(Error::NotCandidate));
+            set_sender(accounts.django);
+            assert_eq!(product_factory.reject_delegation(1), Ok(()));
+            assert_eq!(product_factory.get_product(1).unwrap().get_state(), State::Owned);
+            assert_eq!(product_factory.pending_for(accounts.django), Vec::<u32>::new());
+        }
+
+        #[ink::test]
+        fn multisig_delegation_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.create_product(1, None, None), Ok(0));
+            let signers = vec![accounts.bob, accounts.charlie, accounts.django];
+            assert_eq!(product_factory.delegate_product_multisig(0, signers.clone(), 0, None), Err(Error::InvalidThreshold));
+            assert_eq!(product_factory.delegate_product_multisig(0, signers.clone(), 4, None), Err(Error::InvalidThreshold));
+
+            // a revocation wipes partial confirmations
+            assert_eq!(product_factory.delegate_product_multisig(0, signers.clone(), 2, None), Ok(()));
+            assert_eq!(product_factory.pending_for(accounts.bob), vec![0]);
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.accept_product(0), Err(Error::InvalidDelegate));
+            assert_eq!(product_factory.confirm_acceptance(0), Ok(()));
+            assert_eq!(product_factory.multisig_of(0).unwrap().confirmations, vec![accounts.bob]);
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.revoke_delegation(0), Ok(()));
+            assert_eq!(product_factory.multisig_of(0), None);
+            assert_eq!(product_factory.pending_for(accounts.bob), Vec::<u32>::new());
+            set_sender(accounts.charlie);
+            assert_eq!(product_factory.confirm_acceptance(0), Err(Error::InvalidState));
+
+            // 2-of-3 to an explicit recipient
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.delegate_product_multisig(0, signers.clone(), 2, Some(accounts.eve)), Ok(()));
+            assert_eq!(product_factory.delegated_by(accounts.alice), vec![(0, accounts.eve)]);
+            assert_eq!(product_factory.pending_for(accounts.eve), vec![0]);
+            assert_eq!(product_factory.pending_for(accounts.bob), Vec::<u32>::new());
+            set_sender(accounts.frank);
+            assert_eq!(product_factory.confirm_acceptance(0), Err(Error::NotSigner));
+            set_sender(accounts.charlie);
+            assert_eq!(product_factory.confirm_acceptance(0), Ok(()));
+            assert_eq!(product_factory.confirm_acceptance(0), Err(Error::AlreadyConfirmed));
+            assert_eq!(product_factory.get_product(0).unwrap().get_owner(), accounts.alice);
+            set_sender(accounts.django);
+            assert_eq!(product_factory.confirm_acceptance(0), Ok(()));
+            let p = product_factory.get_product(0).unwrap();
+            assert_eq!((p.get_owner(), p.get_state()), (accounts.eve, State::Owned));
+            assert_eq!(product_factory.multisig_of(0), None);
+            assert_eq!(product_factory.products_of(accounts.eve, false), vec![0]);
+            assert_eq!(product_factory.pending_for(accounts.eve), Vec::<u32>::new());
+
+            // a recall drops the pending delegation as well
+            set_sender(accounts.eve);
+            assert_eq!(product_factory.delegate_product_multisig(0, signers, 2, None), Ok(()));
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.recall_product(0), Ok(()));
+            assert_eq!(product_factory.pending_for(accounts.bob), Vec::<u32>::new());
+        }
+
+        #[ink::test]
+        fn co_ownership_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.create_product(1, None, None), Ok(0));
+            assert_eq!(product_factory.shares_of(0), vec![(accounts.alice, FULL_SHARE_BPS)]);
+            assert_eq!(product_factory.add_co_owner(0, accounts.bob, FULL_SHARE_BPS), Err(Error::InsufficientShare));
+            assert_eq!(product_factory.add_co_owner(0, accounts.bob, 0), Err(Error::InvalidShare));
+            assert_eq!(product_factory.add_co_owner(0, accounts.bob, 4000), Ok(()));
+            assert_eq!(product_factory.add_co_owner(0, accounts.bob, 1000), Err(Error::AlreadyCoOwner));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.add_co_owner(0, accounts.charlie, 1000), Err(Error::InvalidOwner));
+            assert_eq!(product_factory.transfer_share(0, accounts.charlie, 4001), Err(Error::InsufficientShare));
+            assert_eq!(product_factory.transfer_share(0, accounts.charlie, 1500), Ok(()));
+            assert_eq!(
+                product_factory.shares_of(0),
+                vec![(accounts.alice, 6000), (accounts.bob, 2500), (accounts.charlie, 1500)]
+            );
+            let total: u16 = product_factory.shares_of(0).iter().map(|(_, share)| share).sum();
+            assert_eq!(total, FULL_SHARE_BPS);
+
+            // charlie gives its whole share back and stops being a co-owner
+            set_sender(accounts.charlie);
+            assert_eq!(product_factory.transfer_share(0, accounts.alice, 1500), Ok(()));
+            assert_eq!(product_factory.share_of(0, accounts.charlie), 0);
+            assert_eq!(product_factory.approve_action(0), Err(Error::NotCoOwner));
+
+            // delegation waits for every co-owner
+            set_sender(accounts.alice);
+            assert_eq!(
+                product_factory.delegate_product(0, accounts.django, None, None, None),
+                Err(Error::CoOwnerApprovalMissing)
+            );
+            assert_eq!(product_factory.approve_action(0), Ok(()));
+            assert_eq!(product_factory.transfer_product(0, accounts.django), Err(Error::CoOwnerApprovalMissing));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.approve_action(0), Ok(()));
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.delegate_product(0, accounts.django, None, None, None), Ok(()));
+            set_sender(accounts.django);
+            assert_eq!(product_factory.accept_product(0), Ok(()));
+            assert_eq!(product_factory.shares_of(0), vec![(accounts.django, FULL_SHARE_BPS)]);
+            assert_eq!(product_factory.share_of(0, accounts.bob), 0);
+
+            // handing the last share back makes the owner sole owner again
+            assert_eq!(product_factory.add_co_owner(0, accounts.eve, 100), Ok(()));
+            set_sender(accounts.eve);
+            assert_eq!(product_factory.transfer_share(0, accounts.django, 100), Ok(()));
+            set_sender(accounts.django);
+            assert_eq!(product_factory.transfer_product(0, accounts.eve), Ok(()));
+        }
+
+        #[ink::test]
+        fn lending_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.create_product(1, None, None), Ok(0));
+            assert_eq!(product_factory.lend_product(0, accounts.bob, 0), Err(Error::InvalidDuration));
+            assert_eq!(product_factory.lend_product(0, accounts.alice, 10), Err(Error::SelfDelegation));
+
+            // early return by the borrower
+            assert_eq!(product_factory.lend_product(0, accounts.bob, 10), Ok(()));
+            let p = product_factory.get_product(0).unwrap();
+            assert_eq!((p.get_state(), p.get_owner(), p.get_custodian()), (State::Lent, accounts.alice, Some(accounts.bob)));
+            assert_eq!(product_factory.transfer_product(0, accounts.charlie), Err(Error::ProductLent));
+            assert_eq!(product_factory.delegate_product(0, accounts.charlie, None, None, None), Err(Error::ProductLent));
+            assert_eq!(product_factory.list_for_sale(0, 10), Err(Error::ProductLent));
+            assert_eq!(product_factory.burn_product(0), Err(Error::ProductLent));
+            assert_eq!(product_factory.reclaim_product(0), Err(Error::LoanNotExpired));
+            assert_eq!(product_factory.recall_product(0), Err(Error::ProductLent));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.transfer_product(0, accounts.charlie), Err(Error::InvalidOwner));
+            assert_eq!(product_factory.burn_product(0), Err(Error::InvalidOwner));
+            set_sender(accounts.charlie);
+            assert_eq!(product_factory.return_product(0), Err(Error::NotBorrower));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.return_product(0), Ok(()));
+            let p = product_factory.get_product(0).unwrap();
+            assert_eq!((p.get_state(), p.get_custodian(), p.get_lent_until()), (State::Owned, None, None));
+            assert_eq!(product_factory.return_product(0), Err(Error::InvalidState));
+
+            // forced reclaim once the loan ran out
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.lend_product(0, accounts.bob, 2), Ok(()));
+            advance_block_by(2);
+            assert_eq!(product_factory.reclaim_product(0), Err(Error::LoanNotExpired));
+            advance_block_by(1);
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.reclaim_product(0), Err(Error::InvalidOwner));
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.reclaim_product(0), Ok(()));
+            assert_eq!(product_factory.get_product(0).unwrap().get_state(), State::Owned);
+            assert_eq!(product_factory.transfer_product(0, accounts.charlie), Ok(()));
+        }
+
+        #[ink::test]
+        fn delegation_memo_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.create_product(1, None, None), Ok(0));
+            assert_eq!(
+                product_factory.delegate_product(0, accounts.bob, None, None, Some(vec![b'x'; MAX_MEMO_LEN + 1])),
+                Err(Error::MemoTooLong)
+            );
+            assert_eq!(product_factory.get_product(0).unwrap().get_state(), State::Owned);
+            let memo = vec![b'x'; MAX_MEMO_LEN];
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, None, None, Some(memo.clone())), Ok(()));
+            assert_eq!(product_factory.pending_memo(0), Ok(Some(memo.clone())));
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events.last().unwrap().data[..])
+                .expect("Cannot decode event");
+            if let Event::ProductDelegated(ProductDelegated { memo: emitted, .. }) = decoded {
+                assert_eq!(emitted, Some(memo));
+            } else {
+                panic!("Expected a ProductDelegated event")
+            }
+
+            // revoke, reject and accept all clear it
+            assert_eq!(product_factory.revoke_delegation(0), Ok(()));
+            assert_eq!(product_factory.pending_memo(0), Ok(None));
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, None, None, Some(b"PO-2291, dock 4".to_vec())), Ok(()));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.reject_delegation(0), Ok(()));
+            assert_eq!(product_factory.pending_memo(0), Ok(None));
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, None, None, Some(b"PO-2291, dock 4".to_vec())), Ok(()));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.accept_product(0), Ok(()));
+            assert_eq!(product_factory.pending_memo(0), Ok(None));
+            assert_eq!(product_factory.pending_memo(1), Err(Error::PidNotExists));
+        }
+
+        #[ink::test]
+        fn warranty_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            let warranty_until = ink_env::block_timestamp::<Environment>().unwrap() + 10;
+            assert_eq!(product_factory.create_product(1, Some(2), Some(warranty_until)), Ok(0));
+            assert_eq!(product_factory.create_product(2, None, None), Ok(1));
+            assert!(product_factory.is_under_warranty(0));
+            assert!(!product_factory.is_under_warranty(1));
+            let claim_hash = Hash::from([0x01; 32]);
+            assert_eq!(product_factory.claim_warranty(1, claim_hash), Err(Error::NoWarranty));
+            assert_eq!(product_factory.split_product(0, 1), Ok(2));
+            assert_eq!(product_factory.get_product(2).unwrap().get_warranty_until(), Some(warranty_until));
+
+            // the owner claims, not the creator
+            assert_eq!(product_factory.transfer_product(0, accounts.bob), Ok(()));
+            assert_eq!(product_factory.claim_warranty(0, claim_hash), Err(Error::InvalidOwner));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.set_warranty(0, None), Err(Error::NotCreator));
+            assert_eq!(product_factory.claim_warranty(0, claim_hash), Ok(0));
+            let claimed_at = ink_env::block_timestamp::<Environment>().unwrap();
+
+            // claiming right at the expiry is still allowed
+            advance_block_by(2);
+            assert_eq!(ink_env::block_timestamp::<Environment>().unwrap(), warranty_until);
+            assert_eq!(product_factory.claim_warranty(0, Hash::from([0x02; 32])), Ok(1));
+            advance_block_by(1);
+            assert!(!product_factory.is_under_warranty(0));
+            assert_eq!(product_factory.claim_warranty(0, Hash::from([0x03; 32])), Err(Error::WarrantyExpired));
+            assert_eq!(
+                product_factory.warranty_claims_of(0),
+                vec![(accounts.bob, claimed_at, claim_hash), (accounts.bob, warranty_until, Hash::from([0x02; 32]))]
+            );
+
+            // the creator extends it
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.set_warranty(0, Some(warranty_until + 100)), Ok(()));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.claim_warranty(0, Hash::from([0x03; 32])), Ok(2));
+
+            // the claims outlive the product
+            assert_eq!(product_factory.burn_product(0), Ok(()));
+            assert_eq!(product_factory.warranty_claims_of(0).len(), 3);
+        }
+
+        #[ink::test]
+        fn stage_order_test() {
+            assert_eq!(Stage::Manufactured.next(), Ok(Stage::Shipped));
+            assert_eq!(Stage::Shipped.next(), Ok(Stage::Delivered));
+            assert_eq!(Stage::Delivered.next(), Ok(Stage::Sold));
+            assert_eq!(Stage::Sold.next(), Err(Error::FinalStage));
+        }
+
+        #[ink::test]
+        fn advance_stage_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.create_product(1, None, None), Ok(0));
+            assert_eq!(product_factory.get_product(0).unwrap().get_stage(), Stage::Manufactured);
+            assert_eq!(product_factory.advance_stage(0), Ok(()));
+
+            // delegation works at any stage and the stage follows the product
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, None, None, None), Ok(()));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.advance_stage(0), Err(Error::InvalidOwner));
+            assert_eq!(product_factory.accept_product(0), Ok(()));
+            assert_eq!(product_factory.get_product(0).unwrap().get_stage(), Stage::Shipped);
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.advance_stage(0), Err(Error::InvalidOwner));
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.advance_stage(0), Ok(()));
+            assert_eq!(product_factory.advance_stage(0), Ok(()));
+            assert_eq!(product_factory.get_product(0).unwrap().get_stage(), Stage::Sold);
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events.last().unwrap().data[..])
+                .expect("Cannot decode event");
+            if let Event::StageAdvanced(StageAdvanced { pid, from, to }) = decoded {
+                assert_eq!((pid, from, to), (0, Stage::Delivered, Stage::Sold));
+            } else {
+                panic!("Expected a StageAdvanced event")
+            }
+            assert_eq!(product_factory.advance_stage(0), Err(Error::FinalStage));
+            assert_eq!(product_factory.get_product(0).unwrap().get_stage(), Stage::Sold);
+        }
+
+        #[ink::test]
+        fn checkpoint_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.create_product(1, None, None), Ok(0));
+            assert_eq!(product_factory.add_checkpoint(0, b"factory".to_vec()), Ok(()));
+            let packed_at = ink_env::block_timestamp::<Environment>().unwrap();
+            assert_eq!(
+                product_factory.add_checkpoint(0, vec![b'x'; MAX_LOCATION_LEN + 1]),
+                Err(Error::LocationTooLong)
+            );
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.add_checkpoint(0, b"depot".to_vec()), Err(Error::NotOwnerOrDelegate));
+
+            // the delegate logs the route while the delegation is pending
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, None, None, None), Ok(()));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.add_checkpoint(0, vec![b'x'; MAX_LOCATION_LEN]), Ok(()));
+            advance_block_by(1);
+            assert_eq!(product_factory.add_checkpoint(0, b"dock 4".to_vec()), Ok(()));
+            let docked_at = ink_env::block_timestamp::<Environment>().unwrap();
+            set_sender(accounts.charlie);
+            assert_eq!(product_factory.add_checkpoint(0, b"depot".to_vec()), Err(Error::NotOwnerOrDelegate));
+
+            assert_eq!(product_factory.checkpoint_count(0), 3);
+            assert_eq!(product_factory.checkpoint_at(0, 0), Some((accounts.alice, packed_at, b"factory".to_vec())));
+            assert_eq!(product_factory.checkpoint_at(0, 2), Some((accounts.bob, docked_at, b"dock 4".to_vec())));
+            assert_eq!(product_factory.checkpoint_at(0, 3), None);
+            assert_eq!(product_factory.checkpoint_count(1), 0);
+        }
+
+        #[ink::test]
+        fn sensor_readings_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.create_product(1, None, None), Ok(0));
+            assert_eq!(product_factory.record_reading(0, 4, 0), Err(Error::NotSensor));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.set_sensor(0, accounts.eve), Err(Error::InvalidOwner));
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.set_sensor(0, accounts.eve), Ok(()));
+            assert_eq!(product_factory.set_reading_range(0, Some((8, 2))), Err(Error::InvalidRange));
+            assert_eq!(product_factory.set_reading_range(0, Some((2, 8))), Ok(()));
+            assert_eq!(product_factory.set_max_readings(3), Ok(()));
+            assert_eq!(product_factory.record_reading(0, 4, 0), Err(Error::NotSensor));
+
+            set_sender(accounts.eve);
+            let events_before = ink_env::test::recorded_events().count();
+            for value in [2, 8, 5].iter(){
+                assert_eq!(product_factory.record_reading(0, *value, 0), Ok(()));
+            }
+            assert_eq!(ink_env::test::recorded_events().count(), events_before);
+            assert_eq!(product_factory.record_reading(0, 9, 0), Ok(()));
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(emitted_events.len(), events_before + 1);
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events.last().unwrap().data[..])
+                .expect("Cannot decode event");
+            if let Event::ReadingOutOfRange(ReadingOutOfRange { pid, value, min, max, .. }) = decoded {
+                assert_eq!((pid, value, min, max), (0, 9, 2, 8));
+            } else {
+                panic!("Expected a ReadingOutOfRange event")
+            }
+
+            // the fourth reading evicted the first one
+            let values = |product_factory: &ProductFactory, offset, limit| {
+                product_factory.readings_of(0, offset, limit).iter().map(|(_, value, _)| *value).collect::<Vec<_>>()
+            };
+            assert_eq!(product_factory.reading_count(0), 3);
+            assert_eq!(values(&product_factory, 0, 10), vec![8, 5, 9]);
+            assert_eq!(values(&product_factory, 1, 1), vec![5]);
+            assert_eq!(product_factory.readings.get(&(0, 0)), None);
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.set_max_readings(2), Ok(()));
+            assert_eq!(values(&product_factory, 0, 10), vec![5, 9]);
+        }
+
+        #[ink::test]
+        fn oracle_verification_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.create_product(1, None, None), Ok(0));
+            assert_eq!(product_factory.request_verification(0), Err(Error::OracleNotSet));
+            assert_eq!(product_factory.set_oracle(accounts.eve, true), Ok(()));
+            let report_hash = Hash::from([0x01; 32]);
+
+            // passed
+            assert_eq!(product_factory.request_verification(0), Ok(()));
+            assert_eq!(product_factory.get_product(0).unwrap().get_state(), State::PendingVerification);
+            assert_eq!(product_factory.transfer_product(0, accounts.bob), Err(Error::VerificationPending));
+            assert_eq!(product_factory.submit_verification(0, true, report_hash), Err(Error::NotOracle));
+            set_sender(accounts.eve);
+            assert_eq!(product_factory.submit_verification(0, true, report_hash), Ok(()));
+            assert_eq!(product_factory.submit_verification(0, true, report_hash), Err(Error::InvalidState));
+            assert_eq!(product_factory.verification_of(0), Ok(Some((true, report_hash))));
+            let p = product_factory.get_product(0).unwrap();
+            assert_eq!((p.get_state(), p.is_frozen()), (State::Owned, false));
+
+            // failed, freezing the product
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.request_verification(0), Ok(()));
+            set_sender(accounts.eve);
+            let failed_hash = Hash::from([0x02; 32]);
+            assert_eq!(product_factory.submit_verification(0, false, failed_hash), Ok(()));
+            assert_eq!(product_factory.verification_of(0), Ok(Some((false, failed_hash))));
+            let p = product_factory.get_product(0).unwrap();
+            assert_eq!((p.get_state(), p.is_frozen()), (State::Owned, true));
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events[emitted_events.len() - 2].data[..])
+                .expect("Cannot decode event");
+            if let Event::VerificationFailed(VerificationFailed { pid, frozen, .. }) = decoded {
+                assert_eq!((pid, frozen), (0, true));
+            } else {
+                panic!("Expected a VerificationFailed event")
+            }
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.transfer_product(0, accounts.bob), Err(Error::ProductFrozen));
+        }
+
+        #[ink::test]
+        fn create_lot_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.create_lot(1, 0), Err(Error::InvalidQuantity));
+            assert_eq!(product_factory.create_lot(1, MAX_BATCH_SIZE as u32 + 1), Err(Error::BatchTooLarge));
+            assert_eq!(product_factory.create_lot(1, 3), Ok((0, vec![0, 1, 2])));
+            assert_eq!(product_factory.create_lot(1, 2), Err(Error::DuplicateCode));
+            assert_eq!(product_factory.create_lot(2, 2), Ok((1, vec![3, 4])));
+            assert_eq!(product_factory.get_product(1).unwrap().get_lot_id(), Some(0));
+            assert_eq!(product_factory.get_products_by_code(1), vec![0, 1, 2]);
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            let decoded = <Event as scale::Decode>::decode(&mut &emitted_events.last().unwrap().data[..])
+                .expect("Cannot decode event");
+            if let Event::ProductCreated(ProductCreated { pid, lot_id, .. }) = decoded {
+                assert_eq!((pid, lot_id), (4, Some(1)));
+            } else {
+                panic!("Expected a ProductCreated event")
+            }
+
+            // membership survives transfers, burned units leave the run
+            assert_eq!(product_factory.transfer_product(1, accounts.bob), Ok(()));
+            assert_eq!(product_factory.burn_product(2), Ok(()));
+            assert_eq!(product_factory.products_in_lot(0), vec![0, 1]);
+            assert_eq!(product_factory.products_in_lot(1), vec![3, 4]);
+            assert_eq!(product_factory.products_in_lot(2), Vec::<u32>::new());
+            assert_eq!(product_factory.create_product(3, None, None), Ok(5));
+            assert_eq!(product_factory.get_product(5).unwrap().get_lot_id(), None);
+        }
+
+        #[ink::test]
+        fn notes_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.create_product(1, None, None), Ok(0));
+            assert_eq!(product_factory.add_note(0, vec![b'x'; MAX_NOTE_LEN + 1]), Err(Error::NoteTooLong));
+            assert_eq!(product_factory.add_note(0, vec![b'x'; MAX_NOTE_LEN]), Ok(0));
+            let noted_at = ink_env::block_timestamp::<Environment>().unwrap();
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.add_note(0, b"unit repaired 2024-03".to_vec()), Err(Error::InvalidOwner));
+
+            // notes stay behind for the new owner
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.transfer_product(0, accounts.bob), Ok(()));
+            assert_eq!(product_factory.add_note(0, b"unit repaired 2024-03".to_vec()), Err(Error::InvalidOwner));
+            set_sender(accounts.bob);
+            for index in 1..MAX_NOTES{
+                assert_eq!(product_factory.add_note(0, b"unit repaired 2024-03".to_vec()), Ok(index));
+            }
+            assert_eq!(product_factory.add_note(0, Vec::new()), Err(Error::TooManyNotes));
+            assert_eq!(product_factory.note_count(0), MAX_NOTES);
+            assert_eq!(product_factory.note_at(0, 0), Some((accounts.alice, noted_at, vec![b'x'; MAX_NOTE_LEN])));
+            assert_eq!(product_factory.note_at(0, 1).map(|(author, _, note)| (author, note)), Some((accounts.bob, b"unit repaired 2024-03".to_vec())));
+            assert_eq!(product_factory.note_at(0, MAX_NOTES), None);
+        }
+
+        #[ink::test]
+        fn state_changed_event_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.create_product(1, None, None).unwrap();
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, None, None, None), Ok(()));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.accept_product(0), Ok(()));
+            assert_eq!(product_factory.list_for_sale(0, 100), Ok(()));
+            assert_eq!(product_factory.unlist(0), Ok(()));
+            // a transfer keeps the product owned, so no state change is recorded
+            assert_eq!(product_factory.transfer_product(0, accounts.charlie), Ok(()));
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.recall_product(0), Ok(()));
+            assert_eq!(product_factory.resolve_recall(0), Ok(()));
+            // freezing is not a state
+            assert_eq!(product_factory.freeze_product(0), Ok(()));
+            assert_eq!(product_factory.unfreeze_product(0), Ok(()));
+            // failed calls record nothing either
+            assert_eq!(product_factory.resolve_recall(0), Err(Error::InvalidState));
+
+            let changes = ink_env::test::recorded_events()
+                .filter_map(|ev| match <Event as scale::Decode>::decode(&mut &ev.data[..]) {
+                    Ok(Event::StateChanged(StateChanged { pid, from, to, actor })) => Some((pid, from, to, actor)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            let (owned, pending, recalled, listed) = (
+                State::Owned as u8,
+                State::PendingDelegation as u8,
+                State::Recalled as u8,
+                State::Listed as u8,
+            );
+            assert_eq!(
+                changes,
+                vec![
+                    (0, owned, pending, accounts.alice),
+                    (0, pending, owned, accounts.bob),
+                    (0, owned, listed, accounts.bob),
+                    (0, listed, owned, accounts.bob),
+                    (0, owned, recalled, accounts.alice),
+                    (0, recalled, owned, accounts.alice),
+                ]
+            );
+        }
+
+        #[ink::test]
+        fn reentrancy_guard_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.create_products(vec![1, 2]).unwrap();
+            assert_eq!(product_factory.list_for_sale(1, 100), Ok(()));
+
+            // a call entering while an outgoing call is in flight
+            let guard = ink_primitives::Key::from(REENTRANCY_GUARD_KEY);
+            ink_env::set_contract_storage(&guard, &true);
+            assert_eq!(product_factory.create_product(3, None, None), Err(Error::ReentrancyDetected));
+            assert_eq!(product_factory.transfer_product(0, accounts.bob), Err(Error::ReentrancyDetected));
+            assert_eq!(product_factory.withdraw(), Err(Error::ReentrancyDetected));
+            assert_eq!(product_factory.withdraw_fees(accounts.alice, 0), Err(Error::ReentrancyDetected));
+            set_sender_paying(accounts.bob, 100);
+            assert_eq!(product_factory.buy_product(1), Err(Error::ReentrancyDetected));
+            assert_eq!(product_factory.get_product(1).unwrap().owner, accounts.alice);
+            // messages without transfers or calls are not guarded
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, None, None, None), Ok(()));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.accept_product(0), Err(Error::ReentrancyDetected));
+
+            ink_env::clear_contract_storage(&guard);
+            assert_eq!(product_factory.accept_product(0), Ok(()));
+            // the guard is released on errors as well
+            assert_eq!(product_factory.withdraw(), Err(Error::NothingToWithdraw));
+            assert_eq!(ink_env::get_contract_storage::<bool>(&guard), Ok(None));
+            assert_eq!(product_factory.transfer_product(0, accounts.charlie), Ok(()));
+            assert_eq!(ink_env::get_contract_storage::<bool>(&guard), Ok(None));
+        }
+
+        #[ink::test]
+        fn failed_payment_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let contract = ink_env::account_id::<Environment>().expect("Cannot get contract id");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.create_product(1, None, None).unwrap();
+            assert_eq!(product_factory.list_for_sale(0, 100), Ok(()));
+
+            // the contract cannot pay the proceeds out
+            set_sender_with_value(accounts.bob, 100);
+            assert_eq!(product_factory.buy_product(0), Ok(()));
+            assert_eq!(product_factory.withdrawable_of(accounts.alice), 100);
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.withdraw(), Err(Error::TransferFailed));
+            assert_eq!(product_factory.withdrawable_of(accounts.alice), 100);
+            assert_eq!(product_factory.escrowed, 100);
+            assert_eq!(product_factory.withdraw_fees(accounts.alice, 50), Err(Error::TransferFailed));
+
+            test::set_account_balance::<Environment>(contract, 120)
+                .expect("Cannot set contract balance");
+            test::set_account_balance::<Environment>(accounts.alice, 0)
+                .expect("Cannot set seller balance");
+            assert_eq!(product_factory.withdraw(), Ok(()));
+            assert_eq!(test::get_account_balance::<Environment>(accounts.alice), Ok(100));
+            assert_eq!((product_factory.withdrawable_of(accounts.alice), product_factory.escrowed), (0, 0));
+        }
+
+        #[ink::test]
+        fn cold_metadata_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.create_product_with_metadata(1, b"bike".to_vec(), b"red".to_vec()), Ok(0));
+            assert_eq!(product_factory.set_metadata_uri(0, b"ipfs://bike".to_vec()), Ok(()));
+            product_factory.create_product(2, None, None).unwrap();
+
+            // the product entries have no room for it, it lives in the cold maps
+            assert_eq!(product_factory.metadata.get(&0), Some(&(b"bike".to_vec(), b"red".to_vec())));
+            assert_eq!(product_factory.metadata_uris.get(&0), Some(&b"ipfs://bike".to_vec()));
+            assert!(!product_factory.metadata.contains_key(&1));
+            assert!(!product_factory.metadata_uris.contains_key(&1));
+
+            // the lifecycle does not need the cold maps
+            assert_eq!(product_factory.delegate_product(1, accounts.bob, None, None, None), Ok(()));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.accept_product(1), Ok(()));
+            assert_eq!(product_factory.list_for_sale(1, 0), Ok(()));
+            set_sender(accounts.charlie);
+            assert_eq!(product_factory.buy_product(1), Ok(()));
+            assert_eq!(product_factory.transfer_product(1, accounts.django), Ok(()));
+            let product = product_factory.get_product(1).unwrap();
+            assert_eq!((product.owner, product.get_name(), product.get_metadata_uri()), (accounts.django, Vec::new(), None));
+            assert_eq!(product_factory.get_metadata_uri(1), Ok(None));
+            assert_eq!(product_factory.get_metadata_uri(2), Err(Error::PidNotExists));
+
+            // and leaves them alone when the product changes hands
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.transfer_product(0, accounts.bob), Ok(()));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.delegate_product(0, accounts.charlie, None, None, None), Ok(()));
+            set_sender(accounts.charlie);
+            assert_eq!(product_factory.accept_product(0), Ok(()));
+            let product = product_factory.get_product(0).unwrap();
+            assert_eq!(product.owner, accounts.charlie);
+            assert_eq!((product.get_name(), product.get_description()), (b"bike".to_vec(), b"red".to_vec()));
+            assert_eq!(product.get_metadata_uri(), Some(b"ipfs://bike".to_vec()));
+            // every message returns the same view
+            assert_eq!(product_factory.get_products(0, 1, false)[0].1, product);
+            assert_eq!(product_factory.export_products(0, 1).0[0].1, product);
+            assert_eq!(product_factory.get_product_by_id(product_factory.id_of(0).unwrap()), Ok(product));
+            assert_eq!(product_factory.get_last(), product_factory.get_product(1).ok());
+
+            // cleared names drop the entry, burning drops the rest
+            assert_eq!(product_factory.update_metadata(0, Vec::new(), Vec::new()), Ok(()));
+            assert!(!product_factory.metadata.contains_key(&0));
+            assert_eq!(product_factory.burn_product(0), Ok(()));
+            assert!(!product_factory.metadata_uris.contains_key(&0));
+        }
+
+        #[ink::test]
+        fn arithmetic_overflow_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.create_product(1, Some(10), None).unwrap();
+            product_factory.create_product(2, Some(10), None).unwrap();
+            assert_eq!(product_factory.split_product(1, 5), Ok(2));
+
+            // pids used up
+            let next_pid = product_factory.next_pid;
+            product_factory.next_pid = u32::MAX;
+            assert_eq!(product_factory.create_product(3, None, None), Err(Error::ArithmeticOverflow));
+            assert_eq!(product_factory.create_products(vec![3, 4]), Err(Error::ArithmeticOverflow));
+            assert_eq!(product_factory.create_lot(3, 2), Err(Error::ArithmeticOverflow));
+            assert_eq!(product_factory.split_product(0, 5), Err(Error::ArithmeticOverflow));
+            assert_eq!(product_factory.get_product(0).unwrap().quantity, 10);
+            assert_eq!(product_factory.products_of(accounts.alice, false), vec![0, 1, 2]);
+            assert!(!product_factory.code_taken.contains_key(&3));
+            assert_eq!(product_factory.next_lot_id, 0);
+            product_factory.next_pid = next_pid;
+
+            // and the nonce of a creator
+            product_factory.creation_nonces.insert(accounts.alice, u64::MAX);
+            assert_eq!(product_factory.create_product(3, None, None), Err(Error::ArithmeticOverflow));
+            assert_eq!(product_factory.product_count(), 3);
+            product_factory.creation_nonces.insert(accounts.alice, 3);
+
+            // quantities
+            product_factory.products.get_mut(&1).unwrap().quantity = u32::MAX;
+            assert_eq!(product_factory.merge_products(1, 2), Err(Error::ArithmeticOverflow));
+            assert_eq!(product_factory.get_product(2).unwrap().quantity, 5);
+
+            // append-only logs
+            product_factory.checkpoint_counts.insert(0, u32::MAX);
+            assert_eq!(product_factory.add_checkpoint(0, b"Rotterdam".to_vec()), Err(Error::ArithmeticOverflow));
+            assert_eq!(product_factory.checkpoint_at(0, u32::MAX), None);
+            assert_eq!(product_factory.set_sensor(0, accounts.bob), Ok(()));
+            product_factory.reading_counts.insert(0, u32::MAX);
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.record_reading(0, 4, 0), Err(Error::ArithmeticOverflow));
+
+            // escrowed balances
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.list_for_sale(0, 100), Ok(()));
+            product_factory.escrowed = Balance::MAX - 99;
+            set_sender_paying(accounts.bob, 100);
+            assert_eq!(product_factory.buy_product(0), Err(Error::ArithmeticOverflow));
+            let product = product_factory.get_product(0).unwrap();
+            assert_eq!((product.state, product.owner), (State::Listed, accounts.alice));
+            assert_eq!(product_factory.withdrawable_of(accounts.alice), 0);
+            product_factory.escrowed = 0;
+            assert_eq!(product_factory.buy_product(0), Ok(()));
+            assert_eq!(product_factory.withdrawable_of(accounts.alice), 100);
+        }
+
+        #[ink::test]
+        fn max_products_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 4);
+            clear_transferred_value();
+            assert_eq!(product_factory.get_max_products(), 4);
+            assert_eq!(product_factory.create_products(vec![1, 2]), Ok(vec![0, 1]));
+            assert_eq!(product_factory.create_lot(3, 3), Err(Error::MaxProductsReached));
+            assert_eq!(product_factory.create_product(3, Some(2), None), Ok(2));
+            assert_eq!(product_factory.split_product(2, 1), Ok(3));
+            assert_eq!(product_factory.create_product(4, None, None), Err(Error::MaxProductsReached));
+            assert_eq!(product_factory.create_product_with_metadata(4, Vec::new(), Vec::new()), Err(Error::MaxProductsReached));
+            assert_eq!(product_factory.create_products(vec![4]), Err(Error::MaxProductsReached));
+            assert_eq!(product_factory.split_product(2, 1), Err(Error::InsufficientQuantity));
+            assert_eq!(product_factory.product_count(), 4);
+
+            // the cap may not drop below the live products
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.set_max_products(5), Err(Error::NotAdmin));
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.set_max_products(3), Err(Error::MaxProductsTooLow));
+            assert_eq!(product_factory.set_max_products(5), Ok(()));
+            assert_eq!(product_factory.create_product(4, None, None), Ok(4));
+            assert_eq!(product_factory.create_product(5, None, None), Err(Error::MaxProductsReached));
+
+            // burned products free their place
+            assert_eq!(product_factory.burn_product(4), Ok(()));
+            assert_eq!(product_factory.set_max_products(4), Ok(()));
+            assert_eq!(product_factory.create_product(5, None, None), Err(Error::MaxProductsReached));
+            assert_eq!(product_factory.set_max_products(0), Ok(()));
+            assert_eq!(product_factory.create_lot(6, 10).map(|(_, pids)| pids.len()), Ok(10));
+            assert_eq!(product_factory.set_max_products(13), Err(Error::MaxProductsTooLow));
+            assert_eq!(product_factory.set_max_products(14), Ok(()));
+        }
+
+        #[ink::test]
+        fn max_per_owner_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.get_max_per_owner(), 0);
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.set_max_per_owner(2), Err(Error::NotAdmin));
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.set_max_per_owner(2), Ok(()));
+            assert_eq!(product_factory.get_max_per_owner(), 2);
+
+            // creation up to the limit
+            assert_eq!(product_factory.create_products(vec![1, 2, 3]), Err(Error::OwnerQuotaExceeded));
+            assert_eq!(product_factory.create_product(1, Some(2), None), Ok(0));
+            assert_eq!(product_factory.create_product(2, None, None), Ok(1));
+            assert_eq!(product_factory.create_product(3, None, None), Err(Error::OwnerQuotaExceeded));
+            assert_eq!(product_factory.create_lot(3, 1), Err(Error::OwnerQuotaExceeded));
+            assert_eq!(product_factory.split_product(0, 1), Err(Error::OwnerQuotaExceeded));
+            assert_eq!(product_factory.count_of(accounts.alice), 2);
+
+            // a full account cannot take on delegated products
+            product_factory.add_manufacturer(accounts.bob).unwrap();
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.create_products(vec![4, 5]), Ok(vec![2, 3]));
+            assert_eq!(product_factory.delegate_product(2, accounts.alice, None, None, None), Ok(()));
+            assert_eq!(product_factory.delegate_product(3, accounts.alice, None, None, None), Ok(()));
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.accept_product(2), Err(Error::OwnerQuotaExceeded));
+            assert_eq!(
+                product_factory.accept_products(vec![2]),
+                Err(Error::BatchItemFailed { pid: 2, error: Box::new(Error::OwnerQuotaExceeded) })
+            );
+            assert_eq!(product_factory.get_product(2).unwrap().owner, accounts.bob);
+
+            // handing a product over frees its place
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.transfer_product(1, accounts.charlie), Ok(()));
+            assert_eq!(product_factory.accept_product(2), Ok(()));
+            assert_eq!(product_factory.accept_product(3), Err(Error::OwnerQuotaExceeded));
+            assert_eq!(product_factory.transfer_product(0, accounts.bob), Ok(()));
+            assert_eq!(product_factory.accept_products(vec![3]), Ok(()));
+            set_sender(accounts.charlie);
+            assert_eq!(product_factory.transfer_product(1, accounts.alice), Err(Error::OwnerQuotaExceeded));
+            assert_eq!(product_factory.list_for_sale(1, 0), Ok(()));
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.buy_product(1), Err(Error::OwnerQuotaExceeded));
+            assert_eq!(product_factory.count_of(accounts.alice), 2);
+
+            assert_eq!(product_factory.set_max_per_owner(0), Ok(()));
+            assert_eq!(product_factory.buy_product(1), Ok(()));
+            assert_eq!(product_factory.count_of(accounts.alice), 3);
+        }
+
+        #[ink::test]
+        fn config_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(10, 50);
+            clear_transferred_value();
+            let mut config = Config {
+                creation_fee: 10,
+                max_products: 50,
+                max_per_owner: 0,
+                arbiter: None,
+                oracle: None,
+                registry: None,
+            };
+            assert_eq!(product_factory.get_config(), config);
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.set_creation_fee(1), Err(Error::NotAdmin));
+            assert_eq!(product_factory.set_max_products(1), Err(Error::NotAdmin));
+            assert_eq!(product_factory.set_max_per_owner(1), Err(Error::NotAdmin));
+            assert_eq!(product_factory.set_arbiter(accounts.bob), Err(Error::NotAdmin));
+            assert_eq!(product_factory.set_oracle(accounts.bob, false), Err(Error::NotAdmin));
+            assert_eq!(product_factory.set_registry(Some(accounts.bob)), Err(Error::NotAdmin));
+            assert_eq!(product_factory.get_config(), config);
+            assert_eq!(ink_env::test::recorded_events().count(), 0);
+
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.set_creation_fee(0), Ok(()));
+            config.creation_fee = 0;
+            assert_eq!(product_factory.get_config(), config);
+            assert_eq!(product_factory.create_product(1, None, None), Ok(0));
+            assert_eq!(product_factory.set_max_products(1), Ok(()));
+            config.max_products = 1;
+            assert_eq!(product_factory.get_config(), config);
+            assert_eq!(product_factory.create_product(2, None, None), Err(Error::MaxProductsReached));
+            assert_eq!(product_factory.set_max_per_owner(3), Ok(()));
+            config.max_per_owner = 3;
+            assert_eq!(product_factory.get_config(), config);
+            assert_eq!(product_factory.set_arbiter(accounts.charlie), Ok(()));
+            config.arbiter = Some(accounts.charlie);
+            assert_eq!(product_factory.get_config(), config);
+            assert_eq!(product_factory.set_oracle(accounts.django, false), Ok(()));
+            config.oracle = Some(accounts.django);
+            assert_eq!(product_factory.get_config(), config);
+            assert_eq!(product_factory.set_registry(Some(accounts.eve)), Ok(()));
+            config.registry = Some(accounts.eve);
+            assert_eq!(product_factory.get_config(), config);
+            assert_eq!(
+                (product_factory.get_arbiter(), product_factory.get_oracle(), product_factory.get_registry()),
+                (Some(accounts.charlie), Some(accounts.django), Some(accounts.eve))
+            );
+
+            let changes = ink_env::test::recorded_events()
+                .filter_map(|ev| match <Event as scale::Decode>::decode(&mut &ev.data[..]) {
+                    Ok(Event::ConfigChanged(ConfigChanged { field, admin })) => Some((field, admin)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            let fields = [
+                ConfigField::CreationFee,
+                ConfigField::MaxProducts,
+                ConfigField::MaxPerOwner,
+                ConfigField::Arbiter,
+                ConfigField::Oracle,
+                ConfigField::Registry,
+            ];
+            assert_eq!(changes, fields.iter().map(|field| (*field as u8, accounts.alice)).collect::<Vec<_>>());
+            assert_eq!(fields.iter().map(|field| *field as u8).collect::<Vec<_>>(), vec![0, 1, 2, 3, 4, 5]);
+        }
+
+        #[ink::test]
+        fn admin_action_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.create_product(1, None, None).unwrap();
+            let admin_actions = || {
+                ink_env::test::recorded_events()
+                    .filter_map(|ev| match <Event as scale::Decode>::decode(&mut &ev.data[..]) {
+                        Ok(Event::AdminAction(AdminAction { admin, action, subject, pid })) => {
+                            Some((admin, action, subject, pid))
+                        }
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+            };
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.pause(), Err(Error::NotAdmin));
+            assert_eq!(product_factory.freeze_product(0), Err(Error::NotAdmin));
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.freeze_product(9), Err(Error::PidNotExists));
+            assert_eq!(product_factory.set_max_readings(0), Err(Error::InvalidRange));
+            assert_eq!(admin_actions(), Vec::new());
+
+            assert_eq!(product_factory.pause(), Ok(()));
+            assert_eq!(product_factory.unpause(), Ok(()));
+            assert_eq!(product_factory.freeze_product(0), Ok(()));
+            assert_eq!(product_factory.add_manufacturer(accounts.bob), Ok(()));
+            assert_eq!(product_factory.set_blacklisted(accounts.eve, true), Ok(()));
+            // creators set their own royalty without an audit entry
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.create_product(2, None, None), Ok(1));
+            assert_eq!(product_factory.set_royalty(1, 50), Ok(()));
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.set_royalty(1, 100), Ok(()));
+            assert_eq!(product_factory.get_product(1).unwrap().royalty_bps, 100);
+            assert_eq!(product_factory.set_creation_fee(5), Ok(()));
+            assert_eq!(product_factory.transfer_admin(accounts.charlie), Ok(()));
+            set_sender(accounts.charlie);
+            assert_eq!(product_factory.accept_admin(), Ok(()));
+            assert_eq!(product_factory.set_registry_policy(RegistryPolicy::Revert), Ok(()));
+
+            let topics = ink_env::test::recorded_events()
+                .filter(|ev| matches!(<Event as scale::Decode>::decode(&mut &ev.data[..]), Ok(Event::AdminAction(_))))
+                .map(|ev| ev.topics.len())
+                .collect::<Vec<_>>();
+            assert_eq!(topics, vec![2; 10]);
+            assert_eq!(
+                admin_actions(),
+                vec![
+                    (accounts.alice, AdminActionKind::Pause as u8, None, None),
+                    (accounts.alice, AdminActionKind::Unpause as u8, None, None),
+                    (accounts.alice, AdminActionKind::FreezeProduct as u8, None, Some(0)),
+                    (accounts.alice, AdminActionKind::AddManufacturer as u8, Some(accounts.bob), None),
+                    (accounts.alice, AdminActionKind::SetBlacklisted as u8, Some(accounts.eve), None),
+                    (accounts.alice, AdminActionKind::SetRoyalty as u8, None, Some(1)),
+                    (accounts.alice, AdminActionKind::SetCreationFee as u8, None, None),
+                    (accounts.alice, AdminActionKind::TransferAdmin as u8, Some(accounts.charlie), None),
+                    (accounts.charlie, AdminActionKind::AcceptAdmin as u8, Some(accounts.alice), None),
+                    (accounts.charlie, AdminActionKind::SetRegistryPolicy as u8, None, None),
+                ]
+            );
+            assert_eq!((AdminActionKind::SetCreationFee as u8, AdminActionKind::SetRoyalty as u8), (0, 24));
+        }
+
+        #[ink::test]
+        fn contract_info_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 10);
+            clear_transferred_value();
+            let snapshot = |product_factory: &ProductFactory| ContractInfo {
+                admin: product_factory.get_admin(),
+                pending_admin: product_factory.get_pending_admin(),
+                product_count: product_factory.product_count(),
+                live_product_count: product_factory.products.len(),
+                paused: product_factory.is_paused(),
+                creation_fee: product_factory.get_creation_fee(),
+                max_products: product_factory.get_max_products(),
+                max_per_owner: product_factory.get_max_per_owner(),
+                schema_version: product_factory.get_schema_version(),
+                termination_announced_at: product_factory.termination_announced_at,
+            };
+            let info = product_factory.get_contract_info();
+            assert_eq!(info, snapshot(&product_factory));
+            assert_eq!((info.product_count, info.paused, info.schema_version), (0, false, SCHEMA_VERSION));
+
+            product_factory.create_products(vec![1, 2, 3]).unwrap();
+            assert_eq!(product_factory.burn_product(1), Ok(()));
+            assert_eq!(product_factory.set_max_per_owner(5), Ok(()));
+            assert_eq!(product_factory.transfer_admin(accounts.bob), Ok(()));
+            assert_eq!(product_factory.pause(), Ok(()));
+            assert_eq!(product_factory.announce_termination(), Ok(()));
+            assert_eq!(product_factory.set_creation_fee(7), Ok(()));
+
+            let info = product_factory.get_contract_info();
+            assert_eq!(info, snapshot(&product_factory));
+            assert_eq!(
+                (info.admin, info.pending_admin, info.product_count, info.live_product_count),
+                (accounts.alice, Some(accounts.bob), 3, 2)
+            );
+            assert_eq!((info.paused, info.creation_fee, info.max_products, info.max_per_owner), (true, 7, 10, 5));
+            assert!(info.termination_announced_at.is_some());
+        }
+
+        #[ink::test]
+        fn export_products_test() {
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.create_products((0..10).collect()).unwrap();
+            assert_eq!(product_factory.burn_product(2), Ok(()));
+            let pids = |page: &Vec<(u32, Product)>| page.iter().map(|(pid, _)| *pid).collect::<Vec<_>>();
+
+            let (page, next) = product_factory.export_products(0, 4);
+            assert_eq!(pids(&page), vec![0, 1, 3, 4]);
+            assert_eq!(page[2].1.get_code(), 3);
+            assert_eq!(next, Some(5));
+            // products burned between pages are skipped, not returned stale
+            assert_eq!(product_factory.burn_product(5), Ok(()));
+            assert_eq!(product_factory.burn_product(6), Ok(()));
+            let (page, next) = product_factory.export_products(next.unwrap(), 4);
+            assert_eq!(pids(&page), vec![7, 8, 9]);
+            assert_eq!(next, None);
+            // a product created after the walk started shows up on resuming
+            product_factory.create_product(10, None, None).unwrap();
+            assert_eq!(product_factory.export_products(9, 1), (vec![(9, product_factory.get_product(9).unwrap())], Some(10)));
+            let (page, next) = product_factory.export_products(10, 4);
+            assert_eq!((pids(&page), next), (vec![10], None));
+            assert_eq!(product_factory.export_products(50, 4), (Vec::new(), None));
+
+            let (page, next) = product_factory.export_products(0, u32::MAX);
+            assert_eq!(pids(&page), vec![0, 1, 3, 4, 7, 8, 9, 10]);
+            assert_eq!(next, None);
+        }
+
+        #[ink::test]
+        fn export_products_scan_test() {
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            let total = MAX_EXPORT_SCAN + 5;
+            product_factory.next_pid = total - 1;
+            product_factory.create_product(1, None, None).unwrap();
+            assert_eq!(product_factory.product_count(), total);
+            let (page, next) = product_factory.export_products(0, MAX_PAGE_SIZE * 2);
+            assert!(page.is_empty());
+            assert_eq!(next, Some(MAX_EXPORT_SCAN));
+            let (page, next) = product_factory.export_products(next.unwrap(), MAX_PAGE_SIZE * 2);
+            assert_eq!(page.len(), 1);
+            assert_eq!((page[0].0, next), (total - 1, None));
+        }
+
+        #[ink::test]
+        fn products_root_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            assert_eq!(product_factory.current_root(), None);
+            assert_eq!(product_factory.compute_products_root(0), Err(Error::InvalidRange));
+            assert_eq!(product_factory.compute_products_root(1), Ok(Some(Hash::default())));
+
+            product_factory.create_products((0..7).collect()).unwrap();
+            assert_eq!(product_factory.burn_product(3), Ok(()));
+            assert!(product_factory.current_root().unwrap().stale);
+
+            // three chunks of three pids each leave no pid behind
+            assert_eq!(product_factory.compute_products_root(3), Ok(None));
+            assert_eq!(product_factory.compute_products_root(3), Ok(None));
+            let root = product_factory.compute_products_root(3).unwrap().unwrap();
+            let current = product_factory.current_root().unwrap();
+            assert_eq!((current.root, current.block, current.stale), (root, 0, false));
+            assert_eq!(root, reference_root(&product_factory.export_products(0, MAX_PAGE_SIZE).0));
+            assert_eq!(product_factory.compute_products_root(1), Ok(Some(root)));
+
+            advance_block_by(2);
+            assert_eq!(product_factory.transfer_product(4, accounts.bob), Ok(()));
+            assert!(product_factory.current_root().unwrap().stale);
+            assert_eq!(product_factory.current_root().unwrap().root, root);
+            let transferred = product_factory.compute_products_root(MAX_ROOT_CHUNK).unwrap().unwrap();
+            assert_ne!(transferred, root);
+            assert_eq!(transferred, reference_root(&product_factory.export_products(0, MAX_PAGE_SIZE).0));
+            assert_eq!(product_factory.current_root().unwrap().block, 2);
+
+            // a change in the middle of a computation starts it over
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, None, None, None), Ok(()));
+            assert_eq!(product_factory.compute_products_root(4), Ok(None));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.accept_product(0), Ok(()));
+            assert_eq!(product_factory.root_build, None);
+            assert_eq!(product_factory.compute_products_root(4), Ok(None));
+            let accepted = product_factory.compute_products_root(4).unwrap().unwrap();
+            assert_eq!(accepted, reference_root(&product_factory.export_products(0, MAX_PAGE_SIZE).0));
+
+            // failing calls and writes outside the product entries keep it fresh
+            assert_eq!(product_factory.approve(0, accounts.charlie), Ok(()));
+            assert_eq!(product_factory.set_metadata_uri(0, b"ipfs://bike".to_vec()), Ok(()));
+            assert_eq!(product_factory.set_royalty(0, 100), Err(Error::NotCreator));
+            assert_eq!(product_factory.list_for_sale(1, 100), Err(Error::InvalidOwner));
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.freeze_product(3), Err(Error::PidNotExists));
+            assert_eq!(product_factory.recall_product(3), Err(Error::PidNotExists));
+            assert_eq!(product_factory.resolve_recall(1), Err(Error::InvalidState));
+            assert!(!product_factory.current_root().unwrap().stale);
+        }
+
+        /// Hashes the leaves of `products` level by level, carrying an odd
+        /// last node up unchanged.
+        fn reference_root(products: &[(u32, Product)]) -> Hash {
+            fn blake2(value: &impl scale::Encode) -> Hash {
+                let mut output = [0u8; 32];
+                ink_env::hash_encoded::<ink_env::hash::Blake2x256, _>(value, &mut output);
+                Hash::from(output)
+            }
+            let mut level = products
+                .iter()
+                .map(|(pid, p)| blake2(&(*pid, p.owner, p.code, p.state as u8)))
+                .collect::<Vec<_>>();
+            if level.is_empty() {
+                return Hash::default()
+            }
+            while level.len() > 1 {
+                level = level
+                    .chunks(2)
+                    .map(|pair| match pair {
+                        [left, right] => blake2(&(*left, *right)),
+                        _ => pair[0],
+                    })
+                    .collect();
+            }
+            level[0]
+        }
+
+        #[ink::test]
+        fn archive_product_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.create_products(vec![1, 2, 3]).unwrap();
+            assert_eq!(product_factory.delegate_product(2, accounts.bob, None, None, None), Ok(()));
+
+            assert_eq!(product_factory.archive_product(9), Err(Error::PidNotExists));
+            assert_eq!(product_factory.archive_product(2), Err(Error::InvalidState));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.archive_product(0), Err(Error::InvalidOwner));
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.archive_product(0), Ok(()));
+            assert!(product_factory.get_product(0).unwrap().is_archived());
+
+            assert_eq!(product_factory.products_of(accounts.alice, false), vec![1, 2]);
+            assert_eq!(product_factory.products_of(accounts.alice, true), vec![0, 1, 2]);
+            let pids = |page: Vec<(u32, Product)>| page.into_iter().map(|(pid, _)| pid).collect::<Vec<_>>();
+            assert_eq!(pids(product_factory.get_products(0, 10, false)), vec![1, 2]);
+            assert_eq!(pids(product_factory.get_products(0, 10, true)), vec![0, 1, 2]);
+            assert_eq!(product_factory.count_of(accounts.alice), 3);
+
+            assert_eq!(product_factory.transfer_product(0, accounts.bob), Err(Error::ProductArchived));
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, None, None, None), Err(Error::ProductArchived));
+            assert_eq!(product_factory.list_for_sale(0, 10), Err(Error::ProductArchived));
+            assert_eq!(product_factory.start_auction(0, 10, 5), Err(Error::ProductArchived));
+            assert_eq!(product_factory.lend_product(0, accounts.bob, 5), Err(Error::ProductArchived));
+            assert_eq!(product_factory.split_product(0, 1), Err(Error::ProductArchived));
+            assert_eq!(product_factory.get_product(0).unwrap().owner, accounts.alice);
+
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.unarchive_product(0), Err(Error::InvalidOwner));
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.unarchive_product(0), Ok(()));
+            assert_eq!(product_factory.products_of(accounts.alice, false), vec![0, 1, 2]);
+            assert_eq!(product_factory.transfer_product(0, accounts.bob), Ok(()));
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            let archived = emitted_events
+                .iter()
+                .filter_map(|ev| match <Event as scale::Decode>::decode(&mut &ev.data[..]) {
+                    Ok(Event::ProductArchived(ProductArchived { pid })) => Some((pid, true)),
+                    Ok(Event::ProductUnarchived(ProductUnarchived { pid })) => Some((pid, false)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(archived, vec![(0, true), (0, false)]);
+        }
+
+        #[ink::test]
+        fn owner_index_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            let holders = [accounts.alice, accounts.bob, accounts.charlie, accounts.django];
+            for holder in &holders[1..] {
+                product_factory.add_manufacturer(*holder).unwrap();
+            }
+            assert_eq!(product_factory.check_owner_index(), Ok(()));
+
+            // xorshift, seeded so a failure replays the same sequence
+            let mut seed = 0x2545_F491_u32;
+            let mut next = move |bound: u32| {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                seed % bound
+            };
+            let mut code = 0u16;
+            let mut changes = 0;
+            for _ in 0..300 {
+                let other = holders[next(4) as usize];
+                let pid = next(product_factory.product_count().max(1));
+                let owner = product_factory.get_product(pid).map(|p| p.owner).unwrap_or(accounts.alice);
+                let result = match next(8) {
+                    0 => {
+                        set_sender(other);
+                        code += 1;
+                        product_factory.create_product(code, Some(3), None).map(|_| ())
+                    }
+                    1 => {
+                        set_sender(other);
+                        code += 3;
+                        product_factory.create_products(vec![code - 2, code - 1, code]).map(|_| ())
+                    }
+                    2 => {
+                        set_sender(owner);
+                        product_factory
+                            .delegate_product(pid, other, None, None, None)
+                            .and_then(|_| {
+                                set_sender(other);
+                                product_factory.accept_product(pid)
+                            })
+                    }
+                    3 => {
+                        set_sender(owner);
+                        product_factory.delegate_products(vec![pid, pid + 1], other).and_then(|_| {
+                            set_sender(other);
+                            product_factory.accept_products(vec![pid, pid + 1])
+                        })
+                    }
+                    4 => {
+                        set_sender(owner);
+                        product_factory.transfer_product(pid, other)
+                    }
+                    5 => {
+                        set_sender(owner);
+                        product_factory.burn_product(pid)
+                    }
+                    6 => {
+                        set_sender(owner);
+                        product_factory.split_product(pid, 1).map(|_| ())
+                    }
+                    _ => {
+                        set_sender(owner);
+                        product_factory.list_for_sale(pid, 0).and_then(|_| {
+                            set_sender_with_value(other, 0);
+                            product_factory.buy_product(pid)
+                        })
+                    }
+                };
+                if result.is_ok() {
+                    changes += 1;
+                }
+                assert_eq!(product_factory.check_owner_index(), Ok(()));
+            }
+            assert!(changes > 100);
+
+            product_factory.owned_products.get_mut(&accounts.alice).unwrap().push(u32::MAX);
+            assert!(product_factory.check_owner_index().is_err());
+        }
+
+        #[ink::test]
+        fn product_of_owner_by_index_test() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut product_factory = ProductFactory::new(0, 0);
+            clear_transferred_value();
+            product_factory.create_products(vec![1, 2, 3, 4, 5]).unwrap();
+            let enumerate = |product_factory: &ProductFactory, owner: AccountId| {
+                (0..product_factory.count_of(owner))
+                    .map(|index| product_factory.product_of_owner_by_index(owner, index).unwrap())
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(enumerate(&product_factory, accounts.alice), vec![0, 1, 2, 3, 4]);
+            assert_eq!(product_factory.product_of_owner_by_index(accounts.alice, 5), Err(Error::IndexOutOfBounds));
+            assert_eq!(product_factory.product_of_owner_by_index(accounts.bob, 0), Err(Error::IndexOutOfBounds));
+
+            assert_eq!(product_factory.transfer_product(2, accounts.bob), Ok(()));
+            assert_eq!(enumerate(&product_factory, accounts.alice), vec![0, 1, 4, 3]);
+            assert_eq!(product_factory.product_of_owner_by_index(accounts.alice, 4), Err(Error::IndexOutOfBounds));
+            assert_eq!(enumerate(&product_factory, accounts.bob), vec![2]);
+
+            assert_eq!(product_factory.delegate_product(0, accounts.bob, None, None, None), Ok(()));
+            set_sender(accounts.bob);
+            assert_eq!(product_factory.accept_product(0), Ok(()));
+            set_sender(accounts.alice);
+            assert_eq!(product_factory.burn_product(4), Ok(()));
+            assert_eq!(enumerate(&product_factory, accounts.alice), vec![3, 1]);
+            assert_eq!(enumerate(&product_factory, accounts.bob), vec![2, 0]);
+            for owner in [accounts.alice, accounts.bob].iter() {
+                for pid in enumerate(&product_factory, *owner) {
+                    assert_eq!(product_factory.get_product(pid).unwrap().owner, *owner);
+                }
+            }
+        }
+
+        fn advance_block_by(blocks: u32) {
+            for _ in 0..blocks {
+                test::advance_block::<Environment>().expect("Cannot advance block");
+            }
+        }
+
+        /// The default off-chain context transfers 500 units with every call,
+        /// which the creation messages would try to refund from the empty
+        /// contract account.
+        fn clear_transferred_value() {
+            set_sender(ink_env::caller::<Environment>().expect("Cannot get caller"));
+        }
+
+        fn set_sender_with_value(sender: AccountId, value: Balance) {
+            let callee = ink_env::account_id::<ink_env::DefaultEnvironment>()
+                .unwrap_or_else(|_| [0x0; 32].into());
+            test::push_execution_context::<Environment>(
+                sender,
+                callee,
+                1000000,
+                value,
+                test::CallData::new(call::Selector::new([0x00; 4])), // dummy
+            );
+        }
+
+        /// Like `set_sender_with_value`, also crediting the contract with
+        /// `value` as the chain does before the message runs.
+        fn set_sender_paying(sender: AccountId, value: Balance) {
+            let contract = ink_env::account_id::<Environment>().expect("Cannot get contract id");
+            let balance = test::get_account_balance::<Environment>(contract).unwrap_or(0);
+            test::set_account_balance::<Environment>(contract, balance + value)
+                .expect("Cannot set contract balance");
+            set_sender_with_value(sender, value);
+        }
+
+        fn set_sender(sender: AccountId) {
+            let callee = ink_env::account_id::<ink_env::DefaultEnvironment>()
+                .unwrap_or_else(|_| [0x0; 32].into());
+            test::push_execution_context::<Environment>(
+                sender,
+                callee,
+                1000000,
+                0,
+                test::CallData::new(call::Selector::new([0x00; 4])), // dummy
+            );
+        }
+
+        
+    }
+}
+
+/// Pins the selector of every message so a rename or a dropped
+/// `selector` attribute shows up as a test failure. Lives outside the
+/// contract module since ink! rejects `__ink_` identifiers in there.
+#[cfg(test)]
+mod selector_tests {
+    use ink_prelude::{
+        string::String,
+        vec::Vec,
+    };
+
+    extern "Rust" {
+        fn __ink_generate_metadata() -> ink_metadata::InkProject;
+    }
+
+    const CONSTRUCTOR_SELECTORS: &[(&str, [u8; 4])] = &[
+        ("new", [0x9B, 0xAE, 0x9D, 0x5E]),
+    ];
+
+    const MESSAGE_SELECTORS: &[(&str, [u8; 4])] = &[
+        ("create_product", [0x3C, 0x62, 0x91, 0x0F]),
+        ("create_product_with_metadata", [0x04, 0x3C, 0x15, 0xB6]),
+        ("create_products", [0xB6, 0x8D, 0x74, 0x97]),
+        ("create_lot", [0xC1, 0x9A, 0x43, 0xC3]),
+        ("products_in_lot", [0x91, 0xE7, 0x19, 0x7D]),
+        ("get_config", [0xD0, 0x70, 0x78, 0x06]),
+        ("get_contract_info", [0x57, 0xF2, 0x22, 0x6D]),
+        ("get_creation_fee", [0xFD, 0x96, 0xB0, 0xAD]),
+        ("set_creation_fee", [0x78, 0xBC, 0x64, 0x2F]),
+        ("get_max_products", [0x8F, 0x93, 0x7E, 0xB0]),
+        ("set_max_products", [0x17, 0xEA, 0xF3, 0x2D]),
+        ("get_max_per_owner", [0xD7, 0xC7, 0x40, 0x9B]),
+        ("set_max_per_owner", [0x3F, 0xE3, 0x86, 0xE9]),
+        ("withdraw_fees", [0xF7, 0xE9, 0x2E, 0x05]),
+        ("withdraw", [0x41, 0x0F, 0xCC, 0x9D]),
+        ("withdrawable_of", [0xF8, 0x1D, 0x7F, 0x80]),
+        ("get_last", [0x9E, 0x29, 0x8B, 0x06]),
+        ("get_products", [0xDE, 0xC1, 0x60, 0xAC]),
+        ("export_products", [0xA6, 0x85, 0x5C, 0xBB]),
+        ("compute_products_root", [0xF0, 0xAC, 0xCF, 0x65]),
+        ("current_root", [0x51, 0x9C, 0x95, 0xCF]),
+        ("product_count", [0x24, 0x30, 0xDE, 0xE1]),
+        ("get_product", [0xF3, 0xAA, 0x8A, 0xC0]),
+        ("id_of", [0x60, 0x2A, 0xF9, 0x5C]),
+        ("pid_of", [0xC5, 0x46, 0xFA, 0x2E]),
+        ("get_product_by_id", [0xC3, 0x1B, 0xF2, 0xA6]),
+        ("get_creator", [0xF0, 0x8D, 0xFA, 0x6E]),
+        ("delegate_product", [0x5B, 0x92, 0x0A, 0xBE]),
+        ("pending_memo", [0xB4, 0xE6, 0x86, 0xA4]),
+        ("delegate_to_any", [0xC3, 0x20, 0xAD, 0x57]),
+        ("delegate_product_multisig", [0xF2, 0xA5, 0xF9, 0x22]),
+        ("approve", [0x68, 0x12, 0x66, 0xA0]),
+        ("set_approval_for_all", [0xCF, 0xD0, 0xC2, 0x7B]),
+        ("is_approved_for_all", [0x0F, 0x59, 0x22, 0xE9]),
+        ("get_approved", [0x27, 0x59, 0x2D, 0xEA]),
+        ("delegate_product_from", [0x42, 0xC0, 0xD6, 0xD4]),
+        ("delegate_products", [0xA9, 0x53, 0xC2, 0x4F]),
+        ("accept_product", [0xAE, 0x92, 0x35, 0x4A]),
+        ("accept_products", [0x85, 0xF8, 0xA0, 0xCD]),
+        ("confirm_acceptance", [0x3A, 0xC8, 0x8B, 0xB8]),
+        ("multisig_of", [0x62, 0x0F, 0xEF, 0x4E]),
+        ("reject_delegation", [0xCD, 0x68, 0x82, 0xBF]),
+        ("revoke_delegation", [0xB2, 0x30, 0x56, 0x5F]),
+        ("expire_delegation", [0x54, 0x7E, 0xA5, 0x17]),
+        ("transfer_product", [0x92, 0x14, 0x52, 0x48]),
+        ("set_metadata_uri", [0xA3, 0xBA, 0x34, 0x10]),
+        ("update_metadata", [0x94, 0x6D, 0xE4, 0xAE]),
+        ("freeze_metadata", [0xD8, 0xB6, 0xF2, 0xAD]),
+        ("get_metadata_uri", [0xCF, 0x83, 0x81, 0xB3]),
+        ("recall_product", [0xFB, 0xC4, 0x53, 0xC9]),
+        ("resolve_recall", [0xD6, 0x6A, 0x88, 0x71]),
+        ("freeze_product", [0x96, 0x04, 0x86, 0x57]),
+        ("unfreeze_product", [0x17, 0xC5, 0x4B, 0x53]),
+        ("split_product", [0x67, 0x25, 0x3E, 0x02]),
+        ("merge_products", [0x63, 0x87, 0xB0, 0xDF]),
+        ("assemble", [0xE9, 0x2A, 0x60, 0xF7]),
+        ("components_of", [0x57, 0x26, 0xDD, 0xAF]),
+        ("disassemble", [0xE9, 0x57, 0x66, 0x3C]),
+        ("set_royalty", [0x1C, 0x20, 0x86, 0xA1]),
+        ("update_code", [0x32, 0x30, 0x03, 0xBE]),
+        ("advance_stage", [0x84, 0xCC, 0x88, 0x54]),
+        ("set_warranty", [0xC4, 0xE6, 0xA1, 0x83]),
+        ("is_under_warranty", [0x52, 0x13, 0xB9, 0xA1]),
+        ("claim_warranty", [0x61, 0x4F, 0x30, 0xBC]),
+        ("warranty_claims_of", [0x54, 0xED, 0x88, 0xC8]),
+        ("add_co_owner", [0x76, 0x6B, 0x2B, 0xA9]),
+        ("transfer_share", [0xAB, 0x29, 0x53, 0x29]),
+        ("approve_action", [0x20, 0x22, 0xB5, 0xD3]),
+        ("share_of", [0x6C, 0x58, 0x8F, 0xC5]),
+        ("shares_of", [0xC0, 0x08, 0x6D, 0xF1]),
+        ("list_for_sale", [0xFB, 0x33, 0xFF, 0x5F]),
+        ("unlist", [0x5A, 0xEE, 0xB3, 0x15]),
+        ("buy_product", [0x5D, 0x2A, 0x29, 0x24]),
+        ("start_auction", [0x08, 0x3D, 0xE5, 0x7E]),
+        ("bid", [0x66, 0x8D, 0x28, 0xA7]),
+        ("finalize_auction", [0x28, 0xDD, 0x27, 0xB4]),
+        ("get_auction", [0x15, 0xA4, 0x1C, 0xB5]),
+        ("lend_product", [0x19, 0xAD, 0x4C, 0xFD]),
+        ("return_product", [0x94, 0x32, 0x48, 0x06]),
+        ("reclaim_product", [0x25, 0x84, 0x49, 0x38]),
+        ("burn_product", [0x36, 0x44, 0xA2, 0xD9]),
+        ("archive_product", [0xE8, 0x8B, 0x5E, 0xA1]),
+        ("unarchive_product", [0x79, 0x45, 0x71, 0xDD]),
+        ("pending_for", [0x79, 0x71, 0x9B, 0x8C]),
+        ("delegated_by", [0x04, 0x18, 0xB1, 0x22]),
+        ("count_of", [0x7F, 0x13, 0x8C, 0x4B]),
+        ("products_of", [0x08, 0x98, 0x07, 0x95]),
+        ("product_of_owner_by_index", [0x32, 0x21, 0x99, 0x4F]),
+        ("ids_of", [0xC1, 0x01, 0x5C, 0xFA]),
+        ("get_products_by_code", [0xA6, 0xDB, 0xC9, 0x74]),
+        ("history_len", [0x62, 0x39, 0x63, 0xFE]),
+        ("history_entry", [0xA9, 0x7E, 0x97, 0xEA]),
+        ("get_history", [0x7F, 0x9D, 0x5C, 0x65]),
+        ("add_checkpoint", [0x3D, 0x53, 0x7C, 0xA3]),
+        ("checkpoint_count", [0x7A, 0x29, 0xDD, 0xF4]),
+        ("checkpoint_at", [0x2B, 0xF6, 0xB3, 0x7A]),
+        ("add_note", [0x34, 0x93, 0xB4, 0xF2]),
+        ("note_count", [0x10, 0x27, 0x2E, 0xC4]),
+        ("note_at", [0xA1, 0x14, 0xB5, 0xB1]),
+        ("set_sensor", [0x8F, 0xA6, 0x7C, 0x25]),
+        ("sensor_of", [0x50, 0xFF, 0x61, 0x50]),
+        ("set_reading_range", [0xE4, 0xD9, 0x42, 0x81]),
+        ("record_reading", [0x5A, 0x8D, 0x95, 0xD2]),
+        ("reading_count", [0xBE, 0x73, 0x7B, 0x66]),
+        ("readings_of", [0xE9, 0x31, 0xB2, 0xEB]),
+        ("set_max_readings", [0xAB, 0xD3, 0x2B, 0x57]),
+        ("get_max_readings", [0xC5, 0x1B, 0x75, 0xA3]),
+        ("get_admin", [0x57, 0xB8, 0xA8, 0xA7]),
+        ("get_pending_admin", [0xBC, 0xD3, 0x1D, 0x76]),
+        ("transfer_admin", [0xD2, 0x6C, 0x8E, 0x14]),
+        ("accept_admin", [0x38, 0x30, 0x82, 0x1D]),
+        ("pause", [0x81, 0xE0, 0xC6, 0x04]),
+        ("unpause", [0x67, 0x61, 0x66, 0x49]),
+        ("is_paused", [0xFA, 0x7D, 0x50, 0x5B]),
+        ("get_schema_version", [0x2C, 0xD4, 0x63, 0x80]),
+        ("announce_termination", [0x8A, 0x30, 0xA5, 0x6C]),
+        ("terminate", [0x47, 0x6D, 0x83, 0x9F]),
+        ("set_registry", [0xA5, 0x5C, 0x4B, 0x84]),
+        ("get_registry", [0x15, 0xA5, 0xD2, 0x0A]),
+        ("set_registry_policy", [0x05, 0x13, 0x67, 0xB4]),
+        ("get_registry_policy", [0xCB, 0x9B, 0xF0, 0x60]),
+        ("add_manufacturer", [0x60, 0xF8, 0x4C, 0x1B]),
+        ("remove_manufacturer", [0xD6, 0x23, 0xCB, 0x5B]),
+        ("is_manufacturer", [0xBF, 0xE2, 0x5B, 0xF5]),
+        ("add_certifier", [0x4C, 0xDC, 0x9F, 0x8F]),
+        ("remove_certifier", [0xED, 0x04, 0xC1, 0x19]),
+        ("is_certifier", [0x85, 0x8D, 0xF0, 0x9C]),
+        ("certify", [0xF3, 0x82, 0x3E, 0x07]),
+        ("certifications_of", [0x01, 0x59, 0x95, 0xC8]),
+        ("revoke_certification", [0x5E, 0x59, 0x88, 0x6D]),
+        ("revoked_certifications_of", [0x68, 0xF6, 0x86, 0x59]),
+        ("set_arbiter", [0xB0, 0x3E, 0x70, 0x15]),
+        ("get_arbiter", [0x35, 0x23, 0x5F, 0x5C]),
+        ("raise_dispute", [0x97, 0x3C, 0x71, 0x00]),
+        ("resolve_dispute", [0x53, 0x9B, 0x8B, 0x08]),
+        ("set_oracle", [0xAD, 0x28, 0x34, 0x6F]),
+        ("get_oracle", [0x84, 0x89, 0x6D, 0x00]),
+        ("request_verification", [0x6A, 0x3F, 0xEC, 0x45]),
+        ("submit_verification", [0x1B, 0x0B, 0xAA, 0x6E]),
+        ("verification_of", [0x20, 0xBB, 0xC0, 0x38]),
+        ("set_blacklisted", [0x06, 0xD1, 0xF6, 0xED]),
+        ("is_blacklisted", [0x56, 0x63, 0xFE, 0x67]),
+        ("PSP34::balance_of", [0xCD, 0xE7, 0xE5, 0x5F]),
+        ("PSP34::owner_of", [0x11, 0x68, 0x62, 0x4D]),
+        ("PSP34::allowance", [0x47, 0x90, 0xF5, 0x5A]),
+        ("PSP34::approve", [0x19, 0x32, 0xA8, 0xB0]),
+        ("PSP34::transfer", [0x31, 0x28, 0xD6, 0x1B]),
+        ("PSP34::total_supply", [0x62, 0x84, 0x13, 0xFE]),
+        ("ProductRegistry::create_product", [0x16, 0x2B, 0x4B, 0x82]),
+        ("ProductRegistry::delegate_product", [0x78, 0x45, 0x16, 0x42]),
+        ("ProductRegistry::accept_product", [0xDE, 0x77, 0xD1, 0xB1]),
+        ("ProductRegistry::get_product", [0x45, 0x13, 0x1B, 0xE1]),
+        ("ProductRegistry::product_count", [0x86, 0x86, 0x16, 0x14]),
+    ];
+
+    fn selectors<'a>(specs: impl Iterator<Item = (&'a [String], &'a [u8])>) -> Vec<(String, Vec<u8>)> {
+        specs.map(|(name, selector)| (name.join("::"), selector.to_vec())).collect()
+    }
+
+    fn expected(table: &[(&str, [u8; 4])]) -> Vec<(String, Vec<u8>)> {
+        table.iter().map(|(name, selector)| (String::from(*name), selector.to_vec())).collect()
+    }
+
+    #[test]
+    fn selectors_are_pinned() {
+        let metadata = unsafe { __ink_generate_metadata() };
+        let spec = metadata.spec();
+        assert_eq!(
+            selectors(spec.constructors().iter().map(|c| (c.name(), c.selector().to_bytes()))),
+            expected(CONSTRUCTOR_SELECTORS)
+        );
+        assert_eq!(
+            selectors(spec.messages().iter().map(|m| (m.name(), m.selector().to_bytes()))),
+            expected(MESSAGE_SELECTORS)
+        );
     }
 }